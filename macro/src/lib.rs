@@ -1,51 +1,509 @@
 extern crate proc_macro;
 
-use proc_macro::{TokenStream, TokenTree, Literal};
-use quote::quote;
-use syn::{self, parse::Parse, Pat, parse_quote};
+use proc_macro::TokenStream;
+use quote::{quote, format_ident};
+use syn::{self, parse_quote};
 
 #[proc_macro_attribute]
 pub fn yarn_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(attr as syn::AttributeArgs);
     let mut funct = syn::parse_macro_input!(item as syn::ItemFn);
-    let funct_name = funct.sig.ident;
+    let funct_name = funct.sig.ident.clone();
+    let signature_name = format_ident!("{}_signature", funct_name);
 
-    let mut param_names = Vec::new();
+    let pure = has_flag(&args, "pure");
+    let doc = doc_comment(&funct.attrs);
 
-    for params in funct.sig.inputs.iter() {
-        match params {
-            syn::FnArg::Receiver(_) => todo!(),
+    let mut params = Vec::new();
+    let mut context_params = Vec::new();
+
+    for param in funct.sig.inputs.iter() {
+        match param {
+            syn::FnArg::Receiver(receiver) => {
+                return syn::Error::new_spanned(receiver, "Methods are registered by putting #[yarn_library] on their impl block.").to_compile_error().into();
+            },
+            syn::FnArg::Typed(p) if is_context_type(&p.ty) => context_params.push(((*p.pat).clone(), (*p.ty).clone())),
+            syn::FnArg::Typed(p) => params.push(((*p.pat).clone(), (*p.ty).clone())),
+        }
+    }
+
+    let arguments = match YarnArguments::new(&params, &context_params, None) {
+        Ok(arguments) => arguments,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let signature = arguments.signature(&funct.sig.output, pure, &doc);
+    let mut stmts : Vec<syn::Stmt> = vec![
+        parse_quote!(let line = context.line();),
+        parse_quote!(let col = context.col();),
+    ];
+    stmts.extend(arguments.stmts);
+
+    //Without a return type the body returns the YarnResult itself, otherwise the value is converted for it.
+    let block = &funct.block;
+    match &funct.sig.output {
+        syn::ReturnType::Default => stmts.extend(block.stmts.iter().cloned()),
+        syn::ReturnType::Type(_, ty) => {
+            stmts.push(parse_quote!(
+                #[allow(clippy::redundant_closure_call)]
+                let result : #ty = (|| #block)();
+            ));
+            stmts.push(syn::Stmt::Expr(parse_quote!(::yarn_spinner_compiler::value::YarnReturn::into_return(result))));
+        },
+    }
+
+    funct.sig = parse_quote!(fn #funct_name(params : Vec<::yarn_spinner_compiler::value::YarnValue>, context : &::yarn_spinner_compiler::parcer::YarnFunctionContext) -> ::yarn_spinner_compiler::error::YarnResult<Option<::yarn_spinner_compiler::value::YarnValue>>);
+    funct.block.stmts = stmts;
+
+    let vis = &funct.vis;
+    quote!(
+        #funct
+
+        #vis fn #signature_name() -> ::yarn_spinner_compiler::parcer::YarnFunctionSignature {
+            #signature
+        }
+    ).into()
+}
+
+#[proc_macro_attribute]
+pub fn yarn_library(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(attr as syn::AttributeArgs);
+    let mut library = syn::parse_macro_input!(item as syn::ItemImpl);
+
+    let namespace = string_arg(&args, "namespace");
+
+    let mut registrations : Vec<syn::Stmt> = Vec::new();
+    let mut shared = false;
+
+    for item in library.items.iter_mut() {
+        let method = match item {
+            syn::ImplItem::Method(method) if matches!(method.vis, syn::Visibility::Public(_)) => method,
+            _ => continue,
+        };
+
+        //#[yarn_function(pure)] on a method only marks it pure, the method itself is left as written.
+        let mut pure = false;
+        method.attrs.retain(|attr| {
+            if !attr.path.is_ident("yarn_function") {
+                return true;
+            }
+            if let Ok(syn::Meta::List(list)) = attr.parse_meta() {
+                pure = list.nested.iter().any(|arg| matches!(arg, syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("pure")));
+            }
+            false
+        });
+
+        let mut receiver = false;
+        let mut params = Vec::new();
+        let mut call_args : Vec<syn::Expr> = Vec::new();
+
+        for (index, param) in method.sig.inputs.iter().enumerate() {
+            match param {
+                syn::FnArg::Receiver(r) if r.reference.is_some() && r.mutability.is_none() => receiver = true,
+                syn::FnArg::Receiver(r) => {
+                    return syn::Error::new_spanned(r, "Yarn library methods can only take &self, use interior mutability for state they change.").to_compile_error().into();
+                },
+                syn::FnArg::Typed(p) if is_context_type(&p.ty) => call_args.push(parse_quote!(context)),
+                syn::FnArg::Typed(p) => {
+                    let pat = format_ident!("argument_{}", index);
+                    params.push((parse_quote!(#pat), (*p.ty).clone()));
+                    call_args.push(parse_quote!(#pat));
+                },
+            }
+        }
+
+        let arguments = match YarnArguments::new(&params, &[], None) {
+            Ok(arguments) => arguments,
+            Err(error) => return error.to_compile_error().into(),
+        };
+        let output = match &method.sig.output {
+            syn::ReturnType::Default => parse_quote!(-> ()),
+            output => output.clone(),
+        };
+        let signature = arguments.signature(&output, pure, &doc_comment(&method.attrs));
+        let stmts = arguments.stmts;
+
+        let method_name = &method.sig.ident;
+        let name = match &namespace {
+            Some(namespace) => format!("{}_{}", namespace, method_name),
+            None => method_name.to_string(),
+        };
+        let call : syn::Expr = match receiver {
+            true => parse_quote!(library.#method_name(#(#call_args),*)),
+            false => parse_quote!(Self::#method_name(#(#call_args),*)),
+        };
+        let capture : Option<syn::Stmt> = match receiver {
+            true => Some(parse_quote!(let library = library.clone();)),
+            false => None,
+        };
+        shared |= receiver;
+
+        registrations.push(parse_quote!({
+            #capture
+            functions.insert(#name.to_string(), ::yarn_spinner_compiler::parcer::YarnRegisteredFunction::new(
+                move |params : Vec<::yarn_spinner_compiler::value::YarnValue>, context : &::yarn_spinner_compiler::parcer::YarnFunctionContext| -> ::yarn_spinner_compiler::error::YarnResult<Option<::yarn_spinner_compiler::value::YarnValue>> {
+                    let line = context.line();
+                    let col = context.col();
+                    #(#stmts)*
+                    ::yarn_spinner_compiler::value::YarnReturn::into_return(#call)
+                },
+                Some(#signature)
+            ));
+        }));
+    }
+
+    //Methods taking &self all share the one library value.
+    let library_value : Option<syn::Stmt> = match shared {
+        true => Some(parse_quote!(let library = std::sync::Arc::new(self);)),
+        false => None,
+    };
+
+    library.items.push(parse_quote!(
+        pub fn register(self, functions : &mut ::yarn_spinner_compiler::parcer::YarnFunctionMap) {
+            #library_value
+            #(#registrations)*
+        }
+    ));
+
+    quote!(#library).into()
+}
+
+#[proc_macro_attribute]
+pub fn yarn_command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(attr as syn::AttributeArgs);
+    let mut funct = syn::parse_macro_input!(item as syn::ItemFn);
+    let funct_name = funct.sig.ident.clone();
+    let usage_name = format_ident!("{}_usage", funct_name);
+    let command_name = string_arg(&args, "name").unwrap_or_else(|| funct_name.to_string());
+
+    let mut params = Vec::new();
+    let mut context_params = Vec::new();
+    let mut usage = format!("<<{}", command_name);
+
+    for param in funct.sig.inputs.iter() {
+        match param {
+            syn::FnArg::Receiver(receiver) => {
+                return syn::Error::new_spanned(receiver, "A yarn command must be a free function.").to_compile_error().into();
+            },
+            syn::FnArg::Typed(p) if is_context_type(&p.ty) => context_params.push(((*p.pat).clone(), (*p.ty).clone())),
             syn::FnArg::Typed(p) => {
-                match &*p.pat {
-                    Pat::Ident(id) => {
-                        param_names.push(id.ident.clone())
-                    },
-                    _ => todo!(),
+                let name = match &*p.pat {
+                    syn::Pat::Ident(ident) => ident.ident.to_string(),
+                    _ => "_".to_string(),
+                };
+                if wrapped_type(&p.ty, "Option").is_some() {
+                    usage.push_str(format!(" [{}]", name).as_str());
+                } else if wrapped_type(&p.ty, "Vec").is_some() {
+                    usage.push_str(format!(" [{}...]", name).as_str());
+                } else {
+                    usage.push_str(format!(" {}", name).as_str());
                 }
+                params.push(((*p.pat).clone(), (*p.ty).clone()));
             },
         }
     }
+    usage.push_str(">>");
 
-    funct.sig = parse_quote!(fn #funct_name(params : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>>);
+    let arguments = match YarnArguments::new(&params, &context_params, Some(usage.as_str())) {
+        Ok(arguments) => arguments,
+        Err(error) => return error.to_compile_error().into(),
+    };
 
-    for (index, param_name) in param_names.iter().enumerate() {
-        //let  = quote!("index");
-        funct.block.stmts.insert(
-            0 + index, 
-            syn::parse_quote!(
-                let #param_name = params.get(#index).unwrap();
-            )
-        )
-    }
+    //The text arguments are read as the types of the parameters first, so they convert like function arguments.
+    let value_types = arguments.required_types.iter().chain(arguments.optional_types.iter());
+    let variadic = match &arguments.variadic_type {
+        Some(ty) => quote!(Some(<#ty as ::yarn_spinner_compiler::value::YarnArgument>::value_type())),
+        None => quote!(None),
+    };
+    let mut stmts : Vec<syn::Stmt> = vec![
+        parse_quote!(let line = context.line();),
+        parse_quote!(let col = context.col();),
+        parse_quote!(
+            let params = match ::yarn_spinner_compiler::command::command_values(&arguments, &[#(<#value_types as ::yarn_spinner_compiler::value::YarnArgument>::value_type()),*], #variadic) {
+                Some(params) => params,
+                None => return Err(::yarn_spinner_compiler::error::YarnError::new_command_usage_error(line, col, #usage)),
+            };
+        ),
+    ];
+    stmts.extend(arguments.stmts);
 
+    let block = &funct.block;
+    let return_type : syn::Type = match &funct.sig.output {
+        syn::ReturnType::Default => parse_quote!(()),
+        syn::ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+    stmts.push(parse_quote!(
+        #[allow(clippy::redundant_closure_call)]
+        let result : #return_type = (|| #block)();
+    ));
+    stmts.push(syn::Stmt::Expr(parse_quote!(::yarn_spinner_compiler::command::YarnCommandReturn::into_command_result(result))));
+
+    funct.sig = parse_quote!(fn #funct_name(arguments : Vec<String>, context : &::yarn_spinner_compiler::parcer::YarnFunctionContext) -> ::yarn_spinner_compiler::error::YarnResult<::yarn_spinner_compiler::command::YarnCommandStatus>);
+    funct.block.stmts = stmts;
+
+    let vis = &funct.vis;
     quote!(
         #funct
-    ).into()    
+
+        #vis fn #usage_name() -> &'static str {
+            #usage
+        }
+    ).into()
+}
+
+#[proc_macro_derive(YarnEnum)]
+pub fn yarn_enum(item: TokenStream) -> TokenStream {
+    let item = syn::parse_macro_input!(item as syn::DeriveInput);
+    let enum_ident = &item.ident;
+    let enum_name = enum_ident.to_string();
+
+    let variants = match &item.data {
+        syn::Data::Enum(data) => &data.variants,
+        _ => return syn::Error::new_spanned(&item.ident, "YarnEnum can only be derived for enums.").to_compile_error().into(),
+    };
+
+    if variants.is_empty() {
+        return syn::Error::new_spanned(&item.ident, "A yarn enum needs at least one case.").to_compile_error().into();
+    }
+
+    if let Some(variant) = variants.iter().find(|variant| !matches!(variant.fields, syn::Fields::Unit)) {
+        return syn::Error::new_spanned(variant, "The cases of a yarn enum can not have fields.").to_compile_error().into();
+    }
+
+    let idents = variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+    let cases = idents.iter().map(|ident| ident.to_string()).collect::<Vec<_>>();
+
+    quote!(
+        impl ::yarn_spinner_compiler::value::YarnEnum for #enum_ident {
+            fn enum_name() -> &'static str {
+                #enum_name
+            }
+
+            fn cases() -> &'static [&'static str] {
+                &[#(#cases),*]
+            }
+
+            fn from_case(case : &str) -> Option<Self> {
+                match case {
+                    #(#cases => Some(#enum_ident::#idents),)*
+                    _ => None,
+                }
+            }
+
+            fn case(&self) -> &'static str {
+                match self {
+                    #(#enum_ident::#idents => #cases,)*
+                }
+            }
+        }
+
+        impl From<#enum_ident> for ::yarn_spinner_compiler::value::YarnValue {
+            fn from(value : #enum_ident) -> Self {
+                ::yarn_spinner_compiler::value::YarnEnum::to_value(&value)
+            }
+        }
+
+        impl TryFrom<::yarn_spinner_compiler::value::YarnValue> for #enum_ident {
+            type Error = ::yarn_spinner_compiler::error::YarnError;
+
+            fn try_from(value : ::yarn_spinner_compiler::value::YarnValue) -> ::yarn_spinner_compiler::error::YarnResult<Self> {
+                <#enum_ident as ::yarn_spinner_compiler::value::YarnEnum>::from_value(&value, 0, 0)
+            }
+        }
+
+        impl <'a> ::yarn_spinner_compiler::value::YarnArgument<'a> for #enum_ident {
+            fn from_argument(value : &'a ::yarn_spinner_compiler::value::YarnValue, line : usize, col : usize) -> ::yarn_spinner_compiler::error::YarnResult<Self> {
+                <#enum_ident as ::yarn_spinner_compiler::value::YarnEnum>::from_value(value, line, col)
+            }
+
+            fn value_type() -> ::yarn_spinner_compiler::value::YarnValueType {
+                ::yarn_spinner_compiler::value::YarnValueType::ENUM(#enum_name.to_string())
+            }
+        }
+
+        impl ::yarn_spinner_compiler::value::YarnReturn for #enum_ident {
+            fn into_return(self) -> ::yarn_spinner_compiler::error::YarnResult<Option<::yarn_spinner_compiler::value::YarnValue>> {
+                Ok(Some(::yarn_spinner_compiler::value::YarnEnum::to_value(&self)))
+            }
+
+            fn return_type() -> ::yarn_spinner_compiler::value::YarnValueType {
+                ::yarn_spinner_compiler::value::YarnValueType::ENUM(#enum_name.to_string())
+            }
+        }
+    ).into()
 }
 
-struct YarnFunctionMacroInput {}
+//The conversion of a call's arguments into typed parameters, along with the types making up its signature.
+struct YarnArguments {
+    stmts : Vec<syn::Stmt>,
+    required_types : Vec<syn::Type>,
+    optional_types : Vec<syn::Type>,
+    variadic_type : Option<syn::Type>,
+}
+
+impl YarnArguments {
+    //Commands have a usage to show instead of the errors a function call gives for wrong arguments.
+    fn new(params : &[(syn::Pat, syn::Type)], context_params : &[(syn::Pat, syn::Type)], usage : Option<&str>) -> syn::Result<YarnArguments> {
+        let count_error = |expected : usize| -> syn::Expr {
+            match usage {
+                Some(usage) => parse_quote!(::yarn_spinner_compiler::error::YarnError::new_command_usage_error(line, col, #usage)),
+                None => parse_quote!(::yarn_spinner_compiler::error::YarnError::new_invalid_argument_count_error(line, col, #expected, params.len())),
+            }
+        };
+        let convert = |ty : &syn::Type, value : syn::Expr| -> syn::Expr {
+            match usage {
+                Some(usage) => parse_quote!(<#ty as ::yarn_spinner_compiler::value::YarnArgument>::from_argument(#value, line, col)
+                    .map_err(|_| ::yarn_spinner_compiler::error::YarnError::new_command_usage_error(line, col, #usage))),
+                None => parse_quote!(<#ty as ::yarn_spinner_compiler::value::YarnArgument>::from_argument(#value, line, col)),
+            }
+        };
+
+        //Option<T> parameters may be left off the call and a trailing Vec<T> takes all remaining arguments.
+        let mut required_types = Vec::new();
+        let mut optional_types = Vec::new();
+        let mut variadic_type = None;
+        let mut arguments : Vec<syn::Stmt> = Vec::new();
+
+        for (index, (pat, ty)) in params.iter().enumerate() {
+            if variadic_type.is_some() {
+                return Err(syn::Error::new_spanned(ty, "A Vec parameter must be the last parameter of a yarn function."));
+            }
+
+            if let Some(inner) = wrapped_type(ty, "Option") {
+                let conversion = convert(&inner, parse_quote!(value));
+                arguments.push(parse_quote!(
+                    let #pat : #ty = match params.get(#index) {
+                        Some(value) => Some(#conversion?),
+                        None => None,
+                    };
+                ));
+                optional_types.push(inner);
+            } else if let Some(inner) = wrapped_type(ty, "Vec") {
+                let conversion = convert(&inner, parse_quote!(value));
+                arguments.push(parse_quote!(
+                    let #pat : #ty = params.iter()
+                        .skip(#index)
+                        .map(|value| #conversion)
+                        .collect::<::yarn_spinner_compiler::error::YarnResult<#ty>>()?;
+                ));
+                variadic_type = Some(inner);
+            } else if !optional_types.is_empty() {
+                return Err(syn::Error::new_spanned(ty, "A required parameter can not follow an optional parameter."));
+            } else {
+                let conversion = convert(ty, parse_quote!(&params[#index]));
+                arguments.push(parse_quote!(
+                    let #pat : #ty = #conversion?;
+                ));
+                required_types.push(ty.clone());
+            }
+        }
+
+        let required_count = required_types.len();
+        let total_count = required_count + optional_types.len();
+        let mut stmts : Vec<syn::Stmt> = Vec::new();
+
+        //Bound with the type the parameter was written with, so its import is still used.
+        for (pat, ty) in context_params.iter() {
+            stmts.push(parse_quote!(let #pat : #ty = context;));
+        }
 
-impl Parse for YarnFunctionMacroInput {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        todo!()
+        let too_few = count_error(required_count);
+        stmts.push(parse_quote!(
+            if params.len() < #required_count {
+                return Err(#too_few);
+            }
+        ));
+
+        if variadic_type.is_none() {
+            let too_many = count_error(total_count);
+            stmts.push(parse_quote!(
+                if params.len() > #total_count {
+                    return Err(#too_many);
+                }
+            ));
+        }
+
+        stmts.extend(arguments);
+
+        Ok(YarnArguments { stmts, required_types, optional_types, variadic_type })
+    }
+
+    fn signature(&self, output : &syn::ReturnType, pure : bool, doc : &str) -> syn::Expr {
+        let required_types = &self.required_types;
+        let optional_types = &self.optional_types;
+        let return_type = match output {
+            syn::ReturnType::Default => quote!(::yarn_spinner_compiler::value::YarnValueType::ANY),
+            syn::ReturnType::Type(_, ty) => quote!(<#ty as ::yarn_spinner_compiler::value::YarnReturn>::return_type()),
+        };
+        let variadic = match &self.variadic_type {
+            Some(ty) => quote!(.with_variadic(<#ty as ::yarn_spinner_compiler::value::YarnArgument>::value_type())),
+            None => quote!(),
+        };
+
+        parse_quote!(
+            ::yarn_spinner_compiler::parcer::YarnFunctionSignature::new(vec![#(<#required_types as ::yarn_spinner_compiler::value::YarnArgument>::value_type()),*], #return_type)
+                .with_optional_parameters(vec![#(<#optional_types as ::yarn_spinner_compiler::value::YarnArgument>::value_type()),*])
+                #variadic
+                .with_purity(#pure)
+                .with_doc(#doc)
+        )
+    }
+}
+
+fn string_arg(args : &[syn::NestedMeta], name : &str) -> Option<String> {
+    args.iter().find_map(|arg| match arg {
+        syn::NestedMeta::Meta(syn::Meta::NameValue(meta)) if meta.path.is_ident(name) => match &meta.lit {
+            syn::Lit::Str(lit) => Some(lit.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn has_flag(args : &[syn::NestedMeta], flag : &str) -> bool {
+    args.iter().any(|arg| matches!(arg, syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident(flag)))
+}
+
+//The /// comments on a function become the doc of its signature.
+fn doc_comment(attrs : &[syn::Attribute]) -> String {
+    attrs.iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(meta)) if meta.path.is_ident("doc") => match meta.lit {
+                syn::Lit::Str(lit) => Some(lit.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+//A parameter typed &YarnFunctionContext is given the call context instead of an argument.
+fn is_context_type(ty : &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(reference) => match &*reference.elem {
+            syn::Type::Path(path) => path.path.segments.last().map(|segment| segment.ident == "YarnFunctionContext").unwrap_or(false),
+            _ => false,
+        },
+        _ => false,
     }
-}
\ No newline at end of file
+}
+
+//The T of a parameter typed wrapper<T>, such as Option<T> or Vec<T>.
+fn wrapped_type(ty : &syn::Type, wrapper : &str) -> Option<syn::Type> {
+    let segment = match ty {
+        syn::Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(arguments) => match arguments.args.first() {
+            Some(syn::GenericArgument::Type(inner)) => Some(inner.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
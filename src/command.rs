@@ -0,0 +1,231 @@
+use std::{collections::HashMap, sync::Arc};
+
+use yarn_spinner_macros::yarn_command;
+
+use crate::{error::YarnResult, value::{YarnValue, YarnValueType}, parcer::YarnFunctionContext};
+
+//Handles a command for the game. It is given the arguments after the command's name.
+pub type YarnCommandHandler = Arc<dyn Fn(Vec<String>, &YarnFunctionContext) -> YarnResult<YarnCommandStatus> + Send + Sync>;
+
+pub type YarnCommandMap = HashMap<String, YarnCommandHandler>;
+
+//Whether the dialogue can carry on once a command handler returns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YarnCommandStatus {
+    COMPLETE,
+    PENDING, // Paused until the game calls resume
+    WAIT(f64) // Paused for this many seconds of update
+}
+
+//Commands the dialogue handles itself unless the game registers a handler with the same name.
+pub fn builtin_command(name : &str) -> Option<YarnCommandHandler> {
+    match name {
+        "wait" => Some(Arc::new(wait)),
+        _ => None,
+    }
+}
+
+#[yarn_command]
+fn wait(seconds : f64) -> YarnCommandStatus {
+    YarnCommandStatus::WAIT(seconds)
+}
+
+//A command the dialogue ran, after its expressions were filled in.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnCommand {
+    text : String,
+    name : String,
+    arguments : Vec<String>
+}
+
+impl YarnCommand {
+    pub fn new(text : &str) -> YarnCommand {
+        let mut arguments = split_command(text);
+        let name = if arguments.is_empty() { String::new() } else { arguments.remove(0) };
+        YarnCommand { text: text.trim().to_string(), name, arguments }
+    }
+
+    //Splits the text as it was written before filling in the {0}, {1}, ... placeholders of each argument, so a value
+    //with spaces or quotes in it stays the one argument it was written as.
+    pub fn interpolated(text : &str, values : &[YarnValue]) -> YarnCommand {
+        let mut arguments = split_command(text).iter().map(|argument| interpolate(argument, values)).collect::<Vec<String>>();
+        let name = if arguments.is_empty() { String::new() } else { arguments.remove(0) };
+        YarnCommand { text: interpolate(text, values).trim().to_string(), name, arguments }
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn arguments(&self) -> &Vec<String> {
+        &self.arguments
+    }
+}
+
+//Fills the {0}, {1}, ... placeholders a command was compiled with with the values of its expressions.
+pub fn interpolate(text : &str, values : &[YarnValue]) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after.find('}').and_then(|end| after[..end].parse::<usize>().ok().map(|index| (index, end)));
+
+        match placeholder {
+            Some((index, end)) if index < values.len() => {
+                result.push_str(values[index].to_string().as_str());
+                rest = &after[end + 1..];
+            },
+            _ => {
+                result.push('{');
+                rest = after;
+            },
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+//Splits the text of a command into its name and arguments. Arguments are split on whitespace unless they are
+//wrapped in double quotes, where \" and \\ can be used for a quote or a backslash.
+pub fn split_command(text : &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_argument = false;
+    let mut chars = text.trim().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_argument = true;
+            },
+            '\\' if quoted => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            },
+            c if c.is_whitespace() && !quoted => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            },
+            c => {
+                current.push(c);
+                in_argument = true;
+            },
+        }
+    }
+
+    if in_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+//Reads the text arguments of a command as the types its handler declares. Anything after the declared types uses
+//the variadic type. Returns None if an argument can not be read as its type.
+pub fn command_values(arguments : &[String], types : &[YarnValueType], variadic : Option<YarnValueType>) -> Option<Vec<YarnValue>> {
+    arguments.iter().enumerate()
+        .map(|(index, argument)| {
+            let value_type = types.get(index).cloned().or_else(|| variadic.clone()).unwrap_or(YarnValueType::ANY);
+            command_value(argument, value_type)
+        })
+        .collect()
+}
+
+fn command_value(argument : &str, value_type : YarnValueType) -> Option<YarnValue> {
+    match value_type {
+        YarnValueType::STRING => Some(YarnValue::STRING(argument.to_string())),
+        YarnValueType::NUMBER => argument.parse::<f64>().ok().filter(|number| number.is_finite()).map(YarnValue::NUMBER),
+        YarnValueType::BOOL => match argument {
+            "true" => Some(YarnValue::BOOL(true)),
+            "false" => Some(YarnValue::BOOL(false)),
+            _ => None,
+        },
+        YarnValueType::ANY => Some(YarnValue::from(argument)),
+        YarnValueType::ENUM(name) => YarnValue::parse_enum(argument).filter(|value| value.get_type() == YarnValueType::ENUM(name)),
+    }
+}
+
+//What a command handler may return. Handlers that return nothing complete straight away.
+pub trait YarnCommandReturn {
+    fn into_command_result(self) -> YarnResult<YarnCommandStatus>;
+}
+
+impl YarnCommandReturn for () {
+    fn into_command_result(self) -> YarnResult<YarnCommandStatus> {
+        Ok(YarnCommandStatus::COMPLETE)
+    }
+}
+
+impl YarnCommandReturn for YarnCommandStatus {
+    fn into_command_result(self) -> YarnResult<YarnCommandStatus> {
+        Ok(self)
+    }
+}
+
+impl <T : YarnCommandReturn> YarnCommandReturn for YarnResult<T> {
+    fn into_command_result(self) -> YarnResult<YarnCommandStatus> {
+        self?.into_command_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(split_command("walk Mae left 3"), vec!["walk", "Mae", "left", "3"]);
+        assert_eq!(split_command("  say   \"Hello there\" loud "), vec!["say", "Hello there", "loud"]);
+        assert_eq!(split_command("say \"\" \"a \\\"quote\\\"\""), vec!["say", "", "a \"quote\""]);
+        assert!(split_command("").is_empty());
+    }
+
+    #[test]
+    fn test_command() {
+        let command = YarnCommand::interpolated("walk {0} \"{1}\" {2}", &[YarnValue::STRING("Mae".to_string()), YarnValue::NUMBER(2.5), YarnValue::BOOL(true)]);
+        assert_eq!(command.text(), "walk Mae \"2.5\" true");
+        assert_eq!(command.name(), "walk");
+        assert_eq!(command.arguments(), &vec!["Mae", "2.5", "true"]);
+        assert_eq!(YarnCommand::interpolated("say {0}", &[YarnValue::NUMBER(3.0)]).arguments(), &vec!["3"]);
+
+        let name = YarnValue::STRING("Mae \"Maggie\" Borowski".to_string());
+        assert_eq!(YarnCommand::interpolated("say {0} \"hi {0}\" {1}", &[name, YarnValue::STRING(String::new())]).arguments(), &vec![
+            "Mae \"Maggie\" Borowski",
+            "hi Mae \"Maggie\" Borowski",
+            "",
+        ]);
+        assert_eq!(interpolate("{0}{1}", &[YarnValue::STRING("{1}".to_string()), YarnValue::STRING("a".to_string())]), "{1}a");
+    }
+
+    #[test]
+    fn test_command_values() {
+        let arguments = split_command("Mae 3 true 4 5");
+        let types = [YarnValueType::STRING, YarnValueType::NUMBER, YarnValueType::BOOL];
+        assert_eq!(command_values(&arguments, &types, Some(YarnValueType::NUMBER)), Some(vec![
+            YarnValue::STRING("Mae".to_string()),
+            YarnValue::NUMBER(3.0),
+            YarnValue::BOOL(true),
+            YarnValue::NUMBER(4.0),
+            YarnValue::NUMBER(5.0),
+        ]));
+        assert_eq!(command_values(&split_command("3 Mae"), &[YarnValueType::STRING, YarnValueType::ANY], None), Some(vec![
+            YarnValue::STRING("3".to_string()),
+            YarnValue::STRING("Mae".to_string()),
+        ]));
+        assert_eq!(command_values(&split_command("Mae left"), &types, None), None);
+        assert_eq!(command_values(&split_command("nan inf"), &[], Some(YarnValueType::NUMBER)), None);
+    }
+}
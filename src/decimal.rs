@@ -0,0 +1,140 @@
+use std::fmt::{self, Display};
+
+pub const DECIMAL_PLACES : u32 = 6;
+const SCALE : i64 = 1_000_000;
+
+//A fixed-point number with DECIMAL_PLACES digits after the point, for maths such as money that has to come out the
+//same every time. Results are rounded half away from zero to the last place, and None means the result did not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnDecimal {
+    units : i64 // Millionths
+}
+
+impl YarnDecimal {
+    pub fn from_units(units : i64) -> YarnDecimal {
+        YarnDecimal { units }
+    }
+
+    pub fn units(self) -> i64 {
+        self.units
+    }
+
+    pub fn from_int(value : i64) -> Option<YarnDecimal> {
+        value.checked_mul(SCALE).map(YarnDecimal::from_units)
+    }
+
+    pub fn from_f64(value : f64) -> Option<YarnDecimal> {
+        let units = (value * SCALE as f64).round();
+        if units.is_finite() && units.abs() < i64::MAX as f64 {
+            Some(YarnDecimal::from_units(units as i64))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.units as f64 / SCALE as f64
+    }
+
+    //Only when there is nothing after the point.
+    pub fn to_int(self) -> Option<i64> {
+        if self.units % SCALE == 0 {
+            Some(self.units / SCALE)
+        } else {
+            None
+        }
+    }
+
+    //Drops everything after the point.
+    pub fn trunc(self) -> i64 {
+        self.units / SCALE
+    }
+
+    pub fn checked_add(self, other : YarnDecimal) -> Option<YarnDecimal> {
+        self.units.checked_add(other.units).map(YarnDecimal::from_units)
+    }
+
+    pub fn checked_sub(self, other : YarnDecimal) -> Option<YarnDecimal> {
+        self.units.checked_sub(other.units).map(YarnDecimal::from_units)
+    }
+
+    pub fn checked_mul(self, other : YarnDecimal) -> Option<YarnDecimal> {
+        divide_rounded(self.units as i128 * other.units as i128, SCALE as i128).map(YarnDecimal::from_units)
+    }
+
+    pub fn checked_div(self, other : YarnDecimal) -> Option<YarnDecimal> {
+        if other.units == 0 {
+            return None;
+        }
+        divide_rounded(self.units as i128 * SCALE as i128, other.units as i128).map(YarnDecimal::from_units)
+    }
+
+    pub fn checked_rem(self, other : YarnDecimal) -> Option<YarnDecimal> {
+        self.units.checked_rem(other.units).map(YarnDecimal::from_units)
+    }
+
+    pub fn abs(self) -> Option<YarnDecimal> {
+        self.units.checked_abs().map(YarnDecimal::from_units)
+    }
+
+    pub fn round_places(self, places : u32) -> Option<YarnDecimal> {
+        if places >= DECIMAL_PLACES {
+            return Some(self);
+        }
+        let step = 10i64.pow(DECIMAL_PLACES - places);
+        divide_rounded(self.units as i128, step as i128).and_then(|steps| steps.checked_mul(step)).map(YarnDecimal::from_units)
+    }
+}
+
+fn divide_rounded(numerator : i128, denominator : i128) -> Option<i64> {
+    let mut quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if remainder.abs() * 2 >= denominator.abs() {
+        quotient += numerator.signum() * denominator.signum();
+    }
+    i64::try_from(quotient).ok()
+}
+
+//Written without trailing zeros, so 1.500000 is "1.5" and 2.000000 is "2".
+impl Display for YarnDecimal {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let whole = self.units.unsigned_abs() / SCALE as u64;
+        let fraction = self.units.unsigned_abs() % SCALE as u64;
+
+        if fraction == 0 {
+            f.pad(format!("{}{}", sign, whole).as_str())
+        } else {
+            let digits = format!("{:06}", fraction);
+            f.pad(format!("{}{}.{}", sign, whole, digits.trim_end_matches('0')).as_str())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        let cents = |value : f64| YarnDecimal::from_f64(value).unwrap();
+
+        assert_eq!(cents(0.1).checked_add(cents(0.2)), Some(cents(0.3)));
+        assert_eq!(cents(19.99).checked_mul(cents(3.0)), Some(cents(59.97)));
+        assert_eq!(cents(10.0).checked_div(cents(3.0)), Some(YarnDecimal::from_units(3_333_333)));
+        assert_eq!(cents(-2.0).checked_div(cents(3.0)), Some(YarnDecimal::from_units(-666_667)));
+        assert_eq!(cents(1.0).checked_div(cents(0.0)), None);
+        assert_eq!(YarnDecimal::from_units(i64::MAX).checked_add(cents(1.0)), None);
+        assert_eq!(YarnDecimal::from_int(i64::MAX), None);
+
+        assert_eq!(cents(2.675).round_places(2), Some(cents(2.68)));
+        assert_eq!(cents(-2.675).round_places(2), Some(cents(-2.68)));
+        assert_eq!(cents(2.5).to_int(), None);
+        assert_eq!(cents(-4.0).to_int(), Some(-4));
+
+        assert_eq!(cents(1.5).to_string(), "1.5");
+        assert_eq!(cents(-0.05).to_string(), "-0.05");
+        assert_eq!(cents(2.0).to_string(), "2");
+    }
+}
@@ -5,6 +5,7 @@ use crate::value::YarnValue;
 pub type YarnResult<T> = Result<T, YarnError>;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct YarnError {
     error_name : String,
     error_message : String,
@@ -127,6 +128,168 @@ impl YarnError {
         }
     }
 
+    pub fn new_invalid_argument_count_error(line : usize, col : usize, count_expected : usize, count_recieved : usize) -> Self {
+        YarnError { 
+            error_name: "Invalid Argument Count Error".to_string(), 
+            error_message: format!("The function was called with the wrong number of arguments. Arguments expected: {} | Arguments received: {}", count_expected, count_recieved), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_missing_title_error(line : usize, col : usize) -> Self {
+        YarnError { 
+            error_name: "Missing Title Error".to_string(), 
+            error_message: "Every node must have a title header before the '---'.".to_string(), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_node_not_found_error(line : usize, col : usize, title : &str) -> Self {
+        YarnError { 
+            error_name: "Node Not Found Error".to_string(), 
+            error_message: format!("There is no node with the title '{}'.", title), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_duplicate_line_id_error(line : usize, col : usize, id : &str) -> Self {
+        YarnError { 
+            error_name: "Duplicate Line Id Error".to_string(), 
+            error_message: format!("The line id '{}' is used by more than one line.", id), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_line_not_found_error(id : &str) -> Self {
+        YarnError { 
+            error_name: "Line Not Found Error".to_string(), 
+            error_message: format!("There is no line with the id '{}' in the program.", id), 
+            col: 0, 
+            line: 0
+        }
+    }
+
+    pub fn new_invalid_option_error(index : usize) -> Self {
+        YarnError { 
+            error_name: "Invalid Option Error".to_string(), 
+            error_message: format!("Option {} cannot be selected right now.", index), 
+            col: 0, 
+            line: 0
+        }
+    }
+
+    pub fn new_invalid_program_error(reason : &str) -> Self {
+        YarnError { 
+            error_name: "Invalid Program Error".to_string(), 
+            error_message: format!("The compiled program could not be read. {}", reason), 
+            col: 0, 
+            line: 0
+        }
+    }
+
+    pub fn new_function_error(line : usize, col : usize, message : &str) -> Self {
+        YarnError { 
+            error_name: "Function Error".to_string(), 
+            error_message: message.to_string(), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_not_waiting_error() -> Self {
+        YarnError { 
+            error_name: "Not Waiting Error".to_string(), 
+            error_message: "The dialogue is not waiting for a command to finish.".to_string(), 
+            col: 0, 
+            line: 0
+        }
+    }
+
+    pub fn new_command_usage_error(line : usize, col : usize, usage : &str) -> Self {
+        YarnError { 
+            error_name: "Command Usage Error".to_string(), 
+            error_message: format!("The command was given the wrong arguments. Usage: {}", usage), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_command_error(line : usize, col : usize, reason : &str) -> Self {
+        YarnError { 
+            error_name: "Command Error".to_string(), 
+            error_message: reason.to_string(), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_invalid_header_error(line : usize, col : usize, header : &str, value : &str) -> Self {
+        YarnError { 
+            error_name: "Invalid Header Error".to_string(), 
+            error_message: format!("'{}' is not a valid value for the '{}' header.", value, header), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_invalid_snapshot_error(reason : &str) -> Self {
+        YarnError { 
+            error_name: "Invalid Snapshot Error".to_string(), 
+            error_message: format!("The saved dialogue could not be read. {}", reason), 
+            col: 0, 
+            line: 0
+        }
+    }
+
+    pub fn new_snapshot_error(reason : &str) -> Self {
+        YarnError { 
+            error_name: "Snapshot Error".to_string(), 
+            error_message: reason.to_string(), 
+            col: 0, 
+            line: 0
+        }
+    }
+
+    pub fn new_detour_depth_error(line : usize, col : usize, depth : usize) -> Self {
+        YarnError { 
+            error_name: "Detour Depth Error".to_string(), 
+            error_message: format!("The dialogue went more than {} detours deep without returning.", depth), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_arithmetic_error(line : usize, col : usize, reason : &str) -> Self {
+        YarnError { 
+            error_name: "Arithmetic Error".to_string(), 
+            error_message: reason.to_string(), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_duplicate_variable_error(line : usize, col : usize, name : &str) -> Self {
+        YarnError { 
+            error_name: "Duplicate Variable Error".to_string(), 
+            error_message: format!("The variable '${}' is declared more than once.", name), 
+            col, 
+            line
+        }
+    }
+
+    pub fn new_enum_error(line : usize, col : usize, reason : &str) -> Self {
+        YarnError { 
+            error_name: "Enum Error".to_string(), 
+            error_message: reason.to_string(), 
+            col, 
+            line
+        }
+    }
+
     pub fn gen_error_message(&self) -> String {
         format!("{} at ({}, {}) : {}", self.error_name, self.line, self.col, self.error_message)
     }
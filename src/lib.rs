@@ -7,12 +7,23 @@ use value::YarnValue;
 
 use crate::{parcer::{YarnVariableMap, }};
 
-use yarn_spinner_macros::yarn_function;
+pub use yarn_spinner_macros::{yarn_function, yarn_library, yarn_command, YarnEnum};
 
-mod token;
-mod value;
-mod error;
-mod parcer;
+//Lets the code the macros generate name this crate the same way inside it as in a game that depends on it.
+extern crate self as yarn_spinner_compiler;
+
+pub mod token;
+pub mod value;
+pub mod error;
+pub mod parcer;
+pub mod program;
+pub mod vm;
+pub mod runtime;
+pub mod yarnc;
+pub mod command;
+pub mod random;
+pub mod storage;
+pub mod decimal;
 
 // fn main() {
 //     let mut source = String::new();
@@ -85,15 +96,19 @@ mod parcer;
 
 #[cfg(test)]
 mod tests {
-    use yarn_spinner_macros::yarn_function;
+    use std::{collections::HashMap, sync::Mutex};
+
+    use yarn_spinner_macros::{yarn_function, yarn_library, yarn_command, YarnEnum};
 
-    use crate::{token::tokenize, parcer::YarnRuntime};
+    use crate::{token::tokenize, runtime::YarnRuntime, parcer::YarnFunctionContext, vm::YarnDialogueEvent, command::split_command, storage::YarnMemoryStorage, value::YarnEnum};
 
     use super::*;
 
     #[test]
     fn main_test() {
-        test(vec![YarnValue::BOOL(true), YarnValue::BOOL(false)], 0, 0).unwrap();
+        let variables = YarnMemoryStorage::new();
+        let context = YarnFunctionContext::new(&variables, None, 0, 0);
+        test(vec![YarnValue::NUMBER(1.0), YarnValue::NUMBER(2.0)], &context).unwrap();
 
         let tokens = tokenize("1+1");
         let eval = parse_expression(&tokens);
@@ -101,10 +116,278 @@ mod tests {
         let runtime = YarnRuntime::new("source").with_function("test", &test);
     }
 
+    #[test]
+    fn test_yarn_function_arguments() {
+        let variables = YarnMemoryStorage::new();
+        let context = YarnFunctionContext::new(&variables, None, 0, 0);
+        assert_eq!(greet(vec![YarnValue::STRING("Mae".to_string()), YarnValue::NUMBER(2.0)], &context).unwrap(), Some(YarnValue::STRING("Hi Mae x2".to_string())));
+        assert_eq!(half(vec![YarnValue::NUMBER(3.0)], &context).unwrap(), Some(YarnValue::NUMBER(1.5)));
+        assert_eq!(half(vec![YarnValue::NUMBER(-3.0)], &context).unwrap_err().error_name(), "Invalid Opperation Error");
+
+        assert_eq!(test(vec![YarnValue::NUMBER(1.0)], &context).unwrap_err().error_name(), "Invalid Argument Count Error");
+        assert_eq!(test(vec![YarnValue::NUMBER(1.0), YarnValue::BOOL(true)], &context).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(test(vec![YarnValue::NUMBER(1.0), YarnValue::NUMBER(-2.0)], &context).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(greet(vec![YarnValue::STRING("Mae".to_string()), YarnValue::NUMBER(2.5)], &context).unwrap_err().error_name(), "Type Mismatch Error");
+    }
+
+    #[test]
+    fn test_yarn_function_signature() {
+        let signature = greet_signature();
+        assert_eq!(signature.parameters(), &vec![value::YarnValueType::STRING, value::YarnValueType::NUMBER]);
+        assert_eq!(signature.return_type(), value::YarnValueType::STRING);
+        assert!(signature.is_pure());
+        assert_eq!(signature.doc(), "Greets someone a few times.");
+        assert!(!half_signature().is_pure());
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<set $name to greet(\"Mae\")>>\n===\n")
+            .with_variable("name", YarnValue::STRING(String::new()))
+            .with_typed_function("greet", &greet, greet_signature());
+        assert_eq!(runtime.compile().unwrap_err().error_name(), "Invalid Argument Count Error");
+    }
+
+    #[test]
+    fn test_yarn_function_context() {
+        let source = concat!(
+            "title: shop\n",
+            "---\n",
+            "<<set $can_buy to has_item(\"sword\")>>\n",
+            "<<set $can_buy to has_item(\"shield\")>>\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source)
+            .with_variable("can_buy", YarnValue::BOOL(false))
+            .with_variable("sword", YarnValue::NUMBER(1.0))
+            .with_typed_function("has_item", &has_item, has_item_signature());
+        runtime.start("shop").unwrap();
+
+        let error = runtime.next_event().unwrap_err();
+        assert_eq!(error.error_name(), "Function Error");
+        assert_eq!(error.error_message(), "There is no item called shield in shop.");
+        assert_eq!(runtime.variable("can_buy"), Some(YarnValue::BOOL(true)));
+        assert_eq!(has_item_signature().parameters().len(), 1);
+    }
+
+    #[test]
+    fn test_yarn_function_optional_parameters() {
+        let variables = YarnMemoryStorage::new();
+        let context = YarnFunctionContext::new(&variables, None, 0, 0);
+        assert_eq!(total(vec![], &context).unwrap(), Some(YarnValue::NUMBER(0.0)));
+        assert_eq!(total(vec![YarnValue::NUMBER(1.0), YarnValue::NUMBER(2.0), YarnValue::NUMBER(3.5)], &context).unwrap(), Some(YarnValue::NUMBER(6.5)));
+        assert_eq!(total(vec![YarnValue::NUMBER(1.0), YarnValue::BOOL(true)], &context).unwrap_err().error_name(), "Type Mismatch Error");
+
+        assert_eq!(repeat(vec![YarnValue::STRING("ha".to_string())], &context).unwrap(), Some(YarnValue::STRING("ha".to_string())));
+        assert_eq!(repeat(vec![YarnValue::STRING("ha".to_string()), YarnValue::NUMBER(3.0)], &context).unwrap(), Some(YarnValue::STRING("hahaha".to_string())));
+        assert_eq!(repeat(vec![], &context).unwrap_err().error_name(), "Invalid Argument Count Error");
+        assert_eq!(repeat(vec![YarnValue::STRING("ha".to_string()), YarnValue::NUMBER(3.0), YarnValue::NUMBER(3.0)], &context).unwrap_err().error_name(), "Invalid Argument Count Error");
+
+        let signature = repeat_signature();
+        assert_eq!(signature.min_arguments(), 1);
+        assert_eq!(signature.max_arguments(), Some(2));
+        assert_eq!(total_signature().max_arguments(), None);
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<set $sum to total(1, 2, 3)>>\n<<set $word to repeat(\"ha\")>>\n===\n")
+            .with_variable("sum", YarnValue::NUMBER(0.0))
+            .with_variable("word", YarnValue::STRING(String::new()))
+            .with_typed_function("total", &total, total_signature())
+            .with_typed_function("repeat", &repeat, repeat_signature());
+        runtime.compile().unwrap();
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.variable("sum"), Some(YarnValue::NUMBER(6.0)));
+        assert_eq!(runtime.variable("word"), Some(YarnValue::STRING("ha".to_string())));
+    }
+
+    #[test]
+    fn test_yarn_library() {
+        let shop = Shop { stock : Mutex::new(HashMap::from([("sword".to_string(), 2.0)])) };
+        let mut functions = YarnFunctionMap::new();
+        shop.register(&mut functions);
+
+        let mut names = functions.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+        assert_eq!(names, vec!["shop_buy", "shop_stock", "shop_tax"]);
+        assert!(functions["shop_tax"].is_pure());
+        assert!(!functions["shop_stock"].is_pure());
+        assert_eq!(functions["shop_stock"].signature().unwrap().doc(), "How many of an item are left.");
+
+        let variables = YarnMemoryStorage::new();
+        let context = YarnFunctionContext::new(&variables, None, 0, 0);
+        assert_eq!(functions["shop_tax"].call(vec![YarnValue::NUMBER(10.0)], &context).unwrap(), Some(YarnValue::NUMBER(12.0)));
+        assert_eq!(functions["shop_stock"].call(vec![], &context).unwrap_err().error_name(), "Invalid Argument Count Error");
+
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $bought to shop_buy(\"sword\")>>\n",
+            "<<set $left to shop_stock(\"sword\")>>\n",
+            "===\n",
+        );
+        let mut runtime = functions.into_iter().fold(YarnRuntime::new(source), |runtime, (name, function)| runtime.with_registered_function(&name, function))
+            .with_variable("bought", YarnValue::BOOL(false))
+            .with_variable("left", YarnValue::NUMBER(0.0));
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.variable("bought"), Some(YarnValue::BOOL(true)));
+        assert_eq!(runtime.variable("left"), Some(YarnValue::NUMBER(1.0)));
+    }
+
+    #[test]
+    fn test_yarn_command() {
+        let variables = YarnMemoryStorage::new();
+        let context = YarnFunctionContext::new(&variables, Some("start"), 4, 0);
+
+        walk(split_command("Mae left 3"), &context).unwrap();
+        walk(split_command("\"Mae Borowski\" right"), &context).unwrap();
+        assert_eq!(*WALKED.lock().unwrap(), vec!["Mae left 3", "Mae Borowski right 1"]);
+
+        let error = walk(split_command("Mae left three"), &context).unwrap_err();
+        assert_eq!(error.error_name(), "Command Usage Error");
+        assert_eq!(error.error_message(), "The command was given the wrong arguments. Usage: <<walk who direction [steps]>>");
+        assert_eq!(walk(split_command("Mae"), &context).unwrap_err().error_name(), "Command Usage Error");
+        assert_eq!(walk(split_command("Mae left 3 4"), &context).unwrap_err().error_name(), "Command Usage Error");
+        assert_eq!(walk(split_command("Mae up"), &context).unwrap_err().error_name(), "Function Error");
+
+        assert_eq!(shout_words_usage(), "<<shout [words...]>>");
+        assert!(shout_words(split_command("hello there 3"), &context).is_ok());
+        assert!(shout_words(vec![], &context).is_err());
+    }
+
+    #[test]
+    fn test_yarn_enum() {
+        assert_eq!(QuestStage::cases(), &["NotStarted", "Started", "Complete"]);
+        assert_eq!(YarnValue::from(QuestStage::Started), YarnValue::ENUM("QuestStage".to_string(), "Started".to_string()));
+        assert_eq!(QuestStage::try_from(YarnValue::ENUM("QuestStage".to_string(), "Complete".to_string())).unwrap(), QuestStage::Complete);
+        assert_eq!(QuestStage::try_from(YarnValue::ENUM("QuestStage".to_string(), "Failed".to_string())).unwrap_err().error_name(), "Enum Error");
+        assert_eq!(QuestStage::try_from(YarnValue::STRING("Started".to_string())).unwrap_err().error_name(), "Type Mismatch Error");
+
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<if $stage == .NotStarted>>\n",
+            "    <<set $stage to next_stage($stage)>>\n",
+            "<<endif>>\n",
+            "===\n",
+        );
+        let mut runtime = YarnRuntime::new(source)
+            .with_enum::<QuestStage>()
+            .with_variable("stage", QuestStage::NotStarted.into())
+            .with_typed_function("next_stage", &next_stage, next_stage_signature());
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(QuestStage::try_from(runtime.variable("stage").unwrap()).unwrap(), QuestStage::Started);
+
+        let declared = format!("title: start\n---\n{}===\n", QuestStage::declaration());
+        assert!(YarnRuntime::new(declared.as_str()).with_enum::<QuestStage>().compile().is_ok());
+        let mismatched = "title: start\n---\n<<enum QuestStage>>\n<<case Started>>\n<<endenum>>\n===\n";
+        assert_eq!(YarnRuntime::new(mismatched).with_enum::<QuestStage>().compile().unwrap_err().error_name(), "Enum Error");
+
+        let wrong_type = "title: start\n---\n<<set $stage to \"Started\">>\n===\n";
+        let mut runtime = YarnRuntime::new(wrong_type).with_enum::<QuestStage>().with_variable("stage", QuestStage::Started.into());
+        assert_eq!(runtime.compile().unwrap_err().error_name(), "Type Mismatch Error");
+    }
+
+    #[derive(YarnEnum, Debug, PartialEq, Clone, Copy)]
+    enum QuestStage {
+        NotStarted,
+        Started,
+        Complete,
+    }
+
+    #[yarn_function]
+    fn next_stage(stage : QuestStage) -> QuestStage {
+        match stage {
+            QuestStage::NotStarted => QuestStage::Started,
+            _ => QuestStage::Complete,
+        }
+    }
+
     #[yarn_function]
     fn test(test : i32, test_2 : u32) {
         println!("{:?}", test);
         println!("{:?}", test_2);
         Ok(None)
     }
-}
\ No newline at end of file
+
+    /// Greets someone a few times.
+    #[yarn_function(pure)]
+    fn greet(name : &str, times : u8) -> String {
+        format!("Hi {} x{}", name, times)
+    }
+
+    #[yarn_function]
+    fn half(value : f64) -> YarnResult<f64> {
+        if value < 0.0 {
+            return Err(error::YarnError::new_invalid_operation_error(0, 0));
+        }
+        Ok(value / 2.0)
+    }
+
+    #[yarn_function]
+    fn total(values : Vec<f64>) -> f64 {
+        values.iter().sum()
+    }
+
+    #[yarn_function]
+    fn repeat(text : &str, times : Option<u8>) -> String {
+        text.repeat(times.unwrap_or(1) as usize)
+    }
+
+    #[yarn_function]
+    fn has_item(item : &str, context : &YarnFunctionContext) -> YarnResult<bool> {
+        match context.variable(item) {
+            Some(YarnValue::NUMBER(count)) => Ok(count > 0.0),
+            _ => Err(context.error(format!("There is no item called {} in {}.", item, context.current_node().unwrap_or("")).as_str())),
+        }
+    }
+
+    static WALKED : Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    #[yarn_command]
+    fn walk(who : &str, direction : &str, steps : Option<u32>, context : &YarnFunctionContext) -> YarnResult<()> {
+        if direction != "left" && direction != "right" {
+            return Err(context.error("Characters can only walk left or right."));
+        }
+        WALKED.lock().unwrap().push(format!("{} {} {}", who, direction, steps.unwrap_or(1)));
+        Ok(())
+    }
+
+    #[yarn_command(name = "shout")]
+    fn shout_words(words : Vec<String>) -> YarnResult<()> {
+        match words.is_empty() {
+            true => Err(error::YarnError::new_function_error(0, 0, "There is nothing to shout.")),
+            false => Ok(()),
+        }
+    }
+
+    struct Shop {
+        stock : Mutex<HashMap<String, f64>>
+    }
+
+    #[yarn_library(namespace = "shop")]
+    impl Shop {
+        /// How many of an item are left.
+        pub fn stock(&self, item : &str) -> f64 {
+            self.count(item)
+        }
+
+        pub fn buy(&self, item : &str) -> bool {
+            match self.stock.lock().unwrap().get_mut(item) {
+                Some(count) if *count > 0.0 => {
+                    *count -= 1.0;
+                    true
+                },
+                _ => false,
+            }
+        }
+
+        #[yarn_function(pure)]
+        pub fn tax(price : f64) -> f64 {
+            price * 1.2
+        }
+
+        fn count(&self, item : &str) -> f64 {
+            self.stock.lock().unwrap().get(item).copied().unwrap_or(0.0)
+        }
+    }
+}
@@ -10,33 +10,312 @@ mod comparison_expression;
 mod equality_expression;
 mod command;
 mod function;
+mod node;
+mod enum_case;
 
-use std::{collections::{HashMap, VecDeque}, rc::Rc, fmt::Debug, process::Child};
-use crate::{error::{YarnError, YarnResult}, token::{YarnToken, YarnTokenQueue, YarnTokenType::{*, self}}, value::YarnValue};
-use self::equality_expression::EqualityExpressionNode;
+use std::{collections::{HashMap, VecDeque}, rc::Rc, fmt::Debug, process::Child, sync::Arc, cell::RefCell};
+use crate::{random::YarnRandom, vm::YarnNodeVisits, storage::{YarnVariableStorage, YarnMemoryStorage}, error::{YarnError, YarnResult}, token::{YarnToken, YarnTokenQueue, YarnTokenType::{*, self}}, value::{YarnValue, YarnValueType}, program::{YarnProgramNode, YarnSymbols}};
+use self::{equality_expression::EqualityExpressionNode, unary_expression::UnaryOperator, string_literal::StringLiteralNode, number_literal::NumberLiteralNode, bool_literal::BoolLiteralNode, enum_case::EnumCaseNode};
 
 pub type YarnVariableMap = HashMap<String, YarnValue>;
+pub type YarnEnumMap = HashMap<String, Vec<String>>; // Enum name, Case names
 
-pub type YarnFunction = &'static dyn Fn(Vec<YarnValue>, usize, usize) -> YarnResult<Option<YarnValue>>;
+//Shared so the runtime, the VM and a saved program can all hold the same function, including closures that capture game state.
+pub type YarnFunction = Arc<dyn Fn(Vec<YarnValue>, &YarnFunctionContext) -> YarnResult<Option<YarnValue>> + Send + Sync>;
 
-pub type YarnFunctionMap = HashMap<String, YarnFunction>;
+//What a host function can see about the dialogue that called it.
+pub struct YarnFunctionContext<'a> {
+    variables : &'a dyn YarnVariableStorage,
+    random : Option<&'a RefCell<YarnRandom>>,
+    visits : Option<&'a HashMap<String, YarnNodeVisits>>,
+    node : Option<&'a str>,
+    line : usize,
+    col : usize
+}
+
+impl <'a> YarnFunctionContext<'a> {
+    pub fn new(variables : &'a dyn YarnVariableStorage, node : Option<&'a str>, line : usize, col : usize) -> Self {
+        YarnFunctionContext { variables, random: None, visits: None, node, line, col }
+    }
+
+    pub fn with_random(mut self, random : &'a RefCell<YarnRandom>) -> Self {
+        self.random = Some(random);
+        self
+    }
+
+    pub fn with_visits(mut self, visits : &'a HashMap<String, YarnNodeVisits>) -> Self {
+        self.visits = Some(visits);
+        self
+    }
+
+    //How many times the node has been run to the end. Calls made outside of a running dialogue see no visits.
+    pub fn visit_count(&self, node : &str) -> usize {
+        self.visits.and_then(|visits| visits.get(node)).map(|visits| visits.completed()).unwrap_or(0)
+    }
+
+    //Uses the random numbers of the dialogue that made the call, or unseeded ones when there is none.
+    pub fn random<T>(&self, use_random : impl FnOnce(&mut YarnRandom) -> T) -> T {
+        match self.random {
+            Some(random) => use_random(&mut random.borrow_mut()),
+            None => use_random(&mut YarnRandom::from_entropy()),
+        }
+    }
+
+    pub fn variable(&self, name : &str) -> Option<YarnValue> {
+        self.variables.get(name)
+    }
+
+    pub fn current_node(&self) -> Option<&str> {
+        self.node
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    pub fn error(&self, message : &str) -> YarnError {
+        YarnError::new_function_error(self.line, self.col, message)
+    }
+}
+
+//A function the dialogue can call, with the signature the compiler checks calls against when it is known.
+#[derive(Clone)]
+pub struct YarnRegisteredFunction {
+    function : YarnFunction,
+    signature : Option<YarnFunctionSignature>
+}
+
+impl YarnRegisteredFunction {
+    pub fn new<F>(function : F, signature : Option<YarnFunctionSignature>) -> Self
+    where F : Fn(Vec<YarnValue>, &YarnFunctionContext) -> YarnResult<Option<YarnValue>> + Send + Sync + 'static {
+        YarnRegisteredFunction { function: Arc::new(function), signature }
+    }
+
+    pub fn from_shared(function : YarnFunction, signature : Option<YarnFunctionSignature>) -> Self {
+        YarnRegisteredFunction { function, signature }
+    }
+
+    pub fn function(&self) -> YarnFunction {
+        self.function.clone()
+    }
+
+    pub fn call(&self, arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+        (self.function)(arguments, context)
+    }
+
+    pub fn signature(&self) -> Option<&YarnFunctionSignature> {
+        self.signature.as_ref()
+    }
+
+    pub fn is_pure(&self) -> bool {
+        self.signature.as_ref().map(|signature| signature.is_pure()).unwrap_or(false)
+    }
+}
+
+pub type YarnFunctionMap = HashMap<String, YarnRegisteredFunction>;
 
 pub fn default_function_map() -> YarnFunctionMap {
+    use YarnValueType::*;
+
     let mut functions = YarnFunctionMap::new();
-    functions.insert("dice".to_string(), &function::dice);
-    functions.insert("random".to_string(), &function::random);
-    functions.insert("random_range".to_string(), &function::random_range);
-    functions.insert("round".to_string(), &function::round);
-    functions.insert("round_places".to_string(), &function::round_places);
-    functions.insert("floor".to_string(), &function::floor);
-    functions.insert("ceil".to_string(), &function::ceil);
-    functions.insert("inc".to_string(), &function::inc);
-    functions.insert("dec".to_string(), &function::dec);
-    functions.insert("decimal".to_string(), &function::decimal);
+    let mut register = |name : &str, function : fn(Vec<YarnValue>, &YarnFunctionContext) -> YarnResult<Option<YarnValue>>, signature : YarnFunctionSignature| {
+        functions.insert(name.to_string(), YarnRegisteredFunction::new(function, Some(signature)));
+    };
+
+    register("dice", function::dice, YarnFunctionSignature::new(vec![NUMBER], NUMBER)
+        .with_doc("Rolls a die with the given number of sides."));
+    register("random", function::random, YarnFunctionSignature::new(vec![], NUMBER)
+        .with_doc("Returns a random number between 0 and 1."));
+    register("random_range", function::random_range, YarnFunctionSignature::new(vec![NUMBER, NUMBER], NUMBER)
+        .with_doc("Returns a random whole number between the two numbers, including both. The numbers are rounded first."));
+    register("round", function::round, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Rounds a number to the nearest whole number."));
+    register("round_places", function::round_places, YarnFunctionSignature::new(vec![NUMBER, NUMBER], NUMBER).with_purity(true)
+        .with_doc("Rounds a number to the given number of decimal places."));
+    register("floor", function::floor, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Rounds a number down to the nearest whole number."));
+    register("ceil", function::ceil, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Rounds a number up to the nearest whole number."));
+    register("inc", function::inc, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Rounds a number up to the next whole number, adding one if it already is one."));
+    register("dec", function::dec, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Rounds a number down to the previous whole number, taking one if it already is one."));
+    register("decimal", function::decimal, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Returns the part of a number after the decimal point."));
+    register("string", function::string, YarnFunctionSignature::new(vec![ANY], STRING).with_purity(true)
+        .with_doc("Converts a value to a string."));
+    register("number", function::number, YarnFunctionSignature::new(vec![ANY], NUMBER).with_purity(true)
+        .with_doc("Converts a value to a number. Strings must hold a number and true is 1."));
+    register("bool", function::bool, YarnFunctionSignature::new(vec![ANY], BOOL).with_purity(true)
+        .with_doc("Converts a value to a boolean. Strings must be 'true' or 'false' and any number but 0 is true."));
+    register("int", function::int, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Removes the part of a number after the decimal point, rounding towards zero."));
+    register("min", function::min, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_variadic(NUMBER).with_purity(true)
+        .with_doc("Returns the smallest of the numbers."));
+    register("max", function::max, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_variadic(NUMBER).with_purity(true)
+        .with_doc("Returns the largest of the numbers."));
+    register("abs", function::abs, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Returns the number without its sign."));
+    register("pow", function::pow, YarnFunctionSignature::new(vec![NUMBER, NUMBER], NUMBER).with_purity(true)
+        .with_doc("Raises the first number to the power of the second."));
+    register("sqrt", function::sqrt, YarnFunctionSignature::new(vec![NUMBER], NUMBER).with_purity(true)
+        .with_doc("Returns the square root of a number that is not negative."));
+    register("format_invariant", function::format_invariant, YarnFunctionSignature::new(vec![NUMBER], STRING).with_purity(true)
+        .with_doc("Writes a number with a '.' before the decimals and no separators between thousands, whatever the player's language."));
+    register("visited", function::visited, YarnFunctionSignature::new(vec![STRING], BOOL)
+        .with_doc("Returns whether the node with the given title has been run to the end at least once."));
+    register("visited_count", function::visited_count, YarnFunctionSignature::new(vec![STRING], NUMBER)
+        .with_doc("Returns how many times the node with the given title has been run to the end."));
 
     functions
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct YarnFunctionSignature {
+    parameters : Vec<YarnValueType>,
+    required : usize, // Parameters after this many can be left out
+    variadic : Option<YarnValueType>, // Type of any number of extra arguments after the parameters
+    return_type : YarnValueType,
+    pure : bool, // Always returns the same value for the same arguments, so calls can be folded
+    doc : String
+}
+
+impl YarnFunctionSignature {
+    pub fn new(parameters : Vec<YarnValueType>, return_type : YarnValueType) -> Self {
+        YarnFunctionSignature { required: parameters.len(), parameters, variadic: None, return_type, pure: false, doc: String::new() }
+    }
+
+    pub fn with_optional_parameters(mut self, parameters : Vec<YarnValueType>) -> Self {
+        self.parameters.extend(parameters);
+        self
+    }
+
+    pub fn with_variadic(mut self, value_type : YarnValueType) -> Self {
+        self.variadic = Some(value_type);
+        self
+    }
+
+    pub fn with_purity(mut self, pure : bool) -> Self {
+        self.pure = pure;
+        self
+    }
+
+    pub fn with_doc(mut self, doc : &str) -> Self {
+        self.doc = doc.to_string();
+        self
+    }
+
+    pub fn parameters(&self) -> &Vec<YarnValueType> {
+        &self.parameters
+    }
+
+    pub fn variadic(&self) -> Option<YarnValueType> {
+        self.variadic.clone()
+    }
+
+    pub fn min_arguments(&self) -> usize {
+        self.required
+    }
+
+    //None when the function takes any number of extra arguments.
+    pub fn max_arguments(&self) -> Option<usize> {
+        match self.variadic {
+            Some(_) => None,
+            None => Some(self.parameters.len()),
+        }
+    }
+
+    pub fn parameter_type(&self, index : usize) -> Option<YarnValueType> {
+        self.parameters.get(index).cloned().or_else(|| self.variadic.clone())
+    }
+
+    pub fn return_type(&self) -> YarnValueType {
+        self.return_type.clone()
+    }
+
+    pub fn is_pure(&self) -> bool {
+        self.pure
+    }
+
+    pub fn doc(&self) -> &str {
+        self.doc.as_str()
+    }
+}
+
+//Everything the type checker knows before the dialogue is run. Variables keep the type they were declared with,
+//and functions without a known signature are only checked for existence.
+#[derive(Default)]
+pub struct YarnTypeContext {
+    variables : HashMap<String, YarnValueType>,
+    functions : HashMap<String, Option<YarnFunctionSignature>>,
+    enums : YarnEnumMap
+}
+
+impl YarnTypeContext {
+    pub fn new() -> Self {
+        YarnTypeContext { 
+            variables: HashMap::new(), 
+            functions: HashMap::new(),
+            enums: YarnEnumMap::new()
+        }
+    }
+
+    pub fn from_maps(variables : &YarnVariableMap, functions : &YarnFunctionMap) -> Self {
+        let mut context = YarnTypeContext::new();
+
+        for (name, value) in variables.iter() {
+            context.declare_variable(name, value.get_type());
+        }
+
+        for (name, function) in functions.iter() {
+            context.functions.insert(name.clone(), function.signature().cloned());
+        }
+
+        context
+    }
+
+    pub fn declare_variable(&mut self, name : &str, value_type : YarnValueType) {
+        self.variables.insert(name.to_string(), value_type);
+    }
+
+    pub fn declare_function(&mut self, name : &str, signature : YarnFunctionSignature) {
+        self.functions.insert(name.to_string(), Some(signature));
+    }
+
+    pub fn declare_enum(&mut self, name : &str, cases : Vec<String>) {
+        self.enums.insert(name.to_string(), cases);
+    }
+
+    //The type of Name.Case, or an error when there is no such enum or the enum has no such case.
+    pub fn enum_case_type(&self, name : &str, case : &str, line : usize, col : usize) -> YarnResult<YarnValueType> {
+        match self.enums.get(name) {
+            Some(cases) if cases.iter().any(|known| known == case) => Ok(YarnValueType::ENUM(name.to_string())),
+            Some(_) => Err(YarnError::new_enum_error(line, col, format!("'{}' is not a case of the enum '{}'.", case, name).as_str())),
+            None => Err(YarnError::new_enum_error(line, col, format!("There is no enum named '{}'.", name).as_str())),
+        }
+    }
+
+    pub fn enum_of_case(&self, case : &str) -> Option<&str> {
+        enum_of_case(&self.enums, case)
+    }
+
+    pub fn variable_type(&self, name : &str) -> Option<YarnValueType> {
+        self.variables.get(name).cloned()
+    }
+
+    pub fn has_function(&self, name : &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    pub fn function_signature(&self, name : &str) -> Option<&YarnFunctionSignature> {
+        self.functions.get(name).and_then(|signature| signature.as_ref())
+    }
+}
+
 pub enum YarnParseResult {
     Parsed(Box<dyn YarnEvaluator>, usize),
     Error(YarnError),
@@ -44,7 +323,58 @@ pub enum YarnParseResult {
 }
 
 pub trait YarnEvaluator {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>>;
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>>;
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>>;
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator>;
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols);
+
+    fn position(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn constant(&self) -> Option<YarnValue> {
+        None
+    }
+
+    fn take_operand_of(&mut self, _operator : &UnaryOperator) -> Option<Box<dyn YarnEvaluator>> {
+        None
+    }
+
+    //Tells the expression what type the code around it needs, for values like .Happy that do not name their type.
+    fn expect_type(&self, _expected : &YarnValueType) {}
+}
+
+//Replaces an expression whose children are all constants with a literal of its value. Expressions that fail to
+//evaluate are left alone so that the error is still reported when the dialogue runs.
+pub fn fold_constant(eval : Box<dyn YarnEvaluator>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+    match eval.eval(&mut YarnMemoryStorage::new(), functions) {
+        Ok(Some(value)) => literal_from_value(value).unwrap_or(eval),
+        _ => eval
+    }
+}
+
+//Whether comparing the two can be folded. Numbers that are not exactly equal are left for the VM, since how close
+//counts as equal depends on the tolerance the runtime was given.
+pub fn comparable_constants(lhs : &dyn YarnEvaluator, rhs : &dyn YarnEvaluator) -> bool {
+    match (lhs.constant(), rhs.constant()) {
+        (Some(YarnValue::NUMBER(lhs)), Some(YarnValue::NUMBER(rhs))) => lhs == rhs,
+        (Some(_), Some(_)) => true,
+        _ => false
+    }
+}
+
+//Exact numbers have no literal of their own, so expressions giving one are left unfolded.
+pub fn literal_from_value(value : YarnValue) -> Option<Box<dyn YarnEvaluator>> {
+    match value {
+        YarnValue::STRING(value) => Some(StringLiteralNode::new_boxed(value.as_str())),
+        YarnValue::NUMBER(value) => Some(NumberLiteralNode::new_boxed(value)),
+        YarnValue::BOOL(value) => Some(BoolLiteralNode::new_boxed(value)),
+        YarnValue::ENUM(name, case) => Some(EnumCaseNode::new_boxed(Some(name), case, 0, 0)),
+        YarnValue::INT(_) | YarnValue::DECIMAL(_) => None,
+    }
 }
 
 pub trait YarnExpressionParser {
@@ -57,46 +387,292 @@ pub struct YarnNode {
     title : String
 }
 
+impl YarnNode {
+    pub fn new(title : String, headers : HashMap<String, String>, first_step : YarnNodeStack) -> YarnNode {
+        YarnNode { first_step, headers, title }
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn first_step(&self) -> &YarnNodeStack {
+        &self.first_step
+    }
+
+    pub fn type_check(&self, types : &YarnTypeContext) -> YarnResult<()> {
+        self.first_step.type_check(types)
+    }
+
+    pub fn fold(self, functions : &YarnFunctionMap) -> YarnNode {
+        YarnNode {
+            first_step: self.first_step.fold(functions),
+            headers: self.headers,
+            title: self.title,
+        }
+    }
+}
+
 pub struct YarnNodeStack {
-    lines : VecDeque<YarnNodeLine>,
-    options : Vec<YarnNodeStack>
+    lines : VecDeque<YarnNodeLine>
+}
+
+impl YarnNodeStack {
+    pub fn new(lines : VecDeque<YarnNodeLine>) -> YarnNodeStack {
+        YarnNodeStack { lines }
+    }
+
+    pub fn lines(&self) -> &VecDeque<YarnNodeLine> {
+        &self.lines
+    }
+
+    pub fn type_check(&self, types : &YarnTypeContext) -> YarnResult<()> {
+        for line in self.lines.iter() {
+            match line {
+                YarnNodeLine::COMMAND(eval) => {
+                    eval.type_check(types)?;
+                },
+                YarnNodeLine::IF(branches, else_branch) => {
+                    for (condition, stack) in branches.iter() {
+                        type_check_condition(condition.as_ref(), types)?;
+                        stack.type_check(types)?;
+                    }
+
+                    if let Some(stack) = else_branch {
+                        stack.type_check(types)?;
+                    }
+                },
+                YarnNodeLine::OPTIONS(options) => {
+                    for option in options.iter() {
+                        if let Some(condition) = option.condition() {
+                            type_check_condition(condition, types)?;
+                        }
+                        option.stack().type_check(types)?;
+                    }
+                },
+                YarnNodeLine::DECLARE(_, YarnValue::ENUM(name, case), line, col) => {
+                    types.enum_case_type(name, case, *line, *col)?;
+                },
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    //Calls visit with every line in the stack, including the lines inside if statements and options.
+    pub fn visit_lines(&self, visit : &mut dyn FnMut(&YarnNodeLine) -> YarnResult<()>) -> YarnResult<()> {
+        for line in self.lines.iter() {
+            visit(line)?;
+            match line {
+                YarnNodeLine::IF(branches, else_branch) => {
+                    for (_, stack) in branches.iter() {
+                        stack.visit_lines(visit)?;
+                    }
+
+                    if let Some(stack) = else_branch {
+                        stack.visit_lines(visit)?;
+                    }
+                },
+                YarnNodeLine::OPTIONS(options) => {
+                    for option in options.iter() {
+                        option.stack().visit_lines(visit)?;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    //Folds every expression in the stack and removes the branches of if statements that can never run. A branch
+    //that is always taken is spliced into the stack in place of its if statement.
+    pub fn fold(self, functions : &YarnFunctionMap) -> YarnNodeStack {
+        let mut lines = VecDeque::new();
+
+        for line in self.lines {
+            match line {
+                YarnNodeLine::COMMAND(eval) => lines.push_back(YarnNodeLine::COMMAND(eval.fold(functions))),
+                YarnNodeLine::IF(branches, else_branch) => {
+                    let mut kept_branches = Vec::new();
+                    let mut else_branch = else_branch;
+
+                    for (condition, stack) in branches {
+                        let condition = condition.fold(functions);
+                        match condition.constant() {
+                            Some(YarnValue::BOOL(false)) => {},
+                            Some(YarnValue::BOOL(true)) => {
+                                else_branch = Some(stack);
+                                break;
+                            },
+                            _ => kept_branches.push((condition, stack.fold(functions)))
+                        }
+                    }
+
+                    let else_branch = else_branch.map(|stack| stack.fold(functions));
+                    if kept_branches.is_empty() {
+                        if let Some(stack) = else_branch {
+                            lines.extend(stack.lines);
+                        }
+                    } else {
+                        lines.push_back(YarnNodeLine::IF(kept_branches, else_branch));
+                    }
+                },
+                YarnNodeLine::OPTIONS(options) => {
+                    let options = options.into_iter().map(|option| option.fold(functions)).collect();
+                    lines.push_back(YarnNodeLine::OPTIONS(options))
+                },
+                line => lines.push_back(line)
+            }
+        }
+
+        YarnNodeStack { lines }
+    }
+}
+
+fn type_check_condition(condition : &dyn YarnEvaluator, types : &YarnTypeContext) -> YarnResult<()> {
+    let condition_type = condition.type_check(types)?;
+    match condition_type {
+        Some(condition_type) if YarnValueType::BOOL.accepts(&condition_type) => Ok(()),
+        _ => {
+            let (line, col) = condition.position();
+            let received = condition_type.map(|condition_type| condition_type.get_type_as_string().to_string()).unwrap_or("NULL".to_string());
+            Err(YarnError::new_type_mismatch_error(line, col, "BOOL", received.as_str()))
+        }
+    }
+}
+
+pub struct YarnNodeOption {
+    speaker : Option<String>,
+    text : String,
+    tags : Vec<String>,
+    condition : Option<Box<dyn YarnEvaluator>>,
+    stack : YarnNodeStack
+}
+
+impl YarnNodeOption {
+    pub fn new(speaker : Option<String>, text : String, tags : Vec<String>, condition : Option<Box<dyn YarnEvaluator>>, stack : YarnNodeStack) -> YarnNodeOption {
+        YarnNodeOption { speaker, text, tags, condition, stack }
+    }
+
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn condition(&self) -> Option<&dyn YarnEvaluator> {
+        self.condition.as_deref()
+    }
+
+    pub fn stack(&self) -> &YarnNodeStack {
+        &self.stack
+    }
+
+    pub fn fold(self, functions : &YarnFunctionMap) -> YarnNodeOption {
+        YarnNodeOption {
+            condition: self.condition.map(|condition| condition.fold(functions)),
+            stack: self.stack.fold(functions),
+            ..self
+        }
+    }
 }
 
 pub enum YarnNodeLine {
     LINE(Option<String>, String, Vec<String>), //Speaker Name, Line Text, Tags
     COMMAND(Box<dyn YarnEvaluator>), // The Command evaluator
+    IF(Vec<(Box<dyn YarnEvaluator>, YarnNodeStack)>, Option<YarnNodeStack>), // Conditions and their branches, Else branch
+    OPTIONS(Vec<YarnNodeOption>), // A group of options shown together
+    JUMP(String), // Title of the node to jump to
+    DETOUR(String), // Title of the node to run before carrying on from here
+    RETURN, // Goes back to the node that detoured here
+    STOP, // Ends the dialogue
+    DECLARE(String, YarnValue, usize, usize), // Variable name, Starting value, Line, Col
+    ENUM(String, Vec<String>, usize, usize), // Enum name, Case names, Line, Col
     EMPTY
 }
 
-pub struct YarnRuntime {
-    nodes : HashMap<String, YarnNode>,
-    variables : YarnVariableMap,
-    functions : YarnFunctionMap,
+pub fn parse_expression(tokens : &YarnTokenQueue) -> YarnParseResult {
+    EqualityExpressionNode::parse(tokens, 1)
 }
 
-impl YarnRuntime {
-    pub fn new(source : &str) -> YarnRuntime {
-        YarnRuntime { 
-            nodes: HashMap::new(), 
-            variables: YarnVariableMap::new(), 
-            functions: default_function_map() 
+pub fn parse_nodes(tokens : &YarnTokenQueue) -> YarnResult<HashMap<String, YarnNode>> {
+    let mut nodes = HashMap::new();
+    let mut offset = 0;
+
+    while !tokens.check_index(offset, YarnTokenType::EOF) {
+        let (node, endex) = YarnNode::parse(tokens, offset)?;
+        if let Some(node) = node {
+            nodes.insert(node.title().to_string(), node);
         }
+        offset = endex;
     }
+
+    Ok(nodes)
 }
 
-impl From<&str> for YarnRuntime {
-    fn from(source : &str) -> Self {
-        YarnRuntime::new(source)
+//The starting values of every variable declared with <<declare>>. A variable can only be declared once.
+pub fn declared_variables(nodes : &HashMap<String, YarnNode>) -> YarnResult<YarnVariableMap> {
+    let mut declared = YarnVariableMap::new();
+    for node in nodes.values() {
+        node.first_step().visit_lines(&mut |line| match line {
+            YarnNodeLine::DECLARE(name, _, line, col) if declared.contains_key(name) => Err(YarnError::new_duplicate_variable_error(*line, *col, name)),
+            YarnNodeLine::DECLARE(name, value, _, _) => {
+                declared.insert(name.clone(), value.clone());
+                Ok(())
+            },
+            _ => Ok(())
+        })?;
     }
+    Ok(declared)
 }
 
-impl YarnRuntime {
-    pub fn with_function(mut self, name : &str, function : YarnFunction) -> Self {
-        self.functions.insert(name.to_string(), function);
-        self
+//The enum a case written as .Case belongs to, if exactly one enum has a case with that name.
+pub fn enum_of_case<'a>(enums : &'a YarnEnumMap, case : &str) -> Option<&'a str> {
+    let mut owners = enums.iter().filter(|(_, cases)| cases.iter().any(|known| known == case));
+    match (owners.next(), owners.next()) {
+        (Some((name, _)), None) => Some(name.as_str()),
+        _ => None
     }
 }
 
-pub fn parse_expression(tokens : &YarnTokenQueue) -> YarnParseResult {
-    EqualityExpressionNode::parse(tokens, 1)
-}
\ No newline at end of file
+//Every enum declared with <<enum>> along with the enums the game registered, by name. Enums are shared by the whole
+//dialogue and can only be declared once, and declaring a registered enum in the dialogue has to list the same cases.
+pub fn declared_enums(nodes : &HashMap<String, YarnNode>, registered : &YarnEnumMap) -> YarnResult<YarnEnumMap> {
+    let mut declared = YarnEnumMap::new();
+    for node in nodes.values() {
+        node.first_step().visit_lines(&mut |line| match line {
+            YarnNodeLine::ENUM(name, _, line, col) if declared.contains_key(name) => {
+                Err(YarnError::new_enum_error(*line, *col, format!("The enum '{}' is declared more than once.", name).as_str()))
+            },
+            YarnNodeLine::ENUM(name, cases, line, col) if registered.get(name).is_some_and(|registered| registered != cases) => {
+                Err(YarnError::new_enum_error(*line, *col, format!("The enum '{}' does not have the same cases as the one the game registered.", name).as_str()))
+            },
+            YarnNodeLine::ENUM(name, cases, _, _) => {
+                declared.insert(name.clone(), cases.clone());
+                Ok(())
+            },
+            _ => Ok(())
+        })?;
+    }
+
+    for (name, cases) in registered.iter() {
+        declared.entry(name.clone()).or_insert_with(|| cases.clone());
+    }
+    Ok(declared)
+}
+
+pub fn fold_nodes(nodes : HashMap<String, YarnNode>, functions : &YarnFunctionMap) -> HashMap<String, YarnNode> {
+    nodes.into_iter().map(|(title, node)| (title, node.fold(functions))).collect()
+}
@@ -1,6 +1,6 @@
-use crate::{error::{YarnResult, YarnError}, value::YarnValue, token::{YarnTokenQueue, YarnTokenType}};
+use crate::{storage::YarnVariableStorage, error::{YarnResult, YarnError}, value::{YarnValue, YarnValueType, DEFAULT_TOLERANCE}, token::{YarnTokenQueue, YarnTokenType}, program::{YarnProgramNode, YarnSymbols, YarnInstruction, YarnOperator}};
 
-use super::{YarnEvaluator, YarnVariableMap, YarnExpressionParser, factor_expression::FactorExpressionNode, YarnParseResult::{*, self}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, factor_expression::FactorExpressionNode, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext, fold_constant};
 
 
 pub enum AdditiveOperator {
@@ -30,10 +30,17 @@ impl AdditiveExpressionNode {
     pub fn new_boxed(lhs : Box<dyn YarnEvaluator>, rhs : Box<dyn YarnEvaluator>, operator : AdditiveOperator, line : usize, col : usize) -> Box<AdditiveExpressionNode> {
         Box::new(AdditiveExpressionNode::new(lhs, rhs, operator, line, col))
     }
+
+    fn operator(&self) -> YarnOperator {
+        match self.operator {
+            AdditiveOperator::PLUS => YarnOperator::ADD,
+            AdditiveOperator::MINUS => YarnOperator::SUB,
+        }
+    }
 }
 
 impl YarnEvaluator for AdditiveExpressionNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
         let lhs_value = self.lhs.eval(variables, functions);
         let rhs_value = self.rhs.eval(variables, functions);
 
@@ -44,16 +51,7 @@ impl YarnEvaluator for AdditiveExpressionNode {
                 let lhs_value = lhs_value.unwrap();
                 let rhs_value = rhs_value.unwrap();
 
-                let result = match self.operator {
-                    AdditiveOperator::PLUS => lhs_value.add(&rhs_value),
-                    AdditiveOperator::MINUS => lhs_value.sub(&rhs_value),
-                };
-
-                if let Some(result) = result {
-                    Ok(Some(result))
-                } else {
-                    Err(YarnError::new_invalid_operation_error(self.line, self.col))
-                }
+                self.operator().apply(&[lhs_value, rhs_value], DEFAULT_TOLERANCE, self.line, self.col).map(Some)
             } else {
                 Err(YarnError::new_invalid_operation_error(self.line, self.col))
             }
@@ -65,6 +63,57 @@ impl YarnEvaluator for AdditiveExpressionNode {
             }
         }
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let lhs_type = self.lhs.type_check(types)?;
+        let rhs_type = self.rhs.type_check(types)?;
+
+        if let (Some(lhs_type), Some(rhs_type)) = (lhs_type, rhs_type) {
+            let result = match self.operator {
+                AdditiveOperator::PLUS => lhs_type.add(&rhs_type),
+                AdditiveOperator::MINUS => lhs_type.arithmetic(&rhs_type),
+            };
+
+            if let Some(result) = result {
+                Ok(Some(result))
+            } else {
+                let (expected, received) = match (&self.operator, lhs_type.clone()) {
+                    (AdditiveOperator::PLUS, YarnValueType::BOOL) => (YarnValueType::STRING, rhs_type),
+                    (AdditiveOperator::PLUS, _) => (lhs_type, rhs_type),
+                    (AdditiveOperator::MINUS, _) => {
+                        (YarnValueType::NUMBER, if YarnValueType::NUMBER.accepts(&lhs_type) { rhs_type } else { lhs_type })
+                    }
+                };
+                Err(YarnError::new_type_mismatch_error(self.line, self.col, expected.get_type_as_string(), received.get_type_as_string()))
+            }
+        } else {
+            Err(YarnError::new_invalid_operation_error(self.line, self.col))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let lhs = node.lhs.fold(functions);
+        let rhs = node.rhs.fold(functions);
+        let is_constant = lhs.constant().is_some() && rhs.constant().is_some();
+
+        let folded = AdditiveExpressionNode::new_boxed(lhs, rhs, node.operator, node.line, node.col);
+        if is_constant {
+            fold_constant(folded, functions)
+        } else {
+            folded
+        }
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        self.lhs.compile(node, symbols);
+        self.rhs.compile(node, symbols);
+        node.emit(YarnInstruction::OPERATOR(self.operator()), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
 }
 
 impl YarnExpressionParser for AdditiveExpressionNode {
@@ -97,15 +146,15 @@ mod tests {
 
     use std::env::var;
 
-    use crate::{token::tokenize, parcer::YarnFunctionMap};
+    use crate::{storage::YarnMemoryStorage, token::tokenize, parcer::YarnFunctionMap};
 
     use super::*;
 
     #[test]
     fn test_parse_variable_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("foo".to_string(), YarnValue::NUMBER(2.0));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("foo", YarnValue::NUMBER(2.0));
 
         let tokens = tokenize("2 + $foo");
         let result = AdditiveExpressionNode::parse(&tokens, 1);
@@ -129,4 +178,27 @@ mod tests {
             Failed => assert!(false),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_fold_additive_expression() {
+        let functions = YarnFunctionMap::new();
+
+        let tokens = tokenize("2 * 3 + 1");
+        match AdditiveExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.fold(&functions).constant(), Some(YarnValue::NUMBER(7.0))),
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("\"a\" + \"b\"");
+        match AdditiveExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.fold(&functions).constant(), Some(YarnValue::STRING("ab".to_string()))),
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("$foo + 1");
+        match AdditiveExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert!(eval.fold(&functions).constant().is_none()),
+            _ => assert!(false),
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use std::any::Any;
 
-use crate::{value::YarnValue, token::{YarnTokenQueue, YarnTokenType, self}, error::{YarnError, YarnResult}};
+use crate::{storage::YarnVariableStorage, value::{YarnValue, YarnValueType}, token::{YarnTokenQueue, YarnTokenType, self}, error::{YarnError, YarnResult}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}};
 
-use super::{YarnEvaluator, YarnExpressionParser, YarnVariableMap, YarnParseResult::{*, self}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext};
 
 pub struct BoolLiteralNode {
     value : bool
@@ -21,9 +21,25 @@ impl BoolLiteralNode {
 }
 
 impl YarnEvaluator for BoolLiteralNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
         Ok(Some(YarnValue::BOOL(self.value)))
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        Ok(Some(YarnValueType::BOOL))
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        self
+    }
+
+    fn constant(&self) -> Option<YarnValue> {
+        Some(YarnValue::BOOL(self.value))
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        node.emit(YarnInstruction::PUSH(YarnValue::BOOL(self.value)), 0, 0);
+    }
 }
 
 impl YarnExpressionParser for BoolLiteralNode {
@@ -49,14 +65,14 @@ impl YarnExpressionParser for BoolLiteralNode {
 }
 
 mod tests {
-    use crate::{token::tokenize};
+    use crate::{storage::YarnMemoryStorage, token::tokenize};
 
     use super::*;
 
     #[test]
     fn test_parse_bool_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
+        let mut variables = YarnMemoryStorage::new();
 
         let tokens = tokenize("true");
         let result = BoolLiteralNode::parse(&tokens, 1);
@@ -1 +1,356 @@
+use crate::{storage::YarnVariableStorage, error::{YarnError, YarnResult}, value::{YarnValue, YarnValueType}, token::{YarnTokenQueue, YarnTokenType}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}, command::interpolate};
 
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext, equality_expression::EqualityExpressionNode};
+
+pub struct SetCommandNode {
+    identifier : String,
+    value : Box<dyn YarnEvaluator>,
+    line : usize,
+    col : usize
+}
+
+impl SetCommandNode {
+    pub fn new(identifier : String, value : Box<dyn YarnEvaluator>, line : usize, col : usize) -> SetCommandNode {
+        SetCommandNode {
+            identifier,
+            value,
+            line,
+            col,
+        }
+    }
+
+    pub fn new_boxed(identifier : String, value : Box<dyn YarnEvaluator>, line : usize, col : usize) -> Box<SetCommandNode> {
+        Box::new(SetCommandNode::new(identifier, value, line, col))
+    }
+}
+
+impl YarnEvaluator for SetCommandNode {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+        match self.value.eval(variables, functions)? {
+            Some(value) => {
+                let value = match variables.get(self.identifier.as_str()) {
+                    Some(current) => value.stored_as(&current, self.line, self.col)?,
+                    None => value,
+                };
+                variables.set(self.identifier.as_str(), value);
+                Ok(None)
+            },
+            None => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
+        }
+    }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let variable_type = match types.variable_type(self.identifier.as_str()) {
+            Some(variable_type) => variable_type,
+            None => return Err(YarnError::new_variable_not_declared_error(self.line, self.col)),
+        };
+
+        self.value.expect_type(&variable_type);
+        match self.value.type_check(types)? {
+            Some(value_type) => {
+                if variable_type.accepts(&value_type) {
+                    Ok(None)
+                } else {
+                    Err(YarnError::new_type_mismatch_error(self.line, self.col, variable_type.get_type_as_string(), value_type.get_type_as_string()))
+                }
+            },
+            None => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        SetCommandNode::new_boxed(node.identifier, node.value.fold(functions), node.line, node.col)
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        self.value.compile(node, symbols);
+        node.emit(YarnInstruction::STORE(symbols.variable_slot(self.identifier.as_str())), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+}
+
+impl YarnExpressionParser for SetCommandNode {
+    fn parse(tokens : &YarnTokenQueue, offset : usize) -> YarnParseResult {
+        if !tokens.check_index(offset, YarnTokenType::START_COMMAND) {
+            return Failed;
+        }
+
+        let keyword_index = tokens.next_non_space_after(offset);
+        if !tokens.check_word(keyword_index, "set") {
+            return Failed;
+        }
+
+        let line = tokens.peek_line(offset);
+        let col = tokens.peek_col(offset);
+
+        let variable_index = tokens.next_non_space_after(keyword_index);
+        if !tokens.check_index(variable_index, YarnTokenType::DOLLAR_SIGN) {
+            return Error(YarnError::new_unexpected_token_error(tokens.peek_line(variable_index), tokens.peek_col(variable_index)));
+        }
+
+        let identifier = match tokens.peek_only_if_type(variable_index + 1, YarnTokenType::WORD) {
+            Some(token) => token.content().to_string(),
+            None => return Error(YarnError::new_invalid_variable_identifier_error(tokens.peek_line(variable_index + 1), tokens.peek_col(variable_index + 1))),
+        };
+
+        let assignment_index = tokens.next_non_space_after(variable_index + 1);
+        if !(tokens.check_word(assignment_index, "to") || tokens.check_index(assignment_index, YarnTokenType::EQUAL)) {
+            return Error(YarnError::new_unexpected_token_error(tokens.peek_line(assignment_index), tokens.peek_col(assignment_index)));
+        }
+
+        let value_index = tokens.next_non_space_after(assignment_index);
+        match EqualityExpressionNode::parse(tokens, value_index) {
+            Parsed(value, endex) => {
+                let end_index = tokens.skip_spaces(endex);
+                if tokens.check_index(end_index, YarnTokenType::END_COMMAND) {
+                    Parsed(SetCommandNode::new_boxed(identifier, value, line, col), end_index + 1)
+                } else {
+                    Error(YarnError::new_unexpected_token_error(tokens.peek_line(end_index), tokens.peek_col(end_index)))
+                }
+            },
+            Error(error) => Error(error),
+            Failed => Error(YarnError::new_unexpected_token_error(tokens.peek_line(value_index), tokens.peek_col(value_index))),
+        }
+    }
+}
+
+//==================================================================================================================
+//                       Generic Command
+//==================================================================================================================
+
+//A command the dialogue does not handle itself, passed to the game as text. Expressions in braces are compiled in
+//place of {0}, {1}, ... and filled in when the command runs.
+pub struct GenericCommandNode {
+    text : String,
+    expressions : Vec<Box<dyn YarnEvaluator>>,
+    line : usize,
+    col : usize
+}
+
+impl GenericCommandNode {
+    pub fn new(text : String, expressions : Vec<Box<dyn YarnEvaluator>>, line : usize, col : usize) -> GenericCommandNode {
+        GenericCommandNode {
+            text,
+            expressions,
+            line,
+            col,
+        }
+    }
+
+    pub fn new_boxed(text : String, expressions : Vec<Box<dyn YarnEvaluator>>, line : usize, col : usize) -> Box<GenericCommandNode> {
+        Box::new(GenericCommandNode::new(text, expressions, line, col))
+    }
+}
+
+impl YarnEvaluator for GenericCommandNode {
+    //Gives the text of the command with its expressions filled in.
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+        let mut values = Vec::new();
+        for expression in self.expressions.iter() {
+            match expression.eval(variables, functions)? {
+                Some(value) => values.push(value),
+                None => return Err(YarnError::new_invalid_operation_error(self.line, self.col)),
+            }
+        }
+
+        Ok(Some(YarnValue::STRING(interpolate(self.text.as_str(), &values))))
+    }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        for expression in self.expressions.iter() {
+            if expression.type_check(types)?.is_none() {
+                let (line, col) = expression.position();
+                return Err(YarnError::new_invalid_operation_error(line, col));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let expressions = node.expressions.into_iter().map(|expression| expression.fold(functions)).collect();
+        GenericCommandNode::new_boxed(node.text, expressions, node.line, node.col)
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        for expression in self.expressions.iter() {
+            expression.compile(node, symbols);
+        }
+        node.emit(YarnInstruction::RUN_COMMAND(self.text.clone(), self.expressions.len()), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+}
+
+impl YarnExpressionParser for GenericCommandNode {
+    fn parse(tokens : &YarnTokenQueue, offset : usize) -> YarnParseResult {
+        if !tokens.check_index(offset, YarnTokenType::START_COMMAND) {
+            return Failed;
+        }
+
+        let line = tokens.peek_line(offset);
+        let col = tokens.peek_col(offset);
+
+        let mut index = tokens.next_non_space_after(offset);
+        if !tokens.check_index(index, YarnTokenType::WORD) {
+            return Error(YarnError::new_unexpected_token_error(tokens.peek_line(index), tokens.peek_col(index)));
+        }
+
+        let mut text = String::new();
+        let mut expressions = Vec::new();
+
+        loop {
+            let token = match tokens.peek(index) {
+                Some(token) => token,
+                None => return Error(YarnError::new_unexpected_token_error(tokens.peek_line(index), tokens.peek_col(index))),
+            };
+
+            match token.token_type() {
+                YarnTokenType::END_COMMAND => break,
+                YarnTokenType::END_LINE | YarnTokenType::EOF | YarnTokenType::RIGHT_BRACE => {
+                    return Error(YarnError::new_unexpected_token_error(token.line(), token.col()));
+                },
+                YarnTokenType::LEFT_BRACE => {
+                    let expression_index = tokens.skip_spaces(index + 1);
+                    match EqualityExpressionNode::parse(tokens, expression_index) {
+                        Parsed(expression, endex) => {
+                            let close_index = tokens.skip_spaces(endex);
+                            if !tokens.check_index(close_index, YarnTokenType::RIGHT_BRACE) {
+                                return Error(YarnError::new_unexpected_token_error(tokens.peek_line(close_index), tokens.peek_col(close_index)));
+                            }
+
+                            text.push_str(format!("{{{}}}", expressions.len()).as_str());
+                            expressions.push(expression);
+                            index = close_index + 1;
+                        },
+                        Error(error) => return Error(error),
+                        Failed => return Error(YarnError::new_unexpected_token_error(tokens.peek_line(expression_index), tokens.peek_col(expression_index))),
+                    }
+                },
+                _ => {
+                    text.push_str(token.content());
+                    index += 1;
+                },
+            }
+        }
+
+        Parsed(GenericCommandNode::new_boxed(text.trim().to_string(), expressions, line, col), index + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::YarnMemoryStorage, token::tokenize, parcer::default_function_map};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_set_command() {
+        let functions = YarnFunctionMap::new();
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("gold", YarnValue::NUMBER(2.0));
+
+        let tokens = tokenize("<<set $gold to $gold * 3>>");
+        let result = SetCommandNode::parse(&tokens, 1);
+        match result {
+            Parsed(eval, endex) => {
+                assert!(eval.eval(&mut variables, &functions).unwrap().is_none());
+                assert_eq!(variables.get("gold"), Some(YarnValue::NUMBER(6.0)));
+                assert_eq!(endex, 16);
+            },
+            Error(_) => assert!(false),
+            Failed => assert!(false),
+        }
+
+        let tokens = tokenize("<<set $gold = 1>>");
+        let result = SetCommandNode::parse(&tokens, 1);
+        match result {
+            Parsed(eval, _) => {
+                eval.eval(&mut variables, &functions).unwrap();
+                assert_eq!(variables.get("gold"), Some(YarnValue::NUMBER(1.0)));
+            },
+            Error(_) => assert!(false),
+            Failed => assert!(false),
+        }
+
+        let tokens = tokenize("<<jump somewhere>>");
+        let result = SetCommandNode::parse(&tokens, 1);
+        assert!(matches!(result, Failed));
+    }
+
+    #[test]
+    fn test_parse_generic_command() {
+        let functions = default_function_map();
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("name", YarnValue::STRING("Mae".to_string()));
+
+        let tokens = tokenize("<<walk {$name} \"to the left\" {round(2.6) + 1}>>");
+        match GenericCommandNode::parse(&tokens, 1) {
+            Parsed(eval, _) => {
+                let value = eval.eval(&mut variables, &functions).unwrap();
+                assert_eq!(value, Some(YarnValue::STRING("walk Mae \"to the left\" 4".to_string())));
+
+                let types = YarnTypeContext::from_maps(&variables.to_map(), &functions);
+                assert!(eval.type_check(&types).unwrap().is_none());
+            },
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("<<walk {$name>>");
+        assert!(matches!(GenericCommandNode::parse(&tokens, 1), Error(_)));
+
+        let tokens = tokenize("<<$name>>");
+        assert!(matches!(GenericCommandNode::parse(&tokens, 1), Error(_)));
+    }
+
+    #[test]
+    fn test_type_check_set_command() {
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("gold", YarnValue::NUMBER(2.0));
+        variables.set("name", YarnValue::STRING("Mae".to_string()));
+        let types = YarnTypeContext::from_maps(&variables.to_map(), &default_function_map());
+
+        let tokens = tokenize("<<set $gold to round($gold * 1.5)>>");
+        match SetCommandNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert!(eval.type_check(&types).is_ok()),
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("<<set $gold to $name>>");
+        match SetCommandNode::parse(&tokens, 1) {
+            Parsed(eval, _) => {
+                let error = eval.type_check(&types).unwrap_err();
+                assert_eq!(error.error_name(), "Type Mismatch Error");
+            },
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("<<set $name to \"a\" * 2>>");
+        match SetCommandNode::parse(&tokens, 1) {
+            Parsed(eval, _) => {
+                let error = eval.type_check(&types).unwrap_err();
+                assert_eq!(error.error_message(), "The value is the wrong type. Type expected: NUMBER | Type received: STRING");
+            },
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("<<set $missing to 1>>");
+        match SetCommandNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.type_check(&types).unwrap_err().error_name(), "Variable Not Declared Error"),
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("<<set $gold to round(1, 2)>>");
+        match SetCommandNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.type_check(&types).unwrap_err().error_name(), "Invalid Argument Count Error"),
+            _ => assert!(false),
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{error::YarnError, token::{YarnTokenQueue, YarnTokenType}};
+use crate::{storage::YarnVariableStorage, error::{YarnError, YarnResult}, token::{YarnTokenQueue, YarnTokenType}, value::{YarnValueType, DEFAULT_TOLERANCE}, program::{YarnProgramNode, YarnSymbols, YarnInstruction, YarnOperator}};
 
-use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, factor_expression::FactorExpressionNode, additive_expression::AdditiveExpressionNode, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, factor_expression::FactorExpressionNode, additive_expression::AdditiveExpressionNode, YarnFunctionMap, YarnTypeContext, fold_constant, comparable_constants};
 
 pub enum ComparisonOperator {
     LESS_THAN,
@@ -31,10 +31,19 @@ impl ComparisonExpressionNode {
     pub fn new_boxed(lhs : Box<dyn YarnEvaluator>, rhs : Box<dyn YarnEvaluator>, operator : ComparisonOperator, line : usize, col : usize) -> Box<ComparisonExpressionNode> {
         Box::new(ComparisonExpressionNode::new(lhs, rhs, operator, line, col))
     }
+
+    fn operator(&self) -> YarnOperator {
+        match self.operator {
+            ComparisonOperator::LESS_THAN => YarnOperator::LESS_THAN,
+            ComparisonOperator::GREATER_THAN => YarnOperator::GREATER_THAN,
+            ComparisonOperator::GREATER_THAN_EQ => YarnOperator::GREATER_THAN_EQ,
+            ComparisonOperator::LESS_THAN_EQ => YarnOperator::LESS_THAN_EQ,
+        }
+    }
 }
 
 impl YarnEvaluator for ComparisonExpressionNode {
-    fn eval(&self, variables : &mut super::YarnVariableMap, functions : &YarnFunctionMap) -> crate::error::YarnResult<Option<crate::value::YarnValue>> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> crate::error::YarnResult<Option<crate::value::YarnValue>> {
         let lhs_value = self.lhs.eval(variables, functions);
         let rhs_value = self.rhs.eval(variables, functions);
 
@@ -45,18 +54,7 @@ impl YarnEvaluator for ComparisonExpressionNode {
                 let lhs_value = lhs_value.unwrap();
                 let rhs_value = rhs_value.unwrap();
 
-                let result = match self.operator {
-                    ComparisonOperator::LESS_THAN => lhs_value.is_less_than(&rhs_value),
-                    ComparisonOperator::GREATER_THAN => lhs_value.is_greater_than(&rhs_value),
-                    ComparisonOperator::GREATER_THAN_EQ => lhs_value.is_greater_than_eq(&rhs_value),
-                    ComparisonOperator::LESS_THAN_EQ => lhs_value.is_less_than_eq(&rhs_value),
-                };
-
-                if let Some(result) = result {
-                    Ok(Some(result))
-                } else {
-                    Err(YarnError::new_invalid_operation_error(self.line, self.col))
-                }
+                self.operator().apply(&[lhs_value, rhs_value], DEFAULT_TOLERANCE, self.line, self.col).map(Some)
             } else {
                 Err(YarnError::new_invalid_operation_error(self.line, self.col))
             }
@@ -68,6 +66,46 @@ impl YarnEvaluator for ComparisonExpressionNode {
             }
         }
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let lhs_type = self.lhs.type_check(types)?;
+        let rhs_type = self.rhs.type_check(types)?;
+
+        if let (Some(lhs_type), Some(rhs_type)) = (lhs_type, rhs_type) {
+            if let Some(result) = lhs_type.compare(&rhs_type) {
+                Ok(Some(result))
+            } else {
+                let received = if YarnValueType::NUMBER.accepts(&lhs_type) { rhs_type } else { lhs_type };
+                Err(YarnError::new_type_mismatch_error(self.line, self.col, "NUMBER", received.get_type_as_string()))
+            }
+        } else {
+            Err(YarnError::new_invalid_operation_error(self.line, self.col))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let lhs = node.lhs.fold(functions);
+        let rhs = node.rhs.fold(functions);
+        let is_constant = comparable_constants(lhs.as_ref(), rhs.as_ref());
+
+        let folded = ComparisonExpressionNode::new_boxed(lhs, rhs, node.operator, node.line, node.col);
+        if is_constant {
+            fold_constant(folded, functions)
+        } else {
+            folded
+        }
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        self.lhs.compile(node, symbols);
+        self.rhs.compile(node, symbols);
+        node.emit(YarnInstruction::OPERATOR(self.operator()), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
 }
 
 impl YarnExpressionParser for ComparisonExpressionNode {
@@ -104,15 +142,15 @@ mod tests {
 
     use std::env::var;
 
-    use crate::{token::tokenize, parcer::YarnVariableMap, value::YarnValue};
+    use crate::{token::tokenize, storage::YarnMemoryStorage, value::YarnValue};
 
     use super::*;
 
     #[test]
     fn test_parse_variable_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("test".to_string(), YarnValue::BOOL(true));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("test", YarnValue::BOOL(true));
 
         let tokens = tokenize("3 > 2");
         let result = ComparisonExpressionNode::parse(&tokens, 1);
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+
+use crate::{storage::YarnVariableStorage, value::{YarnValue, YarnValueType}, token::{YarnTokenQueue, YarnTokenType}, error::{YarnError, YarnResult}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}};
+
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext};
+
+//A case of an enum, written as Mood.Happy or as .Happy when the enum is known from where it is used or only one enum
+//has a case with that name.
+pub struct EnumCaseNode {
+    enum_name : Option<String>,
+    case : String,
+    expected : RefCell<Option<String>>, // The enum the code around a .Case needs, given before it is type checked
+    line : usize,
+    col : usize
+}
+
+impl EnumCaseNode {
+    pub fn new(enum_name : Option<String>, case : String, line : usize, col : usize) -> EnumCaseNode {
+        EnumCaseNode {
+            enum_name,
+            case,
+            expected: RefCell::new(None),
+            line,
+            col,
+        }
+    }
+
+    pub fn new_boxed(enum_name : Option<String>, case : String, line : usize, col : usize) -> Box<EnumCaseNode> {
+        Box::new(EnumCaseNode::new(enum_name, case, line, col))
+    }
+
+    fn known_enum(&self) -> Option<String> {
+        self.enum_name.clone().or_else(|| self.expected.borrow().clone())
+    }
+}
+
+impl YarnEvaluator for EnumCaseNode {
+    //Without an enum name or an expected enum the case can only be worked out from the declared enums, which eval
+    //does not have.
+    fn eval(&self, _variables : &mut dyn YarnVariableStorage, _functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+        match self.known_enum() {
+            Some(name) => Ok(Some(YarnValue::ENUM(name, self.case.clone()))),
+            None => Err(YarnError::new_enum_error(self.line, self.col, format!("The enum of '.{}' is not known here, write it with the name of its enum.", self.case).as_str())),
+        }
+    }
+
+    //An expected enum without this case is ignored, so the case is looked up by name and the mismatch reported by
+    //whatever expected it.
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let expected = self.expected.borrow().clone().filter(|name| types.enum_case_type(name, self.case.as_str(), self.line, self.col).is_ok());
+        match self.enum_name.clone().or(expected) {
+            Some(name) => types.enum_case_type(name.as_str(), self.case.as_str(), self.line, self.col).map(Some),
+            None => match types.enum_of_case(self.case.as_str()) {
+                Some(name) => Ok(Some(YarnValueType::ENUM(name.to_string()))),
+                None => Err(YarnError::new_enum_error(self.line, self.col, format!("'.{}' is not a case of exactly one enum, write it with the name of its enum.", self.case).as_str())),
+            },
+        }
+    }
+
+    fn fold(self : Box<Self>, _functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        self
+    }
+
+    fn constant(&self) -> Option<YarnValue> {
+        self.known_enum().map(|name| YarnValue::ENUM(name, self.case.clone()))
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        let enum_name = match self.known_enum() {
+            Some(name) => name,
+            None => symbols.enum_of_case(self.case.as_str()).unwrap_or_default().to_string(),
+        };
+        node.emit(YarnInstruction::PUSH(YarnValue::ENUM(enum_name, self.case.clone())), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+
+    fn expect_type(&self, expected : &YarnValueType) {
+        if let (None, YarnValueType::ENUM(name)) = (&self.enum_name, expected) {
+            *self.expected.borrow_mut() = Some(name.clone());
+        }
+    }
+}
+
+impl YarnExpressionParser for EnumCaseNode {
+    fn parse(tokens : &YarnTokenQueue, offset : usize) -> YarnParseResult {
+        let line = tokens.peek_line(offset);
+        let col = tokens.peek_col(offset);
+
+        if tokens.check_index(offset, YarnTokenType::PERIOD) {
+            return match tokens.identifier(offset + 1) {
+                Some((case, endex)) => Parsed(EnumCaseNode::new_boxed(None, case, line, col), endex),
+                None => Failed,
+            };
+        }
+
+        let (name, name_end) = match tokens.identifier(offset) {
+            Some((name, name_end)) if !name.starts_with(|c : char| c.is_ascii_digit()) => (name, name_end),
+            _ => return Failed,
+        };
+
+        if !tokens.check_index(name_end, YarnTokenType::PERIOD) {
+            return Failed;
+        }
+
+        match tokens.identifier(name_end + 1) {
+            Some((case, endex)) => Parsed(EnumCaseNode::new_boxed(Some(name), case, line, col), endex),
+            None => Error(YarnError::new_unexpected_token_error(tokens.peek_line(name_end + 1), tokens.peek_col(name_end + 1))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::YarnMemoryStorage, token::tokenize};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_enum_case() {
+        let functions = YarnFunctionMap::new();
+        let mut variables = YarnMemoryStorage::new();
+        let mut types = YarnTypeContext::new();
+        types.declare_enum("Mood", vec!["Happy".to_string(), "Friendly".to_string()]);
+        types.declare_enum("Weather", vec!["Sunny".to_string(), "Happy".to_string()]);
+
+        let tokens = tokenize("Mood.Friendly");
+        match EnumCaseNode::parse(&tokens, 1) {
+            Parsed(eval, endex) => {
+                assert_eq!(endex, tokens.identifier(3).unwrap().1);
+                assert_eq!(eval.eval(&mut variables, &functions).unwrap(), Some(YarnValue::ENUM("Mood".to_string(), "Friendly".to_string())));
+                assert_eq!(eval.type_check(&types).unwrap(), Some(YarnValueType::ENUM("Mood".to_string())));
+            },
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize(".Sunny");
+        match EnumCaseNode::parse(&tokens, 1) {
+            Parsed(eval, _) => {
+                assert_eq!(eval.type_check(&types).unwrap(), Some(YarnValueType::ENUM("Weather".to_string())));
+                assert_eq!(eval.eval(&mut variables, &functions).unwrap_err().error_name(), "Enum Error");
+            },
+            _ => assert!(false),
+        }
+
+        for source in [".Happy", "Mood.Sunny", "Season.Spring"] {
+            match EnumCaseNode::parse(&tokenize(source), 1) {
+                Parsed(eval, _) => assert_eq!(eval.type_check(&types).unwrap_err().error_name(), "Enum Error"),
+                _ => assert!(false),
+            }
+        }
+
+        match EnumCaseNode::parse(&tokenize(".Happy"), 1) {
+            Parsed(eval, _) => {
+                eval.expect_type(&YarnValueType::ENUM("Weather".to_string()));
+                assert_eq!(eval.type_check(&types).unwrap(), Some(YarnValueType::ENUM("Weather".to_string())));
+                assert_eq!(eval.eval(&mut variables, &functions).unwrap(), Some(YarnValue::ENUM("Weather".to_string(), "Happy".to_string())));
+            },
+            _ => assert!(false),
+        }
+
+        assert!(matches!(EnumCaseNode::parse(&tokenize("2.5"), 1), Failed));
+        assert!(matches!(EnumCaseNode::parse(&tokenize("Mood"), 1), Failed));
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{error::YarnError, token::YarnTokenType};
+use crate::{storage::YarnVariableStorage, error::{YarnError, YarnResult}, token::YarnTokenType, value::{YarnValueType, DEFAULT_TOLERANCE}, program::{YarnProgramNode, YarnSymbols, YarnInstruction, YarnOperator}};
 
-use super::{YarnEvaluator, YarnExpressionParser, comparison_expression::ComparisonExpressionNode, YarnParseResult::{*, self}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, comparison_expression::ComparisonExpressionNode, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext, fold_constant, comparable_constants};
 
 pub enum EqualityOperator {
     EQUAL_TOO,
@@ -29,10 +29,17 @@ impl EqualityExpressionNode {
     pub fn new_boxed(lhs : Box<dyn YarnEvaluator>, rhs : Box<dyn YarnEvaluator>, operator : EqualityOperator, line : usize, col : usize) -> Box<EqualityExpressionNode> {
         Box::new(EqualityExpressionNode::new(lhs, rhs, operator, line, col))
     }
+
+    fn operator(&self) -> YarnOperator {
+        match self.operator {
+            EqualityOperator::EQUAL_TOO => YarnOperator::EQUAL_TOO,
+            EqualityOperator::NOT_EQUAL_TOO => YarnOperator::NOT_EQUAL_TOO,
+        }
+    }
 }
 
 impl YarnEvaluator for EqualityExpressionNode {
-    fn eval(&self, variables : &mut super::YarnVariableMap, functions : &YarnFunctionMap) -> crate::error::YarnResult<Option<crate::value::YarnValue>> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> crate::error::YarnResult<Option<crate::value::YarnValue>> {
         let lhs_value = self.lhs.eval(variables, functions);
         let rhs_value = self.rhs.eval(variables, functions);
 
@@ -43,16 +50,7 @@ impl YarnEvaluator for EqualityExpressionNode {
                 let lhs_value = lhs_value.unwrap();
                 let rhs_value = rhs_value.unwrap();
 
-                let result = match self.operator {
-                    EqualityOperator::EQUAL_TOO => lhs_value.is_equal(&rhs_value),
-                    EqualityOperator::NOT_EQUAL_TOO => lhs_value.is_not_equal(&rhs_value),
-                };
-
-                if let Some(result) = result {
-                    Ok(Some(result))
-                } else {
-                    Err(YarnError::new_invalid_operation_error(self.line, self.col))
-                }
+                self.operator().apply(&[lhs_value, rhs_value], DEFAULT_TOLERANCE, self.line, self.col).map(Some)
             } else {
                 Err(YarnError::new_invalid_operation_error(self.line, self.col))
             }
@@ -64,6 +62,56 @@ impl YarnEvaluator for EqualityExpressionNode {
             }
         }
     }
+
+    //Either side can be a .Case that takes its enum from the other side.
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let lhs_type = self.lhs.type_check(types);
+        if let Ok(Some(lhs_type)) = &lhs_type {
+            self.rhs.expect_type(lhs_type);
+        }
+        let rhs_type = self.rhs.type_check(types)?;
+        let lhs_type = match (lhs_type, &rhs_type) {
+            (Err(_), Some(rhs_type)) => {
+                self.lhs.expect_type(rhs_type);
+                self.lhs.type_check(types)?
+            },
+            (lhs_type, _) => lhs_type?,
+        };
+
+        if let (Some(lhs_type), Some(rhs_type)) = (lhs_type, rhs_type) {
+            if let Some(result) = lhs_type.is_equal(&rhs_type) {
+                Ok(Some(result))
+            } else {
+                Err(YarnError::new_type_mismatch_error(self.line, self.col, lhs_type.get_type_as_string(), rhs_type.get_type_as_string()))
+            }
+        } else {
+            Err(YarnError::new_invalid_operation_error(self.line, self.col))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let lhs = node.lhs.fold(functions);
+        let rhs = node.rhs.fold(functions);
+        let is_constant = comparable_constants(lhs.as_ref(), rhs.as_ref());
+
+        let folded = EqualityExpressionNode::new_boxed(lhs, rhs, node.operator, node.line, node.col);
+        if is_constant {
+            fold_constant(folded, functions)
+        } else {
+            folded
+        }
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        self.lhs.compile(node, symbols);
+        self.rhs.compile(node, symbols);
+        node.emit(YarnInstruction::OPERATOR(self.operator()), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
 }
 
 impl YarnExpressionParser for EqualityExpressionNode {
@@ -96,15 +144,15 @@ mod tests {
 
     use std::env::var;
 
-    use crate::{token::tokenize, parcer::YarnVariableMap, value::YarnValue};
+    use crate::{token::tokenize, storage::YarnMemoryStorage, value::YarnValue};
 
     use super::*;
 
     #[test]
     fn test_parse_variable_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("test".to_string(), YarnValue::BOOL(true));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("test", YarnValue::BOOL(true));
 
         let tokens = tokenize("2 == 2");
         let result = EqualityExpressionNode::parse(&tokens, 1);
@@ -1,8 +1,8 @@
 use std::result;
 
-use crate::{error::{YarnResult, YarnError}, value::YarnValue, token::{YarnTokenQueue, YarnTokenType}};
+use crate::{storage::YarnVariableStorage, error::{YarnResult, YarnError}, value::{YarnValue, YarnValueType, DEFAULT_TOLERANCE}, token::{YarnTokenQueue, YarnTokenType}, program::{YarnProgramNode, YarnSymbols, YarnInstruction, YarnOperator}};
 
-use super::{YarnEvaluator, YarnVariableMap, YarnExpressionParser, unary_expression::UnaryExpressionNode, primary_expression::YarnValueType, YarnParseResult::{*, self}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, unary_expression::UnaryExpressionNode, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext, fold_constant};
 
 pub enum FactorOperator {
     MUL,
@@ -31,10 +31,17 @@ impl FactorExpressionNode {
     pub fn new_boxed(lhs : Box<dyn YarnEvaluator>, rhs : Box<dyn YarnEvaluator>, operator : FactorOperator, line : usize, col : usize) -> Box<FactorExpressionNode> {
         Box::new(FactorExpressionNode::new(lhs, rhs, operator, line, col))
     }
+
+    fn operator(&self) -> YarnOperator {
+        match self.operator {
+            FactorOperator::MUL => YarnOperator::MUL,
+            FactorOperator::DIV => YarnOperator::DIV,
+        }
+    }
 }
 
 impl YarnEvaluator for FactorExpressionNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
         let lhs_value = self.lhs.eval(variables, functions);
         let rhs_value = self.rhs.eval(variables, functions);
 
@@ -45,16 +52,7 @@ impl YarnEvaluator for FactorExpressionNode {
                 let lhs_value = lhs_value.unwrap();
                 let rhs_value = rhs_value.unwrap();
 
-                let result = match self.operator {
-                    FactorOperator::MUL => lhs_value.mult(&rhs_value),
-                    FactorOperator::DIV => lhs_value.div(&rhs_value),
-                };
-
-                if let Some(result) = result {
-                    Ok(Some(result))
-                } else {
-                    Err(YarnError::new_invalid_operation_error(self.line, self.col))
-                }
+                self.operator().apply(&[lhs_value, rhs_value], DEFAULT_TOLERANCE, self.line, self.col).map(Some)
             } else {
                 Err(YarnError::new_invalid_operation_error(self.line, self.col))
             }
@@ -66,6 +64,46 @@ impl YarnEvaluator for FactorExpressionNode {
             }
         }
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let lhs_type = self.lhs.type_check(types)?;
+        let rhs_type = self.rhs.type_check(types)?;
+
+        if let (Some(lhs_type), Some(rhs_type)) = (lhs_type, rhs_type) {
+            if let Some(result) = lhs_type.arithmetic(&rhs_type) {
+                Ok(Some(result))
+            } else {
+                let received = if YarnValueType::NUMBER.accepts(&lhs_type) { rhs_type } else { lhs_type };
+                Err(YarnError::new_type_mismatch_error(self.line, self.col, "NUMBER", received.get_type_as_string()))
+            }
+        } else {
+            Err(YarnError::new_invalid_operation_error(self.line, self.col))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let lhs = node.lhs.fold(functions);
+        let rhs = node.rhs.fold(functions);
+        let is_constant = lhs.constant().is_some() && rhs.constant().is_some();
+
+        let folded = FactorExpressionNode::new_boxed(lhs, rhs, node.operator, node.line, node.col);
+        if is_constant {
+            fold_constant(folded, functions)
+        } else {
+            folded
+        }
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        self.lhs.compile(node, symbols);
+        self.rhs.compile(node, symbols);
+        node.emit(YarnInstruction::OPERATOR(self.operator()), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
 }
 
 impl YarnExpressionParser for FactorExpressionNode {
@@ -98,15 +136,15 @@ mod tests {
 
     use std::env::var;
 
-    use crate::{token::tokenize, parcer::YarnFunctionMap};
+    use crate::{storage::YarnMemoryStorage, token::tokenize, parcer::YarnFunctionMap};
 
     use super::*;
 
     #[test]
     fn test_parse_variable_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("test".to_string(), YarnValue::BOOL(true));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("test", YarnValue::BOOL(true));
 
         let tokens = tokenize("2 * 2");
         let result = FactorExpressionNode::parse(&tokens, 1);
@@ -163,4 +201,23 @@ mod tests {
             Failed => assert!(false),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_type_check_factor_expression() {
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("gold", YarnValue::NUMBER(2.0));
+        let types = YarnTypeContext::from_maps(&variables.to_map(), &YarnFunctionMap::new());
+
+        let tokens = tokenize("$gold * 2");
+        match FactorExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.type_check(&types).unwrap(), Some(YarnValueType::NUMBER)),
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("\"a\" * 2");
+        match FactorExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.type_check(&types).unwrap_err().error_name(), "Type Mismatch Error"),
+            _ => assert!(false),
+        }
+    }
+}
@@ -1,9 +1,7 @@
-use std::i16::MIN;
+use std::{i16::MIN, cmp::Ordering};
 
-use rand::Rng;
-
-use crate::{error::{YarnResult, YarnError}, value::YarnValue::{*, self}, token::{YarnTokenQueue, YarnTokenType}};
-use super::{YarnEvaluator, YarnVariableMap, YarnFunctionMap, YarnExpressionParser, YarnParseResult::{*, self}, parse_expression, equality_expression::EqualityExpressionNode};
+use crate::{storage::YarnVariableStorage, error::{YarnResult, YarnError}, value::{YarnValue::{*, self}, YarnValueType, DEFAULT_TOLERANCE}, decimal::YarnDecimal, token::{YarnTokenQueue, YarnTokenType}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}};
+use super::{YarnEvaluator, YarnFunctionMap, YarnFunctionContext, YarnExpressionParser, YarnParseResult::{*, self}, parse_expression, equality_expression::EqualityExpressionNode, YarnTypeContext, fold_constant};
 
 pub struct FunctionNode {
     arguments : Vec<Box<dyn YarnEvaluator>>,
@@ -28,7 +26,7 @@ impl FunctionNode {
 }
 
 impl YarnEvaluator for FunctionNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
         if functions.contains_key(&self.function_name) {
             let mut values = Vec::new();
             for eval in self.arguments.iter() {
@@ -40,11 +38,72 @@ impl YarnEvaluator for FunctionNode {
                     Err(error) => return Err(error),
                 }
             }
-            functions.get(&self.function_name).unwrap()(values, self.line, self.col)
+            let context = YarnFunctionContext::new(variables, None, self.line, self.col);
+            functions.get(&self.function_name).unwrap().call(values, &context)
         } else {
             Err(YarnError::new_undefined_function_error(self.line, self.col))
         }
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        if !types.has_function(&self.function_name) {
+            return Err(YarnError::new_undefined_function_error(self.line, self.col));
+        }
+
+        let mut argument_types = Vec::new();
+        for eval in self.arguments.iter() {
+            match eval.type_check(types)? {
+                Some(argument_type) => argument_types.push(argument_type),
+                None => return Err(YarnError::new_null_function_arg_error(self.line, self.col)),
+            }
+        }
+
+        if let Some(signature) = types.function_signature(&self.function_name) {
+            if argument_types.len() < signature.min_arguments() {
+                return Err(YarnError::new_invalid_argument_count_error(self.line, self.col, signature.min_arguments(), argument_types.len()));
+            }
+
+            if let Some(max) = signature.max_arguments().filter(|max| argument_types.len() > *max) {
+                return Err(YarnError::new_invalid_argument_count_error(self.line, self.col, max, argument_types.len()));
+            }
+
+            for (index, received) in argument_types.iter().enumerate() {
+                let expected = signature.parameter_type(index).unwrap_or(YarnValueType::ANY);
+                if !expected.accepts(received) {
+                    return Err(YarnError::new_type_mismatch_error(self.line, self.col, expected.get_type_as_string(), received.get_type_as_string()));
+                }
+            }
+
+            Ok(Some(signature.return_type()))
+        } else {
+            Ok(Some(YarnValueType::ANY))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let arguments : Vec<Box<dyn YarnEvaluator>> = node.arguments.into_iter().map(|argument| argument.fold(functions)).collect();
+        let is_pure = functions.get(&node.function_name).map(|function| function.is_pure()).unwrap_or(false);
+        let is_constant = is_pure && arguments.iter().all(|argument| argument.constant().is_some());
+
+        let folded = FunctionNode::new_boxed(arguments, node.function_name, node.line, node.col);
+        if is_constant {
+            fold_constant(folded, functions)
+        } else {
+            folded
+        }
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        for argument in self.arguments.iter() {
+            argument.compile(node, symbols);
+        }
+        node.emit(YarnInstruction::CALL(symbols.function_slot(self.function_name.as_str()), self.arguments.len()), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
 }
 
 impl YarnExpressionParser for FunctionNode {
@@ -66,6 +125,7 @@ impl YarnExpressionParser for FunctionNode {
                         Parsed(eval, endex) => {
                             evals.push(eval);
                             args_offset += endex - current_index;
+                            ran_into_comma = false;
                         },
                         Error(err) => {
                             return Error(err);
@@ -98,7 +158,7 @@ impl YarnExpressionParser for FunctionNode {
                     function_id.content().to_string(),
                     tokens.peek_line(offset),
                     tokens.peek_col(offset)
-                ), offset + args_start + args_offset)
+                ), args_start + args_offset + 1)
             } else {
                 Failed
             }
@@ -113,18 +173,18 @@ impl YarnExpressionParser for FunctionNode {
 //==================================================================================================================
 
 macro_rules! check_arg {
-    ($args:ident, $index:expr, $type:ident, $line:expr, $col:expr) => {
+    ($args:ident, $index:expr, $type:ident, $context:expr) => {
         {
             let mut result = &YarnValue::BOOL(true);
 
             if let Some(value) = $args.get($index) {
-                if let $type(_) = value {
+                if value.get_type() == YarnValueType::$type {
                     result = value;
                 } else {
-                    return Err(YarnError::new_type_mismatch_error($line, $col, stringify!($type), value.get_type_as_string()));
+                    return Err(YarnError::new_type_mismatch_error($context.line(), $context.col(), stringify!($type), value.get_type_as_string()));
                 }
             } else {
-                return Err(YarnError::new_null_function_arg_error($line, $col));
+                return Err(YarnError::new_null_function_arg_error($context.line(), $context.col()));
             }
 
             result
@@ -132,43 +192,40 @@ macro_rules! check_arg {
     };
 }
 
-pub fn dice(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let mut rng = rand::thread_rng();
-    let sides = check_arg!(arguments, 0, NUMBER, line, col);
-    if let Some(sides) = arguments.get(0) {
-        if let YarnValue::NUMBER(sides) = sides {
-            Ok(Some(YarnValue::NUMBER(rng.gen_range(0.0..=*sides).round())))
-        } else {
-            Err(YarnError::new_type_mismatch_error(line, col, "NUMBER", sides.get_type_as_string()))
-        }
-    } else {
-        Err(YarnError::new_null_function_arg_error(line, col))
-    }
+pub fn dice(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let sides = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
+    //next_f64 never reaches 1, so every side from 1 to sides is equally likely and none past it can come up.
+    Ok(Some(YarnValue::NUMBER((context.random(|random| random.next_f64()) * sides).floor() + 1.0)))
 }
 
-pub fn random(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let mut rng = rand::thread_rng();
-    Ok(Some(YarnValue::NUMBER(rng.gen())))
+pub fn random(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    Ok(Some(YarnValue::NUMBER(context.random(|random| random.next_f64()))))
 }
 
-pub fn random_range(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let min = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
-    let max = check_arg!(arguments, 1, NUMBER, line, col).as_f64().unwrap();
-
-    println!("{} .. {} | {:?}", min, max, min..=max);
-
-    let mut rng = rand::thread_rng();
-    Ok(Some(YarnValue::NUMBER(rng.gen_range(min..=max))))
+pub fn random_range(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let min = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
+    let max = check_arg!(arguments, 1, NUMBER, context).as_f64().unwrap();
+    let (low, high) = (min.min(max).round(), min.max(max).round());
+    Ok(Some(YarnValue::NUMBER(low + (context.random(|random| random.next_f64()) * (high - low + 1.0)).floor())))
 }
 
-pub fn round(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
-    Ok(Some(NUMBER(value.round())))
+pub fn round(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    match check_arg!(arguments, 0, NUMBER, context) {
+        INT(value) => Ok(Some(INT(*value))),
+        DECIMAL(value) => Ok(Some(DECIMAL(round_decimal(*value, 0, context)?))),
+        value => Ok(Some(NUMBER(value.as_f64().unwrap().round()))),
+    }
 }
 
-pub fn round_places(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
-    let places = check_arg!(arguments, 1, NUMBER, line, col).as_f64().unwrap();
+//Exact numbers are rounded exactly, so 2.675 becomes 2.68 rather than the 2.67 a float gives.
+pub fn round_places(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let value = check_arg!(arguments, 0, NUMBER, context);
+    let places = check_arg!(arguments, 1, NUMBER, context).as_f64().unwrap();
+    let value = match value {
+        INT(value) => return Ok(Some(INT(*value))),
+        DECIMAL(value) => return Ok(Some(DECIMAL(round_decimal(*value, places.max(0.0) as u32, context)?))),
+        value => value.as_f64().unwrap(),
+    };
 
     let signifigance = 10.0_f64.powi(places as i32);
     let value = ((value * signifigance).round()) / signifigance;
@@ -176,18 +233,22 @@ pub fn round_places(arguments : Vec<YarnValue>, line : usize, col : usize) -> Ya
     Ok(Some(NUMBER(value)))
 }
 
-pub fn floor(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
+fn round_decimal(value : YarnDecimal, places : u32, context : &YarnFunctionContext) -> YarnResult<YarnDecimal> {
+    value.round_places(places).ok_or_else(|| context.error("The result is too large to be a number."))
+}
+
+pub fn floor(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let value = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
     Ok(Some(NUMBER(value.floor())))
 }
 
-pub fn ceil(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
+pub fn ceil(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let value = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
     Ok(Some(NUMBER(value.ceil())))
 }
 
-pub fn inc(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let mut value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
+pub fn inc(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let mut value = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
 
     if value.fract() != 0.0 {
         value = value.ceil()
@@ -198,8 +259,8 @@ pub fn inc(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<
     Ok(Some(NUMBER(value)))
 }
 
-pub fn dec(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let mut value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
+pub fn dec(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let mut value = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
 
     if value.fract() != 0.0 {
         value = value.floor()
@@ -210,32 +271,155 @@ pub fn dec(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<
     Ok(Some(NUMBER(value)))
 }
 
-pub fn decimal(arguments : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
-    let value = check_arg!(arguments, 0, NUMBER, line, col).as_f64().unwrap();
+pub fn decimal(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let value = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
     Ok(Some(NUMBER(value - value.floor())))
 }
 
+pub fn string(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    match arguments.first() {
+        Some(value) => Ok(Some(STRING(value.to_string()))),
+        None => Err(YarnError::new_null_function_arg_error(context.line(), context.col())),
+    }
+}
+
+pub fn number(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    match arguments.first() {
+        Some(STRING(value)) => match value.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Some(NUMBER(number))),
+            _ => Err(context.error(format!("'{}' is not a number.", value).as_str())),
+        },
+        Some(value @ (NUMBER(_) | INT(_) | DECIMAL(_))) => Ok(Some(value.clone())),
+        Some(BOOL(value)) => Ok(Some(NUMBER(if *value { 1.0 } else { 0.0 }))),
+        Some(value @ ENUM(..)) => Err(YarnError::new_type_mismatch_error(context.line(), context.col(), "NUMBER", value.get_type_as_string())),
+        None => Err(YarnError::new_null_function_arg_error(context.line(), context.col())),
+    }
+}
+
+pub fn bool(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    match arguments.first() {
+        Some(STRING(value)) => match value.trim().to_lowercase().as_str() {
+            "true" => Ok(Some(BOOL(true))),
+            "false" => Ok(Some(BOOL(false))),
+            _ => Err(context.error(format!("'{}' is not true or false.", value).as_str())),
+        },
+        Some(value @ (NUMBER(_) | INT(_) | DECIMAL(_))) => Ok(Some(BOOL(value.as_f64() != Some(0.0)))),
+        Some(BOOL(value)) => Ok(Some(BOOL(*value))),
+        Some(value @ ENUM(..)) => Err(YarnError::new_type_mismatch_error(context.line(), context.col(), "BOOL", value.get_type_as_string())),
+        None => Err(YarnError::new_null_function_arg_error(context.line(), context.col())),
+    }
+}
+
+pub fn int(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    match check_arg!(arguments, 0, NUMBER, context) {
+        INT(value) => Ok(Some(INT(*value))),
+        DECIMAL(value) => Ok(Some(INT(value.trunc()))),
+        value => {
+            let value = value.as_f64().unwrap().trunc();
+            if !(i64::MIN as f64..i64::MAX as f64).contains(&value) {
+                return Err(context.error(format!("{} is too large to be a whole number.", value).as_str()));
+            }
+            Ok(Some(INT(value as i64)))
+        }
+    }
+}
+
+//min, max and abs give back the same kind of number they were given. min and max take any number of arguments, so
+//the smallest or largest of a list can be found without nesting calls.
+fn pick_number(arguments : &[YarnValue], keep : Ordering, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let mut picked = check_arg!(arguments, 0, NUMBER, context);
+    for index in 1..arguments.len() {
+        let next = check_arg!(arguments, index, NUMBER, context);
+        picked = match (picked, next) {
+            (NUMBER(first), NUMBER(second)) if second.partial_cmp(first) == Some(keep) => next,
+            (NUMBER(_), NUMBER(_)) => picked,
+            _ if next.compare(picked, DEFAULT_TOLERANCE) == Some(keep) => next,
+            _ => picked,
+        };
+    }
+    Ok(Some(picked.clone()))
+}
+
+pub fn min(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    pick_number(&arguments, Ordering::Less, context)
+}
+
+pub fn max(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    pick_number(&arguments, Ordering::Greater, context)
+}
+
+pub fn abs(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    match check_arg!(arguments, 0, NUMBER, context) {
+        INT(value) => value.checked_abs().map(|value| Some(INT(value))).ok_or_else(|| context.error("The result is too large to be a number.")),
+        DECIMAL(value) => value.abs().map(|value| Some(DECIMAL(value))).ok_or_else(|| context.error("The result is too large to be a number.")),
+        value => Ok(Some(NUMBER(value.as_f64().unwrap().abs()))),
+    }
+}
+
+pub fn pow(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let base = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
+    let exponent = check_arg!(arguments, 1, NUMBER, context).as_f64().unwrap();
+    let result = base.powf(exponent);
+    if !result.is_finite() {
+        return Err(context.error(format!("{} to the power of {} is not a finite number.", base, exponent).as_str()));
+    }
+    Ok(Some(NUMBER(result)))
+}
+
+pub fn sqrt(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    let value = check_arg!(arguments, 0, NUMBER, context).as_f64().unwrap();
+    if value < 0.0 {
+        return Err(context.error(format!("Cannot take the square root of the negative number {}.", value).as_str()));
+    }
+    Ok(Some(NUMBER(value.sqrt())))
+}
+
+pub fn format_invariant(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    Ok(Some(STRING(check_arg!(arguments, 0, NUMBER, context).to_string())))
+}
+
+fn visit_count(arguments : &[YarnValue], context : &YarnFunctionContext) -> YarnResult<usize> {
+    match arguments.first() {
+        Some(STRING(node)) => Ok(context.visit_count(node)),
+        Some(value) => Err(YarnError::new_type_mismatch_error(context.line(), context.col(), "STRING", value.get_type_as_string())),
+        None => Err(YarnError::new_null_function_arg_error(context.line(), context.col())),
+    }
+}
+
+pub fn visited(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    Ok(Some(BOOL(visit_count(&arguments, context)? > 0)))
+}
+
+pub fn visited_count(arguments : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
+    Ok(Some(NUMBER(visit_count(&arguments, context)? as f64)))
+}
+
 #[cfg(test)]
 mod tests {
 
     use std::env::var;
 
-    use crate::{token::tokenize, parcer::{YarnFunctionMap, default_function_map}};
+    use crate::{storage::YarnMemoryStorage, token::tokenize, parcer::{YarnFunctionMap, YarnFunctionSignature, default_function_map}};
 
     use super::*;
 
     #[test]
     fn test_parse_variable_literal() {
         let mut functions = default_function_map();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("foo".to_string(), YarnValue::NUMBER(2.0));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("foo", YarnValue::NUMBER(2.0));
 
         let tokens = tokenize("dice(6)");
         let eval = FunctionNode::parse(&tokens, 1);
         match eval {
             Parsed(eval, endex) => {
-                let value = eval.eval(&mut variables, &functions).unwrap().unwrap();
-                assert!(value >= YarnValue::NUMBER(0.0) && value <= YarnValue::NUMBER(6.0));
+                let mut rolled = [false; 6];
+                for _ in 0..200 {
+                    let value = eval.eval(&mut variables, &functions).unwrap().unwrap().as_f64().unwrap();
+                    assert!((1.0..=6.0).contains(&value) && value.fract() == 0.0);
+                    rolled[value as usize - 1] = true;
+                }
+                assert!(rolled.iter().all(|side| *side));
             },
             Error(err) => {
                 println!("{}", err.gen_error_message());
@@ -244,12 +428,17 @@ mod tests {
             Failed => assert!(false),
         }
 
-        let tokens = tokenize("random_range(0, 2)");
+        let tokens = tokenize("random_range(2, 0)");
         let eval = FunctionNode::parse(&tokens, 1);
         match eval {
             Parsed(eval, endex) => {
-                let value = eval.eval(&mut variables, &functions).unwrap().unwrap();
-                assert!(value >= YarnValue::NUMBER(0.0) && value <= YarnValue::NUMBER(2.0));
+                let mut rolled = [false; 3];
+                for _ in 0..100 {
+                    let value = eval.eval(&mut variables, &functions).unwrap().unwrap().as_f64().unwrap();
+                    assert!((0.0..=2.0).contains(&value) && value.fract() == 0.0);
+                    rolled[value as usize] = true;
+                }
+                assert!(rolled.iter().all(|number| *number));
             },
             Error(_) => assert!(false),
             Failed => assert!(false),
@@ -355,7 +544,83 @@ mod tests {
         }
     }
 
-    pub fn test(args : Vec<YarnValue>, line : usize, col : usize) -> YarnResult<Option<YarnValue>> {
+    pub fn test(args : Vec<YarnValue>, context : &YarnFunctionContext) -> YarnResult<Option<YarnValue>> {
         Ok(Some(YarnValue::BOOL(true)))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_fold_function() {
+        let functions = default_function_map();
+
+        let tokens = tokenize("round(2.4 * 2)");
+        match FunctionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.fold(&functions).constant(), Some(YarnValue::NUMBER(5.0))),
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("dice(6)");
+        match FunctionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert!(eval.fold(&functions).constant().is_none()),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_standard_functions() {
+        let functions = default_function_map();
+        let mut variables = YarnMemoryStorage::new();
+
+        let mut call = |source : &str| match FunctionNode::parse(&tokenize(source), 1) {
+            Parsed(eval, _) => eval.eval(&mut variables, &functions).map(|value| value.unwrap()).map_err(|error| error.error_name().to_string()),
+            _ => Err("Failed".to_string()),
+        };
+
+        assert_eq!(call("string(2)"), Ok(YarnValue::STRING("2".to_string())));
+        assert_eq!(call("string(2.5)"), Ok(YarnValue::STRING("2.5".to_string())));
+        assert_eq!(call("string(true)"), Ok(YarnValue::STRING("true".to_string())));
+        assert_eq!(call("number(\" 4.25\")"), Ok(YarnValue::NUMBER(4.25)));
+        assert_eq!(call("number(true)"), Ok(YarnValue::NUMBER(1.0)));
+        assert_eq!(call("number(\"four\")"), Err("Function Error".to_string()));
+        assert_eq!(call("bool(\"True\")"), Ok(YarnValue::BOOL(true)));
+        assert_eq!(call("bool(0)"), Ok(YarnValue::BOOL(false)));
+        assert_eq!(call("bool(-3)"), Ok(YarnValue::BOOL(true)));
+        assert_eq!(call("bool(\"yes\")"), Err("Function Error".to_string()));
+        assert!(matches!(call("int(2.7)"), Ok(YarnValue::INT(2))));
+        assert!(matches!(call("int(-2.7)"), Ok(YarnValue::INT(-2))));
+        assert_eq!(call("int(10000000000000000000000)"), Err("Function Error".to_string()));
+        assert_eq!(call("min(3, -1)"), Ok(YarnValue::NUMBER(-1.0)));
+        assert_eq!(call("max(3, -1)"), Ok(YarnValue::NUMBER(3.0)));
+        assert_eq!(call("min(3, -1, -4, 2)"), Ok(YarnValue::NUMBER(-4.0)));
+        assert_eq!(call("max(3, 7, -1)"), Ok(YarnValue::NUMBER(7.0)));
+        assert_eq!(call("max(5)"), Ok(YarnValue::NUMBER(5.0)));
+        assert_eq!(call("abs(-4.5)"), Ok(YarnValue::NUMBER(4.5)));
+        assert_eq!(call("pow(2, 10)"), Ok(YarnValue::NUMBER(1024.0)));
+        assert_eq!(call("pow(4, 0.5)"), Ok(YarnValue::NUMBER(2.0)));
+        assert_eq!(call("sqrt(16)"), Ok(YarnValue::NUMBER(4.0)));
+        assert_eq!(call("sqrt(-1)"), Err("Function Error".to_string()));
+        assert_eq!(call("format_invariant(1234.5)"), Ok(YarnValue::STRING("1234.5".to_string())));
+        assert_eq!(call("format_invariant(-0)"), Ok(YarnValue::STRING("0".to_string())));
+        assert_eq!(call("visited(\"start\")"), Ok(YarnValue::BOOL(false)));
+        assert_eq!(call("visited_count(\"start\")"), Ok(YarnValue::NUMBER(0.0)));
+        assert_eq!(call("visited(2)"), Err("Type Mismatch Error".to_string()));
+    }
+
+    #[test]
+    fn test_type_check_function_arity() {
+        let mut types = YarnTypeContext::new();
+        types.declare_function("max", YarnFunctionSignature::new(vec![YarnValueType::NUMBER], YarnValueType::NUMBER).with_variadic(YarnValueType::NUMBER));
+        types.declare_function("pad", YarnFunctionSignature::new(vec![YarnValueType::STRING], YarnValueType::STRING).with_optional_parameters(vec![YarnValueType::NUMBER]));
+
+        let check = |source : &str| match FunctionNode::parse(&tokenize(source), 1) {
+            Parsed(eval, _) => eval.type_check(&types).map_err(|error| error.error_name().to_string()),
+            _ => Err("Failed".to_string()),
+        };
+
+        assert_eq!(check("max(1, 2, 3)"), Ok(Some(YarnValueType::NUMBER)));
+        assert_eq!(check("max()"), Err("Invalid Argument Count Error".to_string()));
+        assert_eq!(check("max(1, true)"), Err("Type Mismatch Error".to_string()));
+        assert_eq!(check("pad(\"a\")"), Ok(Some(YarnValueType::STRING)));
+        assert_eq!(check("pad(\"a\", 2)"), Ok(Some(YarnValueType::STRING)));
+        assert_eq!(check("pad(\"a\", 2, 3)"), Err("Invalid Argument Count Error".to_string()));
+    }
+}
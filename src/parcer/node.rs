@@ -0,0 +1,583 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{error::{YarnError, YarnResult}, token::{YarnTokenQueue, YarnTokenType}, value::{YarnValue, YarnValueType}, decimal::YarnDecimal, storage::YarnMemoryStorage};
+
+use super::{YarnFunctionMap, YarnNode, YarnNodeStack, YarnNodeLine, YarnNodeOption, YarnEvaluator, YarnExpressionParser, YarnParseResult::*, command::{SetCommandNode, GenericCommandNode}, equality_expression::EqualityExpressionNode};
+
+//==================================================================================================================
+//                       Line Helpers
+//==================================================================================================================
+
+//All of these take the index of the START_LINE token of a line.
+
+fn line_end(tokens : &YarnTokenQueue, offset : usize) -> usize {
+    let mut index = offset;
+    while !(tokens.check_index(index, YarnTokenType::END_LINE) || tokens.check_index(index, YarnTokenType::EOF) || tokens.peek(index).is_none()) {
+        index += 1;
+    }
+    index
+}
+
+fn line_indent(tokens : &YarnTokenQueue, offset : usize) -> (usize, usize) {
+    let mut indent = 0;
+    let mut index = offset + 1;
+    loop {
+        if tokens.check_index(index, YarnTokenType::SPACE) {
+            indent += 1;
+        } else if tokens.check_index(index, YarnTokenType::TAB) {
+            indent += 4;
+        } else {
+            break;
+        }
+        index += 1;
+    }
+    (indent, index)
+}
+
+fn next_line(tokens : &YarnTokenQueue, offset : usize) -> usize {
+    let end = line_end(tokens, offset + 1);
+    if tokens.check_index(end, YarnTokenType::EOF) {
+        end
+    } else {
+        end + 1
+    }
+}
+
+fn is_blank_line(tokens : &YarnTokenQueue, offset : usize) -> bool {
+    let (_, start) = line_indent(tokens, offset);
+    tokens.check_index(start, YarnTokenType::END_LINE)
+}
+
+fn command_keyword(tokens : &YarnTokenQueue, start : usize) -> Option<usize> {
+    if tokens.check_index(start, YarnTokenType::START_COMMAND) {
+        Some(tokens.next_non_space_after(start))
+    } else {
+        None
+    }
+}
+
+fn is_block_command(tokens : &YarnTokenQueue, start : usize, token_type : YarnTokenType) -> bool {
+    match command_keyword(tokens, start) {
+        Some(keyword) => tokens.check_index(keyword, token_type),
+        None => false,
+    }
+}
+
+fn expect_command_end(tokens : &YarnTokenQueue, index : usize) -> YarnResult<()> {
+    let index = tokens.skip_spaces(index);
+    if tokens.check_index(index, YarnTokenType::END_COMMAND) {
+        Ok(())
+    } else {
+        Err(YarnError::new_unexpected_token_error(tokens.peek_line(index), tokens.peek_col(index)))
+    }
+}
+
+fn parse_condition(tokens : &YarnTokenQueue, keyword : usize) -> YarnResult<Box<dyn YarnEvaluator>> {
+    let condition_index = tokens.next_non_space_after(keyword);
+    match EqualityExpressionNode::parse(tokens, condition_index) {
+        Parsed(condition, endex) => {
+            expect_command_end(tokens, endex)?;
+            Ok(condition)
+        },
+        Error(error) => Err(error),
+        Failed => Err(YarnError::new_unexpected_token_error(tokens.peek_line(condition_index), tokens.peek_col(condition_index))),
+    }
+}
+
+//<<declare $name = value>> with an optional `as int`, `as decimal`, `as number`, `as string`, `as bool` or the name of
+//an enum at the end.
+//The value has to be worked out before the dialogue runs, so it can not use variables or functions.
+fn parse_declare(tokens : &YarnTokenQueue, keyword : usize) -> YarnResult<YarnNodeLine> {
+    let variable_index = tokens.next_non_space_after(keyword);
+    if !tokens.check_index(variable_index, YarnTokenType::DOLLAR_SIGN) {
+        return Err(YarnError::new_unexpected_token_error(tokens.peek_line(variable_index), tokens.peek_col(variable_index)));
+    }
+
+    let identifier = match tokens.peek_only_if_type(variable_index + 1, YarnTokenType::WORD) {
+        Some(token) => token.content().to_string(),
+        None => return Err(YarnError::new_invalid_variable_identifier_error(tokens.peek_line(variable_index + 1), tokens.peek_col(variable_index + 1))),
+    };
+
+    let assignment_index = tokens.next_non_space_after(variable_index + 1);
+    if !tokens.check_index(assignment_index, YarnTokenType::EQUAL) {
+        return Err(YarnError::new_unexpected_token_error(tokens.peek_line(assignment_index), tokens.peek_col(assignment_index)));
+    }
+
+    let value_index = tokens.next_non_space_after(assignment_index);
+    let line = tokens.peek_line(value_index);
+    let col = tokens.peek_col(value_index);
+    let (eval, endex) = match EqualityExpressionNode::parse(tokens, value_index) {
+        Parsed(eval, endex) => (eval, tokens.skip_spaces(endex)),
+        Error(error) => return Err(error),
+        Failed => return Err(YarnError::new_unexpected_token_error(line, col)),
+    };
+
+    //A value written as .Case takes the enum it is declared as.
+    if tokens.check_word(endex, "as") {
+        if let Some(name) = tokens.peek_only_if_type(tokens.next_non_space_after(endex), YarnTokenType::WORD).map(|token| token.content()) {
+            eval.expect_type(&YarnValueType::ENUM(name.to_string()));
+        }
+    }
+    let value = match eval.eval(&mut YarnMemoryStorage::new(), &YarnFunctionMap::new())? {
+        Some(value) => value,
+        None => return Err(YarnError::new_invalid_operation_error(line, col)),
+    };
+
+    if !tokens.check_word(endex, "as") {
+        expect_command_end(tokens, endex)?;
+        return Ok(YarnNodeLine::DECLARE(identifier, value, line, col));
+    }
+
+    let type_index = tokens.next_non_space_after(endex);
+    let type_line = tokens.peek_line(type_index);
+    let type_col = tokens.peek_col(type_index);
+    let expected = match tokens.peek_only_if_type(type_index, YarnTokenType::WORD).map(|token| token.content()) {
+        Some("int") => Some(YarnValue::INT(0)),
+        Some("decimal") => Some(YarnValue::DECIMAL(YarnDecimal::default())),
+        Some("number") => Some(YarnValue::NUMBER(0.0)),
+        Some("string") => Some(YarnValue::STRING(String::new())),
+        Some("bool") => Some(YarnValue::BOOL(false)),
+        Some(name) if value.get_type() == YarnValueType::ENUM(name.to_string()) => Some(value.clone()),
+        _ => None,
+    };
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return Err(YarnError::new_unexpected_token_error(type_line, type_col)),
+    };
+    expect_command_end(tokens, type_index + 1)?;
+
+    if expected.get_type() != value.get_type() {
+        return Err(YarnError::new_type_mismatch_error(line, col, expected.get_type_as_string(), value.get_type_as_string()));
+    }
+    Ok(YarnNodeLine::DECLARE(identifier, value.stored_as(&expected, line, col)?, line, col))
+}
+
+//<<enum Name>> followed by a <<case Name>> line for each of its cases and then <<endenum>>.
+fn parse_enum(tokens : &YarnTokenQueue, offset : usize, keyword : usize) -> YarnResult<(YarnNodeLine, usize)> {
+    let line = tokens.peek_line(keyword);
+    let col = tokens.peek_col(keyword);
+    let name_index = tokens.next_non_space_after(keyword);
+    let (name, name_end) = match tokens.identifier(name_index) {
+        Some(identifier) => identifier,
+        None => return Err(YarnError::new_unexpected_token_error(tokens.peek_line(name_index), tokens.peek_col(name_index))),
+    };
+    expect_command_end(tokens, name_end)?;
+
+    let mut cases : Vec<String> = Vec::new();
+    let mut offset = next_line(tokens, offset);
+    loop {
+        if tokens.check_index(offset, YarnTokenType::EOF) {
+            return Err(YarnError::new_unexpected_token_error(tokens.peek_line(offset), tokens.peek_col(offset)));
+        }
+
+        if is_blank_line(tokens, offset) {
+            offset = next_line(tokens, offset);
+            continue;
+        }
+
+        let (_, start) = line_indent(tokens, offset);
+        let keyword = match command_keyword(tokens, start) {
+            Some(keyword) => keyword,
+            None => return Err(YarnError::new_unexpected_token_error(tokens.peek_line(start), tokens.peek_col(start))),
+        };
+
+        if tokens.check_index(keyword, YarnTokenType::END) && tokens.check_word(keyword + 1, "enum") {
+            expect_command_end(tokens, keyword + 2)?;
+            if cases.is_empty() {
+                return Err(YarnError::new_enum_error(line, col, format!("The enum '{}' has no cases.", name).as_str()));
+            }
+            return Ok((YarnNodeLine::ENUM(name, cases, line, col), next_line(tokens, offset)));
+        }
+
+        if !tokens.check_word(keyword, "case") {
+            return Err(YarnError::new_unexpected_token_error(tokens.peek_line(keyword), tokens.peek_col(keyword)));
+        }
+
+        let case_index = tokens.next_non_space_after(keyword);
+        let (case, case_end) = match tokens.identifier(case_index) {
+            Some(identifier) => identifier,
+            None => return Err(YarnError::new_unexpected_token_error(tokens.peek_line(case_index), tokens.peek_col(case_index))),
+        };
+        expect_command_end(tokens, case_end)?;
+
+        if cases.contains(&case) {
+            let message = format!("The enum '{}' has the case '{}' more than once.", name, case);
+            return Err(YarnError::new_enum_error(tokens.peek_line(case_index), tokens.peek_col(case_index), message.as_str()));
+        }
+        cases.push(case);
+        offset = next_line(tokens, offset);
+    }
+}
+
+//Splits the content of a line into the speaker, the text and the hashtags at the end of it.
+fn parse_line_content(tokens : &YarnTokenQueue, start : usize, end : usize) -> (Option<String>, String, Vec<String>) {
+    let tag_start = (start..end).find(|index| tokens.check_index(*index, YarnTokenType::HASHTAG)).unwrap_or(end);
+
+    let mut speaker = None;
+    let mut text_start = start;
+    if let Some(colon) = (start..tag_start).find(|index| tokens.check_index(*index, YarnTokenType::COLON)) {
+        let is_name = (start..colon).all(|index| tokens.check_index(index, YarnTokenType::WORD) || tokens.check_index(index, YarnTokenType::SPACE));
+        if is_name && colon > start {
+            speaker = Some(tokens.content_between(start, colon).trim().to_string());
+            text_start = colon + 1;
+        }
+    }
+
+    let text = tokens.content_between(text_start, tag_start).trim().to_string();
+    let tags = tokens.content_between(tag_start, end)
+        .split_whitespace()
+        .map(|tag| tag.trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    (speaker, text, tags)
+}
+
+//==================================================================================================================
+//                       Node Parsing
+//==================================================================================================================
+
+impl YarnNode {
+    //Parses the node starting at the line at offset. Returns None if there were only blank lines left.
+    pub fn parse(tokens : &YarnTokenQueue, offset : usize) -> YarnResult<(Option<YarnNode>, usize)> {
+        let mut offset = offset;
+        let mut headers = HashMap::new();
+
+        loop {
+            if tokens.check_index(offset, YarnTokenType::EOF) {
+                if headers.is_empty() {
+                    return Ok((None, offset));
+                } else {
+                    return Err(YarnError::new_unexpected_token_error(tokens.peek_line(offset), tokens.peek_col(offset)));
+                }
+            }
+
+            let (_, start) = line_indent(tokens, offset);
+            let end = line_end(tokens, start);
+
+            if tokens.check_index(start, YarnTokenType::START_NODE) {
+                offset = next_line(tokens, offset);
+                break;
+            }
+
+            if start != end {
+                if let Some(key) = tokens.peek_only_if_type(start, YarnTokenType::WORD) {
+                    if tokens.check_index(start + 1, YarnTokenType::COLON) {
+                        let value = tokens.content_between(start + 2, end).trim().to_string();
+                        if key.content() == "tracking" && value != "always" && value != "never" {
+                            return Err(YarnError::new_invalid_header_error(key.line(), key.col(), key.content(), value.as_str()));
+                        }
+                        headers.insert(key.content().to_string(), value);
+                    } else {
+                        return Err(YarnError::new_unexpected_token_error(tokens.peek_line(start + 1), tokens.peek_col(start + 1)));
+                    }
+                } else {
+                    return Err(YarnError::new_unexpected_token_error(tokens.peek_line(start), tokens.peek_col(start)));
+                }
+            }
+
+            offset = next_line(tokens, offset);
+        }
+
+        let title = match headers.get("title") {
+            Some(title) => title.clone(),
+            None => return Err(YarnError::new_missing_title_error(tokens.peek_line(offset), 0)),
+        };
+
+        let (stack, endex) = parse_stack(tokens, offset, None)?;
+        let (_, start) = line_indent(tokens, endex);
+        if !tokens.check_index(start, YarnTokenType::END_NODE) {
+            return Err(YarnError::new_unexpected_token_error(tokens.peek_line(start), tokens.peek_col(start)));
+        }
+
+        Ok((Some(YarnNode::new(title, headers, stack)), next_line(tokens, endex)))
+    }
+}
+
+//Parses lines until the end of the node, a line that belongs to an enclosing if statement, or a line that is not
+//indented past body_indent. Returns the stack and the offset of the line that stopped it.
+fn parse_stack(tokens : &YarnTokenQueue, offset : usize, body_indent : Option<usize>) -> YarnResult<(YarnNodeStack, usize)> {
+    let mut offset = offset;
+    let mut lines = VecDeque::new();
+
+    loop {
+        if tokens.check_index(offset, YarnTokenType::EOF) {
+            break;
+        }
+
+        if is_blank_line(tokens, offset) {
+            offset = next_line(tokens, offset);
+            continue;
+        }
+
+        let (indent, start) = line_indent(tokens, offset);
+        let end = line_end(tokens, start);
+
+        if let Some(body_indent) = body_indent {
+            if indent <= body_indent {
+                break;
+            }
+        }
+
+        if tokens.check_index(start, YarnTokenType::END_NODE)
+            || is_block_command(tokens, start, YarnTokenType::ELSEIF)
+            || is_block_command(tokens, start, YarnTokenType::ELSE)
+            || is_block_command(tokens, start, YarnTokenType::ENDIF) {
+            break;
+        }
+
+        if tokens.check_index(start, YarnTokenType::ARROW) {
+            let (options, endex) = parse_options(tokens, offset, indent)?;
+            lines.push_back(YarnNodeLine::OPTIONS(options));
+            offset = endex;
+            continue;
+        }
+
+        if let Some(keyword) = command_keyword(tokens, start) {
+            if tokens.check_index(keyword, YarnTokenType::IF) {
+                let (line, endex) = parse_if(tokens, offset, body_indent)?;
+                lines.push_back(line);
+                offset = endex;
+                continue;
+            } else if tokens.check_word(keyword, "set") {
+                match SetCommandNode::parse(tokens, start) {
+                    Parsed(eval, _) => lines.push_back(YarnNodeLine::COMMAND(eval)),
+                    Error(error) => return Err(error),
+                    Failed => return Err(YarnError::new_unexpected_token_error(tokens.peek_line(keyword), tokens.peek_col(keyword))),
+                }
+            } else if tokens.check_word(keyword, "jump") || tokens.check_word(keyword, "detour") {
+                let title_start = tokens.next_non_space_after(keyword);
+                let title_end = (title_start..end).find(|index| tokens.check_index(*index, YarnTokenType::END_COMMAND)).unwrap_or(end);
+                expect_command_end(tokens, title_end)?;
+                let title = tokens.content_between(title_start, title_end).trim().to_string();
+                lines.push_back(if tokens.check_word(keyword, "jump") { YarnNodeLine::JUMP(title) } else { YarnNodeLine::DETOUR(title) });
+            } else if tokens.check_word(keyword, "return") {
+                expect_command_end(tokens, keyword + 1)?;
+                lines.push_back(YarnNodeLine::RETURN);
+            } else if tokens.check_word(keyword, "stop") {
+                expect_command_end(tokens, keyword + 1)?;
+                lines.push_back(YarnNodeLine::STOP);
+            } else if tokens.check_word(keyword, "declare") {
+                lines.push_back(parse_declare(tokens, keyword)?);
+            } else if tokens.check_word(keyword, "enum") {
+                let (line, endex) = parse_enum(tokens, offset, keyword)?;
+                lines.push_back(line);
+                offset = endex;
+                continue;
+            } else {
+                match GenericCommandNode::parse(tokens, start) {
+                    Parsed(eval, _) => lines.push_back(YarnNodeLine::COMMAND(eval)),
+                    Error(error) => return Err(error),
+                    Failed => return Err(YarnError::new_unexpected_token_error(tokens.peek_line(keyword), tokens.peek_col(keyword))),
+                }
+            }
+        } else {
+            let (speaker, text, tags) = parse_line_content(tokens, start, end);
+            lines.push_back(YarnNodeLine::LINE(speaker, text, tags));
+        }
+
+        offset = next_line(tokens, offset);
+    }
+
+    Ok((YarnNodeStack::new(lines), offset))
+}
+
+fn parse_options(tokens : &YarnTokenQueue, offset : usize, indent : usize) -> YarnResult<(Vec<YarnNodeOption>, usize)> {
+    let mut offset = offset;
+    let mut options = Vec::new();
+
+    loop {
+        while !tokens.check_index(offset, YarnTokenType::EOF) && is_blank_line(tokens, offset) {
+            offset = next_line(tokens, offset);
+        }
+
+        let (option_indent, start) = line_indent(tokens, offset);
+        if option_indent != indent || !tokens.check_index(start, YarnTokenType::ARROW) {
+            break;
+        }
+
+        let end = line_end(tokens, start);
+        let command_start = (start..end).find(|index| tokens.check_index(*index, YarnTokenType::START_COMMAND));
+        let condition = match command_start {
+            Some(command_start) => {
+                let keyword = tokens.next_non_space_after(command_start);
+                if !tokens.check_index(keyword, YarnTokenType::IF) {
+                    return Err(YarnError::new_unexpected_token_error(tokens.peek_line(keyword), tokens.peek_col(keyword)));
+                }
+                Some(parse_condition(tokens, keyword)?)
+            },
+            None => None,
+        };
+
+        let (speaker, text, tags) = parse_line_content(tokens, tokens.skip_spaces(start + 1), command_start.unwrap_or(end));
+        let (stack, endex) = parse_stack(tokens, next_line(tokens, offset), Some(indent))?;
+
+        options.push(YarnNodeOption::new(speaker, text, tags, condition, stack));
+        offset = endex;
+    }
+
+    Ok((options, offset))
+}
+
+fn parse_if(tokens : &YarnTokenQueue, offset : usize, body_indent : Option<usize>) -> YarnResult<(YarnNodeLine, usize)> {
+    let (_, start) = line_indent(tokens, offset);
+    let mut condition = parse_condition(tokens, tokens.next_non_space_after(start))?;
+    let mut offset = next_line(tokens, offset);
+    let mut branches = Vec::new();
+
+    loop {
+        let (stack, endex) = parse_stack(tokens, offset, body_indent)?;
+        branches.push((condition, stack));
+
+        let (_, start) = line_indent(tokens, endex);
+        let keyword = tokens.next_non_space_after(start);
+        if is_block_command(tokens, start, YarnTokenType::ELSEIF) {
+            condition = parse_condition(tokens, keyword)?;
+            offset = next_line(tokens, endex);
+        } else if is_block_command(tokens, start, YarnTokenType::ELSE) {
+            expect_command_end(tokens, keyword + 1)?;
+            let (else_stack, endex) = parse_stack(tokens, next_line(tokens, endex), body_indent)?;
+            let (_, start) = line_indent(tokens, endex);
+            if is_block_command(tokens, start, YarnTokenType::ENDIF) {
+                expect_command_end(tokens, tokens.next_non_space_after(start) + 1)?;
+                return Ok((YarnNodeLine::IF(branches, Some(else_stack)), next_line(tokens, endex)));
+            } else {
+                return Err(YarnError::new_unexpected_token_error(tokens.peek_line(start), tokens.peek_col(start)));
+            }
+        } else if is_block_command(tokens, start, YarnTokenType::ENDIF) {
+            expect_command_end(tokens, keyword + 1)?;
+            return Ok((YarnNodeLine::IF(branches, None), next_line(tokens, endex)));
+        } else {
+            return Err(YarnError::new_unexpected_token_error(tokens.peek_line(start), tokens.peek_col(start)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{token::tokenize, parcer::{parse_nodes, fold_nodes, default_function_map}};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_nodes() {
+        let source = concat!(
+            "title: hello_node\n",
+            "position: 1,2\n",
+            "---\n",
+            "Mae: Hello there! #greeting\n",
+            "<<set $visited to true>>\n",
+            "<<if $gold > 2>>\n",
+            "    You are rich.\n",
+            "<<elseif $gold == 2>>\n",
+            "    You are fine.\n",
+            "<<else>>\n",
+            "    You are poor.\n",
+            "<<endif>>\n",
+            "-> Who are you?\n",
+            "    Mae: Nobody.\n",
+            "    <<jump other_node>>\n",
+            "-> Goodbye. <<if $gold > 1>>\n",
+            "===\n",
+            "title: other_node\n",
+            "---\n",
+            "===\n",
+        );
+
+        let tokens = tokenize(source);
+        let nodes = parse_nodes(&tokens).unwrap();
+        assert_eq!(nodes.len(), 2);
+
+        let node = nodes.get("hello_node").unwrap();
+        assert_eq!(node.headers().get("position").unwrap(), "1,2");
+
+        let lines = node.first_step().lines();
+        assert_eq!(lines.len(), 4);
+
+        match &lines[0] {
+            YarnNodeLine::LINE(speaker, text, tags) => {
+                assert_eq!(speaker.as_deref(), Some("Mae"));
+                assert_eq!(text, "Hello there!");
+                assert_eq!(tags, &vec!["greeting".to_string()]);
+            },
+            _ => assert!(false),
+        }
+
+        assert!(matches!(&lines[1], YarnNodeLine::COMMAND(_)));
+
+        match &lines[2] {
+            YarnNodeLine::IF(branches, else_branch) => {
+                assert_eq!(branches.len(), 2);
+                assert_eq!(else_branch.as_ref().unwrap().lines().len(), 1);
+            },
+            _ => assert!(false),
+        }
+
+        match &lines[3] {
+            YarnNodeLine::OPTIONS(options) => {
+                assert_eq!(options.len(), 2);
+                assert_eq!(options[0].text(), "Who are you?");
+                assert_eq!(options[0].stack().lines().len(), 2);
+                assert!(matches!(&options[0].stack().lines()[1], YarnNodeLine::JUMP(title) if title == "other_node"));
+                assert_eq!(options[1].text(), "Goodbye.");
+                assert!(options[1].condition().is_some());
+                assert_eq!(options[1].stack().lines().len(), 0);
+            },
+            _ => assert!(false),
+        }
+
+        assert_eq!(nodes.get("other_node").unwrap().first_step().lines().len(), 0);
+    }
+
+    #[test]
+    fn test_parse_node_errors() {
+        let tokens = tokenize("title: a\n---\n<<if true>>\nHello\n===\n");
+        assert!(parse_nodes(&tokens).is_err());
+
+        let tokens = tokenize("position: 1,2\n---\n===\n");
+        assert_eq!(parse_nodes(&tokens).err().unwrap().error_name(), "Missing Title Error");
+
+        let tokens = tokenize("title: a\ntracking: sometimes\n---\n===\n");
+        assert_eq!(parse_nodes(&tokens).err().unwrap().error_name(), "Invalid Header Error");
+    }
+
+    #[test]
+    fn test_fold_if_branches() {
+        let source = concat!(
+            "title: a\n",
+            "---\n",
+            "<<if false>>\n",
+            "    Never.\n",
+            "<<elseif 1 + 1 == 2>>\n",
+            "    Always.\n",
+            "    Twice.\n",
+            "<<else>>\n",
+            "    Never either.\n",
+            "<<endif>>\n",
+            "<<if $gold > 2 * 2>>\n",
+            "    Maybe.\n",
+            "<<elseif false>>\n",
+            "    Never.\n",
+            "<<endif>>\n",
+            "<<if false>>\n",
+            "    Never.\n",
+            "<<endif>>\n",
+            "===\n",
+        );
+
+        let tokens = tokenize(source);
+        let nodes = fold_nodes(parse_nodes(&tokens).unwrap(), &default_function_map());
+        let lines = nodes.get("a").unwrap().first_step().lines();
+
+        assert_eq!(lines.len(), 3);
+        assert!(matches!(&lines[0], YarnNodeLine::LINE(_, text, _) if text == "Always."));
+        assert!(matches!(&lines[1], YarnNodeLine::LINE(_, text, _) if text == "Twice."));
+        match &lines[2] {
+            YarnNodeLine::IF(branches, else_branch) => {
+                assert_eq!(branches.len(), 1);
+                assert!(else_branch.is_none());
+            },
+            _ => assert!(false),
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use core::num;
 
-use crate::{value::YarnValue, token::{YarnTokenQueue, YarnTokenType}, error::{YarnError}};
+use crate::{storage::YarnVariableStorage, value::{YarnValue, YarnValueType}, token::{YarnTokenQueue, YarnTokenType}, error::{YarnError, YarnResult}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}};
 
-use super::{YarnEvaluator, YarnVariableMap, YarnExpressionParser, YarnParseResult::{self, *}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{self, *}, YarnFunctionMap, YarnTypeContext};
 
 #[derive(Debug)]
 pub struct NumberLiteralNode {
@@ -26,9 +26,25 @@ impl  NumberLiteralNode {
 }
 
 impl YarnEvaluator for NumberLiteralNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
         Ok(Some(YarnValue::NUMBER(self.value)))
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        Ok(Some(YarnValueType::NUMBER))
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        self
+    }
+
+    fn constant(&self) -> Option<YarnValue> {
+        Some(YarnValue::NUMBER(self.value))
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        node.emit(YarnInstruction::PUSH(YarnValue::NUMBER(self.value)), 0, 0);
+    }
 }
 
 impl YarnExpressionParser for NumberLiteralNode {
@@ -62,14 +78,14 @@ impl YarnExpressionParser for NumberLiteralNode {
 #[cfg(test)]
 mod tests {
 
-    use crate::{token::tokenize, value::YarnValue};
+    use crate::{storage::YarnMemoryStorage, token::tokenize, value::YarnValue};
 
     use super::*;
 
     #[test]
     fn test_parse_number_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
+        let mut variables = YarnMemoryStorage::new();
 
         let tokens = tokenize("2");
         let result = NumberLiteralNode::parse(&tokens, 1);
@@ -1,15 +1,8 @@
 use crate::{error::{YarnResult, YarnError}, token::{YarnTokenQueue, self, YarnTokenType}};
-use super::{YarnExpressionParser, variable::VariableNode, string_literal::StringLiteralNode, number_literal::NumberLiteralNode, YarnParseResult::{*, self}, bool_literal::BoolLiteralNode, equality_expression::EqualityExpressionNode, function::FunctionNode};
+use super::{YarnExpressionParser, variable::VariableNode, string_literal::StringLiteralNode, number_literal::NumberLiteralNode, YarnParseResult::{*, self}, bool_literal::BoolLiteralNode, equality_expression::EqualityExpressionNode, function::FunctionNode, enum_case::EnumCaseNode};
 
 pub struct PrimaryExpressionNode;
 
-pub enum YarnValueType {
-    VARIABLE,
-    STRING,
-    NUMBER,
-    BOOL
-}
-
 impl YarnExpressionParser for PrimaryExpressionNode {
     fn parse(tokens : &YarnTokenQueue, offset : usize) -> YarnParseResult {
         let variable_eval = VariableNode::parse(tokens, offset);
@@ -36,6 +29,12 @@ impl YarnExpressionParser for PrimaryExpressionNode {
             _ => { return bool_eval }
         }
 
+        let enum_eval = EnumCaseNode::parse(tokens, offset);
+        match enum_eval {
+            Failed => {},
+            _ => { return enum_eval }
+        }
+
         let function_eval = FunctionNode::parse(tokens, offset);
         match function_eval {
             Failed => {},
@@ -62,15 +61,15 @@ impl YarnExpressionParser for PrimaryExpressionNode {
 }
 
 mod tests {
-    use crate::{token::tokenize, value::YarnValue, parcer::{YarnVariableMap, YarnFunctionMap}};
+    use crate::{token::tokenize, value::YarnValue, storage::{YarnVariableStorage, YarnMemoryStorage}, parcer::{YarnFunctionMap}};
 
     use super::*;
 
     #[test]
     fn test_parse_primary_expression() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("test".to_string(), YarnValue::BOOL(true));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("test", YarnValue::BOOL(true));
 
         let tokens = tokenize("true");
         let result = PrimaryExpressionNode::parse(&tokens, 1);
@@ -1,6 +1,6 @@
-use crate::{value::{YarnValue, self}, token::{YarnTokenQueue, YarnTokenType::{*, self}, self}, error::{YarnError, YarnResult} };
+use crate::{storage::YarnVariableStorage, value::{YarnValue, YarnValueType, self}, token::{YarnTokenQueue, YarnTokenType::{*, self}, self}, error::{YarnError, YarnResult}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}};
 
-use super::{YarnEvaluator, YarnVariableMap, YarnExpressionParser, YarnParseResult::{*, self}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext};
 
 
 pub struct StringLiteralNode {
@@ -18,9 +18,25 @@ impl StringLiteralNode {
 }
 
 impl YarnEvaluator for StringLiteralNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
         Ok(Some(YarnValue::STRING(self.value.clone())))
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        Ok(Some(YarnValueType::STRING))
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        self
+    }
+
+    fn constant(&self) -> Option<YarnValue> {
+        Some(YarnValue::STRING(self.value.clone()))
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        node.emit(YarnInstruction::PUSH(YarnValue::STRING(self.value.clone())), 0, 0);
+    }
 }
 
 impl YarnExpressionParser for StringLiteralNode {
@@ -60,14 +76,14 @@ impl YarnExpressionParser for StringLiteralNode {
 }
 
 mod tests {
-    use crate::token::tokenize;
+    use crate::{token::tokenize, storage::YarnMemoryStorage};
 
     use super::*;
 
     #[test]
     fn test_parse_string_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
+        let mut variables = YarnMemoryStorage::new();
 
         let tokens = tokenize("\"test\"");
         let result = StringLiteralNode::parse(&tokens, 1);
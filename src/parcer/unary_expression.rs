@@ -1,10 +1,11 @@
 use std::process::Child;
 
-use crate::{error::{YarnResult, YarnError}, value::{YarnValue, self}, token::{YarnTokenQueue, self, YarnTokenType}};
+use crate::{storage::YarnVariableStorage, error::{YarnResult, YarnError}, value::{YarnValue, YarnValueType, self}, token::{YarnTokenQueue, self, YarnTokenType}, program::{YarnProgramNode, YarnSymbols, YarnInstruction, YarnOperator}};
 
-use super::{YarnEvaluator, YarnVariableMap, YarnExpressionParser, YarnParseResult::{*, self}, primary_expression::PrimaryExpressionNode, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, YarnParseResult::{*, self}, primary_expression::PrimaryExpressionNode, YarnFunctionMap, YarnTypeContext, fold_constant, bool_literal::BoolLiteralNode};
 
-enum UnaryOperator {
+#[derive(PartialEq)]
+pub enum UnaryOperator {
     NOT,
     NEGATIVE
 }
@@ -32,7 +33,7 @@ impl UnaryExpressionNode {
 }
 
 impl YarnEvaluator for UnaryExpressionNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> YarnResult<Option<YarnValue>> {
         let value = self.child.eval(variables, functions);
 
         match value {
@@ -40,14 +41,16 @@ impl YarnEvaluator for UnaryExpressionNode {
                 if let Some(value) = value {
                     if let UnaryOperator::NOT = self.operator {
                         match value {
-                            YarnValue::STRING(_) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
-                            YarnValue::NUMBER(_) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
+                            YarnValue::STRING(_) | YarnValue::ENUM(..) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
+                            YarnValue::NUMBER(_) | YarnValue::INT(_) | YarnValue::DECIMAL(_) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
                             YarnValue::BOOL(boolean) => Ok(Some(YarnValue::BOOL(!boolean))),
                         }
                     } else {
                         match value {
-                            YarnValue::STRING(_) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
+                            YarnValue::STRING(_) | YarnValue::ENUM(..) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
                             YarnValue::NUMBER(number) => Ok(Some(YarnValue::NUMBER(-number))),
+                            YarnValue::INT(_) | YarnValue::DECIMAL(_) => value.negate().map(Some)
+                                .ok_or_else(|| YarnError::new_arithmetic_error(self.line, self.col, "The result is too large to be a number.")),
                             YarnValue::BOOL(_) => Err(YarnError::new_invalid_operation_error(self.line, self.col)),
                         }
                     }
@@ -58,6 +61,63 @@ impl YarnEvaluator for UnaryExpressionNode {
             Err(error) => Err(error)
         }
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        let expected = match self.operator {
+            UnaryOperator::NOT => YarnValueType::BOOL,
+            UnaryOperator::NEGATIVE => YarnValueType::NUMBER,
+        };
+
+        match self.child.type_check(types)? {
+            Some(child_type) => {
+                if expected.accepts(&child_type) {
+                    Ok(Some(expected))
+                } else {
+                    Err(YarnError::new_type_mismatch_error(self.line, self.col, expected.get_type_as_string(), child_type.get_type_as_string()))
+                }
+            },
+            None => Err(YarnError::new_invalid_operation_error(self.line, self.col))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        let node = *self;
+        let mut child = node.child.fold(functions);
+
+        //Two of the same operator cancel each other out, so -(-x) and !(!x) become x.
+        if let Some(operand) = child.take_operand_of(&node.operator) {
+            return operand;
+        }
+
+        let is_constant = child.constant().is_some();
+        let folded = UnaryExpressionNode::new_boxed(child, node.operator, node.line, node.col);
+        if is_constant {
+            fold_constant(folded, functions)
+        } else {
+            folded
+        }
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        self.child.compile(node, symbols);
+        let operator = match self.operator {
+            UnaryOperator::NOT => YarnOperator::NOT,
+            UnaryOperator::NEGATIVE => YarnOperator::NEGATIVE,
+        };
+        node.emit(YarnInstruction::OPERATOR(operator), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+
+    fn take_operand_of(&mut self, operator : &UnaryOperator) -> Option<Box<dyn YarnEvaluator>> {
+        if &self.operator == operator {
+            Some(std::mem::replace(&mut self.child, BoolLiteralNode::new_boxed(false)))
+        } else {
+            None
+        }
+    }
 }
 
 impl YarnExpressionParser for UnaryExpressionNode {
@@ -86,14 +146,14 @@ impl YarnExpressionParser for UnaryExpressionNode {
 }
 
 mod tests {
-    use crate::token::tokenize;
+    use crate::{token::tokenize, storage::YarnMemoryStorage};
 
     use super::*;
 
     #[test]
     fn test_parse_primary_expression() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
+        let mut variables = YarnMemoryStorage::new();
 
         let tokens = tokenize("!true");
         let result = UnaryExpressionNode::parse(&tokens, 1);
@@ -129,4 +189,28 @@ mod tests {
             Failed => assert!(false),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_fold_unary_expression() {
+        let functions = YarnFunctionMap::new();
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("test", YarnValue::BOOL(true));
+
+        let tokens = tokenize("!(!$test)");
+        match UnaryExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => {
+                let mut folded = eval.fold(&functions);
+                variables.set("test", YarnValue::BOOL(false));
+                assert_eq!(folded.eval(&mut variables, &functions).unwrap().unwrap(), YarnValue::BOOL(false));
+                assert!(folded.take_operand_of(&UnaryOperator::NOT).is_none());
+            },
+            _ => assert!(false),
+        }
+
+        let tokens = tokenize("-(2 * 3)");
+        match UnaryExpressionNode::parse(&tokens, 1) {
+            Parsed(eval, _) => assert_eq!(eval.fold(&functions).constant(), Some(YarnValue::NUMBER(-6.0))),
+            _ => assert!(false),
+        }
+    }
+}
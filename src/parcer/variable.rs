@@ -1,6 +1,6 @@
-use crate::{value::YarnValue, token::{YarnTokenQueue, YarnTokenType}, error::{YarnError, YarnResult}};
+use crate::{storage::YarnVariableStorage, value::{YarnValue, YarnValueType}, token::{YarnTokenQueue, YarnTokenType}, error::{YarnError, YarnResult}, program::{YarnProgramNode, YarnSymbols, YarnInstruction}};
 
-use super::{YarnEvaluator, YarnVariableMap, YarnExpressionParser, YarnTokenType::*, YarnParseResult::{*, self}, YarnFunctionMap};
+use super::{YarnEvaluator, YarnExpressionParser, YarnTokenType::*, YarnParseResult::{*, self}, YarnFunctionMap, YarnTypeContext};
 
 
 pub struct VariableNode {
@@ -24,13 +24,33 @@ impl VariableNode {
 }
 
 impl YarnEvaluator for VariableNode {
-    fn eval(&self, variables : &mut YarnVariableMap, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
+    fn eval(&self, variables : &mut dyn YarnVariableStorage, functions : &YarnFunctionMap) -> Result<Option<YarnValue>, YarnError> {
         if let Some(var) = variables.get(self.identifier.as_str()) {
             Ok(Some(var.clone()))
         } else {
             Err(YarnError::new_variable_not_declared_error(self.line, self.col))
         }
     }
+
+    fn type_check(&self, types : &YarnTypeContext) -> YarnResult<Option<YarnValueType>> {
+        if let Some(value_type) = types.variable_type(self.identifier.as_str()) {
+            Ok(Some(value_type))
+        } else {
+            Err(YarnError::new_variable_not_declared_error(self.line, self.col))
+        }
+    }
+
+    fn fold(self : Box<Self>, functions : &YarnFunctionMap) -> Box<dyn YarnEvaluator> {
+        self
+    }
+
+    fn compile(&self, node : &mut YarnProgramNode, symbols : &mut YarnSymbols) {
+        node.emit(YarnInstruction::LOAD(symbols.variable_slot(self.identifier.as_str())), self.line, self.col);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
 }
 
 impl YarnExpressionParser for VariableNode {
@@ -54,15 +74,15 @@ mod tests {
 
     use std::env::var;
 
-    use crate::token::tokenize;
+    use crate::{token::tokenize, storage::YarnMemoryStorage};
 
     use super::*;
 
     #[test]
     fn test_parse_variable_literal() {
         let functions = YarnFunctionMap::new();
-        let mut variables = YarnVariableMap::new();
-        variables.insert("test".to_string(), YarnValue::BOOL(true));
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("test", YarnValue::BOOL(true));
 
         let tokens = tokenize("$test");
         let result = VariableNode::parse(&tokens, 1);
@@ -0,0 +1,638 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{value::YarnValue, error::{YarnError, YarnResult}, parcer::{YarnNode, YarnNodeStack, YarnNodeLine, YarnEnumMap, declared_enums, enum_of_case}};
+
+//==================================================================================================================
+//                       Instructions
+//==================================================================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum YarnOperator {
+    ADD,
+    SUB,
+    MUL,
+    DIV,
+    EQUAL_TOO,
+    NOT_EQUAL_TOO,
+    LESS_THAN,
+    LESS_THAN_EQ,
+    GREATER_THAN,
+    GREATER_THAN_EQ,
+    NOT,
+    NEGATIVE,
+    MOD,
+    AND,
+    OR,
+    XOR
+}
+
+impl YarnOperator {
+    pub fn operand_count(&self) -> usize {
+        match self {
+            YarnOperator::NOT | YarnOperator::NEGATIVE => 1,
+            _ => 2
+        }
+    }
+
+    //Every operator goes through here, whether the dialogue is running or an expression is being evaluated ahead of
+    //time, so numbers behave the same everywhere. Dividing by zero, or arithmetic that gives anything other than a
+    //finite number, is an error rather than a value that quietly spreads into the variables.
+    pub fn apply(&self, operands : &[YarnValue], tolerance : f64, line : usize, col : usize) -> YarnResult<YarnValue> {
+        let result = match (self, operands) {
+            (YarnOperator::NOT, [YarnValue::BOOL(value)]) => Some(YarnValue::BOOL(!value)),
+            (YarnOperator::NEGATIVE, [value]) => value.negate(),
+            (YarnOperator::DIV | YarnOperator::MOD, [lhs, rhs]) if lhs.is_number() && rhs.as_f64() == Some(0.0) => {
+                return Err(YarnError::new_arithmetic_error(line, col, "Division by zero."));
+            },
+            (YarnOperator::ADD, [lhs, rhs]) => lhs.add(rhs),
+            (YarnOperator::SUB, [lhs, rhs]) => lhs.sub(rhs),
+            (YarnOperator::MUL, [lhs, rhs]) => lhs.mult(rhs),
+            (YarnOperator::DIV, [lhs, rhs]) => lhs.div(rhs),
+            (YarnOperator::MOD, [lhs, rhs]) => lhs.rem(rhs),
+            (YarnOperator::AND, [YarnValue::BOOL(lhs), YarnValue::BOOL(rhs)]) => Some(YarnValue::BOOL(*lhs && *rhs)),
+            (YarnOperator::OR, [YarnValue::BOOL(lhs), YarnValue::BOOL(rhs)]) => Some(YarnValue::BOOL(*lhs || *rhs)),
+            (YarnOperator::XOR, [YarnValue::BOOL(lhs), YarnValue::BOOL(rhs)]) => Some(YarnValue::BOOL(lhs != rhs)),
+            (YarnOperator::EQUAL_TOO, [lhs, rhs]) => lhs.is_equal_within(rhs, tolerance),
+            (YarnOperator::NOT_EQUAL_TOO, [lhs, rhs]) => lhs.is_not_equal_within(rhs, tolerance),
+            (YarnOperator::LESS_THAN, [lhs, rhs]) => lhs.compare(rhs, tolerance).map(|order| YarnValue::BOOL(order == Ordering::Less)),
+            (YarnOperator::LESS_THAN_EQ, [lhs, rhs]) => lhs.compare(rhs, tolerance).map(|order| YarnValue::BOOL(order != Ordering::Greater)),
+            (YarnOperator::GREATER_THAN, [lhs, rhs]) => lhs.compare(rhs, tolerance).map(|order| YarnValue::BOOL(order == Ordering::Greater)),
+            (YarnOperator::GREATER_THAN_EQ, [lhs, rhs]) => lhs.compare(rhs, tolerance).map(|order| YarnValue::BOOL(order != Ordering::Less)),
+            _ => None
+        };
+
+        match result {
+            Some(YarnValue::NUMBER(value)) if value.is_nan() => Err(YarnError::new_arithmetic_error(line, col, "The result is not a number.")),
+            Some(YarnValue::NUMBER(value)) if value.is_infinite() => Err(YarnError::new_arithmetic_error(line, col, "The result is too large to be a number.")),
+            Some(value) => Ok(value),
+            //Exact numbers give nothing back when they overflow.
+            None if operands.iter().all(YarnValue::is_number) => Err(YarnError::new_arithmetic_error(line, col, "The result is too large to be a number.")),
+            None => Err(YarnError::new_invalid_operation_error(line, col)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum YarnInstruction {
+    PUSH(YarnValue),
+    POP,
+    LOAD(usize), // Variable slot
+    STORE(usize), // Variable slot
+    CALL(usize, usize), // Function slot, Argument count
+    OPERATOR(YarnOperator),
+    JUMP_TO(usize), // Instruction index
+    JUMP_IF_FALSE(usize), // Instruction index
+    RUN_LINE(String), // Line id
+    RUN_COMMAND(String, usize), // Command text, Expression count
+    ADD_OPTION(String, usize, bool), // Line id, Destination instruction index, Has condition
+    SHOW_OPTIONS,
+    RUN_NODE(String), // Node title
+    DETOUR(String), // Node title
+    RETURN, // To the node that detoured here, or ends the dialogue if none did
+    STOP
+}
+
+//==================================================================================================================
+//                       Program
+//==================================================================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnLine {
+    id : String,
+    speaker : Option<String>,
+    text : String,
+    tags : Vec<String>
+}
+
+impl YarnLine {
+    pub fn new(id : String, speaker : Option<String>, text : String, tags : Vec<String>) -> YarnLine {
+        YarnLine { id, speaker, text, tags }
+    }
+
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnProgramNode {
+    title : String,
+    headers : HashMap<String, String>,
+    instructions : Vec<YarnInstruction>,
+    positions : Vec<(usize, usize)> // The line and col each instruction was compiled from, used for runtime errors
+}
+
+impl YarnProgramNode {
+    pub fn new(title : &str, headers : HashMap<String, String>) -> YarnProgramNode {
+        YarnProgramNode {
+            title: title.to_string(),
+            headers,
+            instructions: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn instructions(&self) -> &Vec<YarnInstruction> {
+        &self.instructions
+    }
+
+    pub fn instruction(&self, index : usize) -> Option<&YarnInstruction> {
+        self.instructions.get(index)
+    }
+
+    pub fn position(&self, index : usize) -> (usize, usize) {
+        self.positions.get(index).copied().unwrap_or((0, 0))
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn emit(&mut self, instruction : YarnInstruction, line : usize, col : usize) -> usize {
+        self.instructions.push(instruction);
+        self.positions.push((line, col));
+        self.instructions.len() - 1
+    }
+
+    //Points the jump or option at index to destination. Used once the code it jumps to has been emitted.
+    pub fn patch(&mut self, index : usize, destination : usize) {
+        match self.instructions.get_mut(index) {
+            Some(YarnInstruction::JUMP_TO(target)) => *target = destination,
+            Some(YarnInstruction::JUMP_IF_FALSE(target)) => *target = destination,
+            Some(YarnInstruction::ADD_OPTION(_, target, _)) => *target = destination,
+            _ => {}
+        }
+    }
+}
+
+//Variables and functions are referred to by slot in the instructions. Functions are linked by index when a program is
+//loaded, while variables are looked up in the storage by name every time so changes made by the game are always seen.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnSymbolTable {
+    names : Vec<String>,
+    slots : HashMap<String, usize>
+}
+
+impl YarnSymbolTable {
+    pub fn new() -> YarnSymbolTable {
+        YarnSymbolTable {
+            names: Vec::new(),
+            slots: HashMap::new(),
+        }
+    }
+
+    pub fn intern(&mut self, name : &str) -> usize {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
+        }
+
+        self.names.push(name.to_string());
+        self.slots.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    pub fn slot(&self, name : &str) -> Option<usize> {
+        self.slots.get(name).copied()
+    }
+
+    pub fn name(&self, slot : usize) -> &str {
+        self.names[slot].as_str()
+    }
+
+    pub fn names(&self) -> &Vec<String> {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnSymbols {
+    variables : YarnSymbolTable,
+    functions : YarnSymbolTable,
+    enums : YarnEnumMap
+}
+
+impl YarnSymbols {
+    pub fn new() -> YarnSymbols {
+        YarnSymbols {
+            variables: YarnSymbolTable::new(),
+            functions: YarnSymbolTable::new(),
+            enums: YarnEnumMap::new(),
+        }
+    }
+
+    pub fn variables(&self) -> &YarnSymbolTable {
+        &self.variables
+    }
+
+    pub fn functions(&self) -> &YarnSymbolTable {
+        &self.functions
+    }
+
+    pub fn variable_slot(&mut self, name : &str) -> usize {
+        self.variables.intern(name)
+    }
+
+    pub fn function_slot(&mut self, name : &str) -> usize {
+        self.functions.intern(name)
+    }
+
+    pub fn enums(&self) -> &YarnEnumMap {
+        &self.enums
+    }
+
+    pub fn declare_enums(&mut self, enums : YarnEnumMap) {
+        self.enums.extend(enums);
+    }
+
+    pub fn enum_of_case(&self, case : &str) -> Option<&str> {
+        enum_of_case(&self.enums, case)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnProgram {
+    nodes : HashMap<String, YarnProgramNode>,
+    lines : HashMap<String, YarnLine>,
+    symbols : YarnSymbols,
+    initial_values : HashMap<String, YarnValue> // Values variables start with before the dialogue sets them
+}
+
+impl YarnProgram {
+    pub fn new() -> YarnProgram {
+        YarnProgram {
+            nodes: HashMap::new(),
+            lines: HashMap::new(),
+            symbols: YarnSymbols::new(),
+            initial_values: HashMap::new(),
+        }
+    }
+
+    pub fn nodes(&self) -> &HashMap<String, YarnProgramNode> {
+        &self.nodes
+    }
+
+    pub fn node(&self, title : &str) -> Option<&YarnProgramNode> {
+        self.nodes.get(title)
+    }
+
+    pub fn lines(&self) -> &HashMap<String, YarnLine> {
+        &self.lines
+    }
+
+    pub fn line(&self, id : &str) -> Option<&YarnLine> {
+        self.lines.get(id)
+    }
+
+    pub fn add_node(&mut self, node : YarnProgramNode) {
+        self.nodes.insert(node.title().to_string(), node);
+    }
+
+    pub fn add_line(&mut self, line : YarnLine) {
+        self.lines.insert(line.id().to_string(), line);
+    }
+
+    pub fn symbols(&self) -> &YarnSymbols {
+        &self.symbols
+    }
+
+    pub fn symbols_mut(&mut self) -> &mut YarnSymbols {
+        &mut self.symbols
+    }
+
+    pub fn initial_values(&self) -> &HashMap<String, YarnValue> {
+        &self.initial_values
+    }
+
+    pub fn set_initial_value(&mut self, name : &str, value : YarnValue) {
+        self.initial_values.insert(name.to_string(), value);
+    }
+
+    //A fingerprint of the instructions, which the positions in a snapshot point into. Hashed by hand with FNV-1a
+    //rather than with std's hasher, which may change between builds of the game.
+    pub fn identity(&self) -> u64 {
+        let mut titles = self.nodes.keys().collect::<Vec<&String>>();
+        titles.sort();
+
+        let mut hash : u64 = 0xcbf29ce484222325;
+        for title in titles {
+            for byte in format!("{}{:?}", title, self.nodes[title].instructions()).bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+}
+
+//==================================================================================================================
+//                       Compiler
+//==================================================================================================================
+
+pub fn compile_program(nodes : &HashMap<String, YarnNode>, enums : &YarnEnumMap) -> YarnResult<YarnProgram> {
+    let mut program = YarnProgram::new();
+    program.symbols_mut().declare_enums(declared_enums(nodes, enums)?);
+
+    for node in nodes.values() {
+        let mut compiler = YarnNodeCompiler { program: &mut program, title: node.title(), line_count: 0 };
+        let mut program_node = YarnProgramNode::new(node.title(), node.headers().clone());
+        compiler.compile_stack(node.first_step(), &mut program_node)?;
+        program_node.emit(YarnInstruction::RETURN, 0, 0);
+        program.add_node(program_node);
+    }
+
+    for node in program.nodes().values() {
+        for (index, instruction) in node.instructions().iter().enumerate() {
+            if let YarnInstruction::RUN_NODE(title) | YarnInstruction::DETOUR(title) = instruction {
+                if program.node(title).is_none() {
+                    let (line, col) = node.position(index);
+                    return Err(YarnError::new_node_not_found_error(line, col, title));
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+struct YarnNodeCompiler<'a> {
+    program : &'a mut YarnProgram,
+    title : &'a str,
+    line_count : usize
+}
+
+impl <'a> YarnNodeCompiler<'a> {
+    //Lines keep the id given to them with a #line: tag, and every other line gets one made from the node title.
+    fn add_line(&mut self, speaker : Option<&str>, text : &str, tags : &[String]) -> YarnResult<String> {
+        let explicit_id = tags.iter().find(|tag| tag.starts_with("line:"));
+        let id = match explicit_id {
+            Some(id) => {
+                if self.program.line(id).is_some() {
+                    return Err(YarnError::new_duplicate_line_id_error(0, 0, id));
+                }
+                id.clone()
+            },
+            None => {
+                self.line_count += 1;
+                format!("line:{}-{}", self.title, self.line_count)
+            }
+        };
+
+        let tags = tags.iter().filter(|tag| !tag.starts_with("line:")).cloned().collect();
+        self.program.add_line(YarnLine::new(id.clone(), speaker.map(|speaker| speaker.to_string()), text.to_string(), tags));
+        Ok(id)
+    }
+
+    fn compile_stack(&mut self, stack : &YarnNodeStack, node : &mut YarnProgramNode) -> YarnResult<()> {
+        for line in stack.lines().iter() {
+            match line {
+                YarnNodeLine::LINE(speaker, text, tags) => {
+                    let id = self.add_line(speaker.as_deref(), text, tags)?;
+                    node.emit(YarnInstruction::RUN_LINE(id), 0, 0);
+                },
+                YarnNodeLine::COMMAND(eval) => eval.compile(node, self.program.symbols_mut()),
+                YarnNodeLine::IF(branches, else_branch) => {
+                    let mut end_jumps = Vec::new();
+
+                    for (condition, stack) in branches.iter() {
+                        condition.compile(node, self.program.symbols_mut());
+                        let (line, col) = condition.position();
+                        let skip_jump = node.emit(YarnInstruction::JUMP_IF_FALSE(0), line, col);
+                        self.compile_stack(stack, node)?;
+                        end_jumps.push(node.emit(YarnInstruction::JUMP_TO(0), 0, 0));
+                        node.patch(skip_jump, node.len());
+                    }
+
+                    if let Some(stack) = else_branch {
+                        self.compile_stack(stack, node)?;
+                    }
+
+                    for jump in end_jumps {
+                        node.patch(jump, node.len());
+                    }
+                },
+                YarnNodeLine::OPTIONS(options) => {
+                    let mut option_instructions = Vec::new();
+
+                    for option in options.iter() {
+                        let has_condition = match option.condition() {
+                            Some(condition) => {
+                                condition.compile(node, self.program.symbols_mut());
+                                true
+                            },
+                            None => false,
+                        };
+                        let id = self.add_line(option.speaker(), option.text(), option.tags())?;
+                        option_instructions.push(node.emit(YarnInstruction::ADD_OPTION(id, 0, has_condition), 0, 0));
+                    }
+
+                    node.emit(YarnInstruction::SHOW_OPTIONS, 0, 0);
+
+                    let mut end_jumps = Vec::new();
+                    for (option, instruction) in options.iter().zip(option_instructions) {
+                        node.patch(instruction, node.len());
+                        self.compile_stack(option.stack(), node)?;
+                        end_jumps.push(node.emit(YarnInstruction::JUMP_TO(0), 0, 0));
+                    }
+
+                    for jump in end_jumps {
+                        node.patch(jump, node.len());
+                    }
+                },
+                YarnNodeLine::JUMP(title) => {
+                    node.emit(YarnInstruction::RUN_NODE(title.clone()), 0, 0);
+                },
+                YarnNodeLine::DETOUR(title) => {
+                    node.emit(YarnInstruction::DETOUR(title.clone()), 0, 0);
+                },
+                YarnNodeLine::RETURN => {
+                    node.emit(YarnInstruction::RETURN, 0, 0);
+                },
+                YarnNodeLine::STOP => {
+                    node.emit(YarnInstruction::STOP, 0, 0);
+                },
+                YarnNodeLine::DECLARE(..) | YarnNodeLine::ENUM(..) | YarnNodeLine::EMPTY => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{token::tokenize, parcer::parse_nodes};
+
+    use super::*;
+    use super::YarnInstruction::*;
+
+    #[test]
+    fn test_compile_program() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "Mae: Hi! #line:greeting\n",
+            "<<set $gold to $gold + 1>>\n",
+            "<<if $gold > 2>>\n",
+            "    Rich.\n",
+            "<<endif>>\n",
+            "-> Leave\n",
+            "    <<jump start>>\n",
+            "-> Stay <<if true>>\n",
+            "===\n",
+        );
+
+        let tokens = tokenize(source);
+        let program = compile_program(&parse_nodes(&tokens).unwrap(), &YarnEnumMap::new()).unwrap();
+        let node = program.node("start").unwrap();
+
+        assert_eq!(node.instructions(), &vec![
+            RUN_LINE("line:greeting".to_string()),
+            LOAD(0),
+            PUSH(YarnValue::NUMBER(1.0)),
+            OPERATOR(YarnOperator::ADD),
+            STORE(0),
+            LOAD(0),
+            PUSH(YarnValue::NUMBER(2.0)),
+            OPERATOR(YarnOperator::GREATER_THAN),
+            JUMP_IF_FALSE(11),
+            RUN_LINE("line:start-1".to_string()),
+            JUMP_TO(11),
+            ADD_OPTION("line:start-2".to_string(), 15, false),
+            PUSH(YarnValue::BOOL(true)),
+            ADD_OPTION("line:start-3".to_string(), 17, true),
+            SHOW_OPTIONS,
+            RUN_NODE("start".to_string()),
+            JUMP_TO(18),
+            JUMP_TO(18),
+            RETURN
+        ]);
+
+        assert_eq!(program.symbols().variables().names(), &vec!["gold".to_string()]);
+        assert_eq!(program.line("line:greeting").unwrap().speaker(), Some("Mae"));
+        assert_eq!(program.line("line:start-3").unwrap().text(), "Stay");
+    }
+
+    #[test]
+    fn test_compile_slots() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $bonus to round($gold)>>\n",
+            "<<set $gold to floor($bonus) + round($gold)>>\n",
+            "===\n",
+        );
+
+        let program = compile_program(&parse_nodes(&tokenize(source)).unwrap(), &YarnEnumMap::new()).unwrap();
+        assert_eq!(program.node("start").unwrap().instructions(), &vec![
+            LOAD(0),
+            CALL(0, 1),
+            STORE(1),
+            LOAD(1),
+            CALL(1, 1),
+            LOAD(0),
+            CALL(0, 1),
+            OPERATOR(YarnOperator::ADD),
+            STORE(0),
+            RETURN
+        ]);
+
+        let symbols = program.symbols();
+        assert_eq!(symbols.variables().names(), &vec!["gold".to_string(), "bonus".to_string()]);
+        assert_eq!(symbols.functions().names(), &vec!["round".to_string(), "floor".to_string()]);
+        assert_eq!((symbols.variables().slot("bonus"), symbols.variables().slot("luck")), (Some(1), None));
+        assert_eq!(symbols.functions().name(1), "floor");
+    }
+
+    #[test]
+    fn test_compile_missing_node() {
+        let tokens = tokenize("title: start\n---\n<<jump nowhere>>\n===\n");
+        let error = compile_program(&parse_nodes(&tokens).unwrap(), &YarnEnumMap::new()).unwrap_err();
+        assert_eq!(error.error_name(), "Node Not Found Error");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_program_serde() {
+        let source = "title: start\n---\nMae: Hi! #line:greeting\n<<set $gold to $gold + 0.1>>\n-> Leave\n    <<stop>>\n===\n";
+        let mut program = compile_program(&parse_nodes(&tokenize(source)).unwrap(), &YarnEnumMap::new()).unwrap();
+        program.set_initial_value("gold", YarnValue::NUMBER(0.2));
+
+        let json = serde_json::to_string(&program).unwrap();
+        assert!(json.contains("{\"PUSH\":{\"NUMBER\":0.1}}"));
+        assert_eq!(serde_json::from_str::<YarnProgram>(json.as_str()).unwrap(), program);
+
+        let error = YarnError::new_node_not_found_error(3, 4, "shop");
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"error_name":"Node Not Found Error","error_message":"There is no node with the title 'shop'.","col":4,"line":3}"#);
+        let restored = serde_json::from_str::<YarnError>(json.as_str()).unwrap();
+        assert_eq!(restored.gen_error_message(), error.gen_error_message());
+    }
+
+    //Saved programs and tools read this layout, so it should only change on purpose.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_program_serde_golden() {
+        let source = "title: start\n---\nMae: Hi! #line:greeting\n<<set $gold to 2>>\n===\n";
+        let program = compile_program(&parse_nodes(&tokenize(source)).unwrap(), &YarnEnumMap::new()).unwrap();
+
+        let golden = serde_json::json!({
+            "nodes": {
+                "start": {
+                    "title": "start",
+                    "headers": {"title": "start"},
+                    "instructions": [{"RUN_LINE": "line:greeting"}, {"PUSH": {"NUMBER": 2.0}}, {"STORE": 0}, "RETURN"],
+                    "positions": [[0, 0], [0, 0], [3, 0], [0, 0]]
+                }
+            },
+            "lines": {
+                "line:greeting": {"id": "line:greeting", "speaker": "Mae", "text": "Hi!", "tags": []}
+            },
+            "symbols": {
+                "variables": {"names": ["gold"], "slots": {"gold": 0}},
+                "functions": {"names": [], "slots": {}},
+                "enums": {}
+            },
+            "initial_values": {}
+        });
+        assert_eq!(serde_json::to_value(&program).unwrap(), golden);
+        assert_eq!(serde_json::from_value::<YarnProgram>(golden).unwrap(), program);
+    }
+}
@@ -0,0 +1,74 @@
+//The random numbers the dialogue uses. Its whole state is one number, so it can be saved with the rest of the
+//dialogue and a seeded run always gives the same rolls. Uses SplitMix64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnRandom {
+    state : u64
+}
+
+impl Default for YarnRandom {
+    fn default() -> Self {
+        YarnRandom::from_entropy()
+    }
+}
+
+impl YarnRandom {
+    pub fn new(seed : u64) -> YarnRandom {
+        YarnRandom { state: seed }
+    }
+
+    pub fn from_entropy() -> YarnRandom {
+        YarnRandom::new(rand::random())
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state : u64) {
+        self.state = state;
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    //Between 0 and 1, never 1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    //Between min and max, including both.
+    pub fn range(&mut self, min : f64, max : f64) -> f64 {
+        let value = min + (max - min) * ((self.next_u64() >> 11) as f64 / ((1u64 << 53) - 1) as f64);
+        value.clamp(min.min(max), min.max(max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_random() {
+        let mut first = YarnRandom::new(42);
+        let mut second = YarnRandom::new(42);
+        let rolls = (0..5).map(|_| first.next_u64()).collect::<Vec<u64>>();
+        assert_eq!(rolls, (0..5).map(|_| second.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(rolls[0], rolls[1]);
+
+        let mut restored = YarnRandom::new(0);
+        restored.set_state(first.state());
+        assert_eq!(restored.next_f64(), first.next_f64());
+
+        for _ in 0..100 {
+            let value = first.range(2.0, 4.0);
+            assert!((2.0..=4.0).contains(&value));
+            assert!((0.0..1.0).contains(&first.next_f64()));
+        }
+    }
+}
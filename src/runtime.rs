@@ -0,0 +1,922 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{value::{YarnValue, YarnEnum}, error::YarnResult, token::tokenize, parcer::{YarnFunctionMap, YarnFunction, YarnFunctionContext, YarnFunctionSignature, YarnRegisteredFunction, YarnTypeContext, default_function_map, parse_nodes, fold_nodes, declared_variables, declared_enums, YarnEnumMap}, program::{YarnProgram, compile_program}, vm::{YarnVirtualMachine, YarnDialogueEvent, YarnNodeVisits, YarnSnapshot, link_functions}, storage::{YarnVariableStorage, YarnMemoryStorage, YarnWatchedStorage}, command::{YarnCommandMap, YarnCommandReturn}, random::YarnRandom};
+
+pub struct YarnRuntime {
+    source : String,
+    program : Option<YarnProgram>,
+    variables : YarnWatchedStorage, // Whichever storage the game gave, with the listeners called on every set
+    functions : YarnFunctionMap,
+    enums : YarnEnumMap,
+    commands : YarnCommandMap,
+    linked_functions : Vec<Option<YarnFunction>>, // Functions by the slots of the current program
+    vm : YarnVirtualMachine,
+}
+
+impl YarnRuntime {
+    pub fn new(source : &str) -> YarnRuntime {
+        YarnRuntime { 
+            source: source.to_string(),
+            program: None,
+            variables: YarnWatchedStorage::new(YarnMemoryStorage::new()),
+            functions: default_function_map(),
+            enums: YarnEnumMap::new(),
+            commands: YarnCommandMap::new(),
+            linked_functions: Vec::new(),
+            vm: YarnVirtualMachine::new(),
+        }
+    }
+}
+
+impl From<&str> for YarnRuntime {
+    fn from(source : &str) -> Self {
+        YarnRuntime::new(source)
+    }
+}
+
+impl YarnRuntime {
+    pub fn with_function<F>(self, name : &str, function : F) -> Self
+    where F : Fn(Vec<YarnValue>, &YarnFunctionContext) -> YarnResult<Option<YarnValue>> + Send + Sync + 'static {
+        self.with_registered_function(name, YarnRegisteredFunction::new(function, None))
+    }
+
+    //Functions made with #[yarn_function] have their signature generated as <name>_signature().
+    pub fn with_typed_function<F>(self, name : &str, function : F, signature : YarnFunctionSignature) -> Self
+    where F : Fn(Vec<YarnValue>, &YarnFunctionContext) -> YarnResult<Option<YarnValue>> + Send + Sync + 'static {
+        self.with_registered_function(name, YarnRegisteredFunction::new(function, Some(signature)))
+    }
+
+    pub fn with_registered_function(mut self, name : &str, function : YarnRegisteredFunction) -> Self {
+        self.functions.insert(name.to_string(), function);
+        if let Some(program) = &self.program {
+            self.linked_functions = link_functions(program, &self.functions);
+        }
+        self
+    }
+
+    //Commands without a handler are given to the game as YarnDialogueEvent::COMMAND instead.
+    //A handler can return a YarnCommandStatus to pause the dialogue until the command is done.
+    pub fn with_command<F, R>(mut self, name : &str, handler : F) -> Self
+    where F : Fn(Vec<String>, &YarnFunctionContext) -> R + Send + Sync + 'static, R : YarnCommandReturn {
+        self.commands.insert(name.to_string(), Arc::new(move |arguments, context| handler(arguments, context).into_command_result()));
+        self
+    }
+
+    pub fn with_variable(mut self, name : &str, value : YarnValue) -> Self {
+        self.variables.set(name, value);
+        self
+    }
+
+    //Lets the dialogue use a rust enum made with #[derive(YarnEnum)] without declaring it with <<enum>>.
+    pub fn with_enum<T : YarnEnum>(mut self) -> Self {
+        self.enums.insert(T::enum_name().to_string(), T::cases().iter().map(|case| case.to_string()).collect());
+        self
+    }
+
+    //Keeps the dialogue's variables in the game's own storage instead of in the runtime. Variables given before this
+    //are copied into the storage unless it already has them, and listeners keep working with any storage.
+    pub fn with_storage<S : YarnVariableStorage + 'static>(mut self, storage : S) -> Self {
+        self.variables.replace(storage);
+        if let Some(program) = self.program.take() {
+            self.load_program(program);
+        }
+        self
+    }
+
+    //Called with the name and new value whenever the dialogue or the game sets a variable.
+    pub fn with_variable_listener<F>(mut self, listener : F) -> Self
+    where F : Fn(&str, &YarnValue) + Send + Sync + 'static {
+        self.variables.add_listener(Arc::new(listener));
+        self
+    }
+
+    //Makes dice, random and random_range give the same results every time the dialogue is run.
+    pub fn with_seed(mut self, seed : u64) -> Self {
+        self.vm.set_random(YarnRandom::new(seed));
+        self
+    }
+
+    //How far apart two numbers can be and still count as equal, relative to their size once they are above one.
+    pub fn with_tolerance(mut self, tolerance : f64) -> Self {
+        self.vm.set_tolerance(tolerance);
+        self
+    }
+
+    //Use an already compiled program instead of compiling the source.
+    pub fn with_program(mut self, program : YarnProgram) -> Self {
+        self.load_program(program);
+        self
+    }
+
+    //Variables the program declares start with their declared value unless the storage already has one.
+    fn load_program(&mut self, program : YarnProgram) {
+        for (name, value) in program.initial_values() {
+            if !self.variables.contains(name) {
+                self.variables.set(name, value.clone());
+            }
+        }
+        self.linked_functions = link_functions(&program, &self.functions);
+        self.program = Some(program);
+    }
+
+    pub fn type_context(&self) -> YarnTypeContext {
+        let mut types = YarnTypeContext::from_maps(&self.variables.to_map(), &self.functions);
+        for (name, cases) in self.enums.iter() {
+            types.declare_enum(name.as_str(), cases.clone());
+        }
+        types
+    }
+
+    pub fn compile(&mut self) -> YarnResult<&YarnProgram> {
+        let tokens = tokenize(self.source.as_str());
+        let nodes = parse_nodes(&tokens)?;
+
+        let mut variables = declared_variables(&nodes)?;
+        variables.extend(self.variables.to_map());
+
+        let mut types = YarnTypeContext::from_maps(&variables, &self.functions);
+        for (name, cases) in declared_enums(&nodes, &self.enums)? {
+            types.declare_enum(name.as_str(), cases);
+        }
+        for node in nodes.values() {
+            node.type_check(&types)?;
+        }
+
+        let nodes = fold_nodes(nodes, &self.functions);
+        let mut program = compile_program(&nodes, &self.enums)?;
+        for (name, value) in variables {
+            program.set_initial_value(name.as_str(), value);
+        }
+        self.load_program(program);
+        Ok(self.program.as_ref().unwrap())
+    }
+
+    pub fn program(&self) -> Option<&YarnProgram> {
+        self.program.as_ref()
+    }
+
+    pub fn variable(&self, name : &str) -> Option<YarnValue> {
+        self.variables.get(name)
+    }
+
+    pub fn storage(&self) -> &dyn YarnVariableStorage {
+        &self.variables
+    }
+
+    pub fn storage_mut(&mut self) -> &mut dyn YarnVariableStorage {
+        &mut self.variables
+    }
+
+    pub fn visit_count(&self, node : &str) -> usize {
+        self.vm.visit_count(node)
+    }
+
+    //Save these with the rest of the dialogue's state so visited() still knows where the player has been after loading.
+    pub fn visits(&self) -> &HashMap<String, YarnNodeVisits> {
+        self.vm.visits()
+    }
+
+    pub fn set_visits(&mut self, visits : HashMap<String, YarnNodeVisits>) {
+        self.vm.set_visits(visits);
+    }
+
+    pub fn functions(&self) -> &YarnFunctionMap {
+        &self.functions
+    }
+
+    pub fn commands(&self) -> &YarnCommandMap {
+        &self.commands
+    }
+
+    pub fn start(&mut self, node : &str) -> YarnResult<()> {
+        if self.program.is_none() {
+            self.compile()?;
+        }
+        self.vm.set_node(self.program.as_ref().unwrap(), node)
+    }
+
+    pub fn next_event(&mut self) -> YarnResult<YarnDialogueEvent> {
+        let program = match &self.program {
+            Some(program) => program,
+            None => return Ok(YarnDialogueEvent::DIALOGUE_COMPLETE),
+        };
+
+        self.vm.run(program, &mut self.variables, &self.linked_functions, &self.commands)
+    }
+
+    pub fn select_option(&mut self, index : usize) -> YarnResult<()> {
+        self.vm.select_option(index)
+    }
+
+    //Save this with the rest of the dialogue's state so random rolls carry on the same way after loading.
+    pub fn random_state(&self) -> u64 {
+        self.vm.random().state()
+    }
+
+    pub fn set_random_state(&mut self, state : u64) {
+        self.vm.set_random(YarnRandom::new(state));
+    }
+
+    //Everything needed to pick the dialogue back up later, see YarnSnapshot::to_bytes and, with the serde feature, to_json.
+    pub fn snapshot(&self) -> YarnSnapshot {
+        let empty = YarnProgram::new();
+        self.vm.snapshot(self.program.as_ref().unwrap_or(&empty), self.variables.to_map())
+    }
+
+    //The variables go in first so a runtime that has not compiled yet can type check against them.
+    pub fn restore(&mut self, snapshot : &YarnSnapshot) -> YarnResult<()> {
+        for (name, value) in snapshot.variables() {
+            self.variables.set(name, value.clone());
+        }
+        if self.program.is_none() {
+            self.compile()?;
+        }
+        self.vm.restore(self.program.as_ref().unwrap(), snapshot)
+    }
+
+    //For commands that returned PENDING, once the game has finished them.
+    pub fn resume(&mut self) -> YarnResult<()> {
+        self.vm.resume()
+    }
+
+    //Call every frame with the seconds since the last one, so waits end on their own.
+    pub fn update(&mut self, delta : f64) {
+        self.vm.update(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::{command::YarnCommandStatus, storage::YarnLayeredStorage, vm::MAX_DETOUR_DEPTH};
+
+    use super::*;
+
+    //Reads a snapshot back from every form it can be saved in.
+    fn saved_forms(snapshot : &YarnSnapshot) -> Vec<YarnSnapshot> {
+        let mut forms = vec![YarnSnapshot::from_bytes(&snapshot.to_bytes()).unwrap()];
+        #[cfg(feature = "serde")]
+        forms.push(YarnSnapshot::from_json(snapshot.to_json().unwrap().as_str()).unwrap());
+        forms
+    }
+
+    #[test]
+    fn test_runtime_dialogue() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $visits to $visits + 1>>\n",
+            "-> Again\n",
+            "    <<jump start>>\n",
+            "-> Done\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source).with_variable("visits", YarnValue::NUMBER(0.0));
+        runtime.start("start").unwrap();
+
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+        runtime.select_option(0).unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+        runtime.select_option(1).unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::DIALOGUE_COMPLETE);
+        assert_eq!(runtime.variable("visits"), Some(YarnValue::NUMBER(2.0)));
+
+        let mut runtime = YarnRuntime::new(source);
+        assert!(runtime.compile().is_err());
+    }
+
+    #[test]
+    fn test_runtime_commands() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<walk Mae {$steps + 1}>>\n",
+            "<<play_sound \"door creak\">>\n",
+            "<<walk Bea>>\n",
+            "<<say {$name}>>\n",
+            "===\n",
+        );
+
+        let walked = Arc::new(Mutex::new(Vec::new()));
+        let walks = walked.clone();
+        let mut runtime = YarnRuntime::new(source)
+            .with_variable("steps", YarnValue::NUMBER(2.0))
+            .with_variable("name", YarnValue::STRING("Mae Borowski".to_string()))
+            .with_command("walk", move |arguments, context| {
+                match context.variable("steps") {
+                    Some(_) => {
+                        walks.lock().unwrap().push(arguments.join(" "));
+                        Ok(())
+                    },
+                    None => Err(context.error("Nobody can walk.")),
+                }
+            });
+        runtime.start("start").unwrap();
+
+        match runtime.next_event().unwrap() {
+            YarnDialogueEvent::COMMAND(command) => {
+                assert_eq!(command.name(), "play_sound");
+                assert_eq!(command.arguments(), &vec!["door creak".to_string()]);
+            },
+            _ => assert!(false),
+        }
+        match runtime.next_event().unwrap() {
+            YarnDialogueEvent::COMMAND(command) => assert_eq!(command.arguments(), &vec!["Mae Borowski".to_string()]),
+            _ => assert!(false),
+        }
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(*walked.lock().unwrap(), vec!["Mae 3", "Bea"]);
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<declare $gold = 1>>\n===\n");
+        assert!(runtime.compile().is_ok());
+        assert_eq!(runtime.variable("gold"), Some(YarnValue::NUMBER(1.0)));
+    }
+
+    #[test]
+    fn test_runtime_waiting_commands() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<pan_camera left>>\n",
+            "First.\n",
+            "<<wait 1.5>>\n",
+            "Second.\n",
+            "<<stop>>\n",
+            "Never.\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source).with_command("pan_camera", |_, _| YarnCommandStatus::PENDING);
+        runtime.start("start").unwrap();
+
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::WAITING);
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::WAITING);
+        runtime.resume().unwrap();
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::LINE(line) if line.text() == "First."));
+
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::WAITING);
+        runtime.update(1.0);
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::WAITING);
+        runtime.update(1.0);
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::LINE(line) if line.text() == "Second."));
+
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::DIALOGUE_COMPLETE);
+        assert_eq!(runtime.resume().unwrap_err().error_name(), "Not Waiting Error");
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<wait soon>>\n===\n");
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap_err().error_message(), "The command was given the wrong arguments. Usage: <<wait seconds>>");
+
+        for seconds in ["nan", "inf", "-inf"] {
+            let mut runtime = YarnRuntime::new(format!("title: start\n---\n<<wait {}>>\n===\n", seconds).as_str());
+            runtime.start("start").unwrap();
+            assert_eq!(runtime.next_event().unwrap_err().error_name(), "Command Usage Error");
+        }
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<linger>>\n===\n").with_command("linger", |_, _| YarnCommandStatus::WAIT(f64::INFINITY));
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap_err().gen_error_message(), "Command Error at (2, 0) : A command can not wait for inf seconds.");
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<wait 5>>\nDone.\n===\n").with_command("wait", |_, _| ());
+        runtime.start("start").unwrap();
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::LINE(_)));
+    }
+
+    #[test]
+    fn test_runtime_visited() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $seen to visited(\"shop\")>>\n",
+            "<<set $count to visited_count(\"shop\")>>\n",
+            "-> Shop\n",
+            "    <<jump shop>>\n",
+            "-> Sneak\n",
+            "    <<jump alley>>\n",
+            "-> Leave\n",
+            "===\n",
+            "title: shop\n",
+            "tracking: always\n",
+            "---\n",
+            "<<jump start>>\n",
+            "===\n",
+            "title: alley\n",
+            "tracking: never\n",
+            "---\n",
+            "<<jump start>>\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source)
+            .with_variable("seen", YarnValue::BOOL(true))
+            .with_variable("count", YarnValue::NUMBER(-1.0));
+        runtime.start("start").unwrap();
+
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+        assert_eq!(runtime.variable("seen"), Some(YarnValue::BOOL(false)));
+        assert_eq!(runtime.variable("count"), Some(YarnValue::NUMBER(0.0)));
+
+        for visits in 1..3 {
+            runtime.select_option(0).unwrap();
+            assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+            assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("shop".to_string()));
+            assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+            assert_eq!(runtime.variable("seen"), Some(YarnValue::BOOL(true)));
+            assert_eq!(runtime.variable("count"), Some(YarnValue::NUMBER(visits as f64)));
+        }
+
+        runtime.select_option(1).unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("alley".to_string()));
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+        assert_eq!(runtime.visits().get("start"), Some(&YarnNodeVisits::new(4, 3)));
+        assert_eq!(runtime.visits().get("alley"), None);
+
+        let saved = runtime.visits().clone();
+        runtime.select_option(2).unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!((runtime.visit_count("start"), runtime.visit_count("shop"), runtime.visit_count("nowhere")), (4, 2, 0));
+
+        let mut restored = YarnRuntime::new(source)
+            .with_variable("seen", YarnValue::BOOL(true))
+            .with_variable("count", YarnValue::NUMBER(-1.0));
+        restored.set_visits(saved);
+        restored.start("start").unwrap();
+        assert!(matches!(restored.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+        assert_eq!(restored.variable("count"), Some(YarnValue::NUMBER(2.0)));
+        assert_eq!(restored.visits().get("start"), Some(&YarnNodeVisits::new(5, 3)));
+    }
+
+    #[test]
+    fn test_runtime_visits_snapshot() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $shop to visited_count(\"shop\")>>\n",
+            "<<set $alley to visited(\"alley\")>>\n",
+            "-> Shop\n",
+            "    <<jump shop>>\n",
+            "-> Sneak\n",
+            "    <<jump alley>>\n",
+            "===\n",
+            "title: shop\n",
+            "---\n",
+            "<<jump start>>\n",
+            "===\n",
+            "title: alley\n",
+            "tracking: never\n",
+            "---\n",
+            "<<jump start>>\n",
+            "===\n",
+        );
+
+        let new_runtime = || YarnRuntime::new(source)
+            .with_variable("shop", YarnValue::NUMBER(-1.0))
+            .with_variable("alley", YarnValue::BOOL(true));
+
+        let mut runtime = new_runtime();
+        runtime.start("start").unwrap();
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+        for option in [0, 1, 0] {
+            runtime.select_option(option).unwrap();
+            while !matches!(runtime.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)) {}
+        }
+        assert_eq!(runtime.variable("shop"), Some(YarnValue::NUMBER(2.0)));
+        assert_eq!(runtime.variable("alley"), Some(YarnValue::BOOL(false)));
+        assert_eq!(runtime.visits().get("alley"), None);
+
+        let snapshot = runtime.snapshot();
+        for loaded in saved_forms(&snapshot) {
+            let mut restored = new_runtime();
+            restored.restore(&loaded).unwrap();
+            assert_eq!(restored.visits(), runtime.visits());
+            assert_eq!((restored.visit_count("start"), restored.visit_count("shop"), restored.visit_count("alley")), (3, 2, 0));
+
+            assert!(matches!(restored.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)));
+            restored.select_option(0).unwrap();
+            while !matches!(restored.next_event().unwrap(), YarnDialogueEvent::OPTIONS(_)) {}
+            assert_eq!(restored.variable("shop"), Some(YarnValue::NUMBER(3.0)));
+            assert_eq!(restored.variable("alley"), Some(YarnValue::BOOL(false)));
+        }
+    }
+
+    #[test]
+    fn test_runtime_detour() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "Before.\n",
+            "<<detour aside>>\n",
+            "After.\n",
+            "<<detour quick>>\n",
+            "<<detour ending>>\n",
+            "Never.\n",
+            "===\n",
+            "title: aside\n",
+            "---\n",
+            "Aside.\n",
+            "<<jump more>>\n",
+            "===\n",
+            "title: more\n",
+            "---\n",
+            "More.\n",
+            "===\n",
+            "title: quick\n",
+            "---\n",
+            "<<return>>\n",
+            "Skipped.\n",
+            "===\n",
+            "title: ending\n",
+            "---\n",
+            "<<stop>>\n",
+            "===\n",
+        );
+
+        let rest = |runtime : &mut YarnRuntime| {
+            let mut events = Vec::new();
+            loop {
+                match runtime.next_event().unwrap() {
+                    YarnDialogueEvent::LINE(line) => events.push(line.text().to_string()),
+                    YarnDialogueEvent::NODE_COMPLETE(title) => events.push(format!("[{}]", title)),
+                    YarnDialogueEvent::DIALOGUE_COMPLETE => return events,
+                    _ => assert!(false),
+                }
+            }
+        };
+
+        let mut runtime = YarnRuntime::new(source);
+        runtime.start("start").unwrap();
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::LINE(line) if line.text() == "Before."));
+        assert!(matches!(runtime.next_event().unwrap(), YarnDialogueEvent::LINE(line) if line.text() == "Aside."));
+
+        let snapshot = runtime.snapshot();
+        assert_eq!(snapshot.detours().len(), 1);
+        assert_eq!(snapshot.detours()[0].0, "start");
+
+        let expected = rest(&mut runtime);
+        assert_eq!(expected, vec!["[aside]", "More.", "[more]", "After.", "[quick]", "[ending]"]);
+        assert_eq!(runtime.visit_count("start"), 0);
+
+        for loaded in saved_forms(&snapshot) {
+            let mut restored = YarnRuntime::new(source);
+            restored.restore(&loaded).unwrap();
+            assert_eq!(rest(&mut restored), expected);
+        }
+
+        runtime.start("start").unwrap();
+        runtime.next_event().unwrap();
+        runtime.next_event().unwrap();
+        runtime.start("quick").unwrap();
+        assert_eq!(rest(&mut runtime), vec!["[quick]"]);
+
+        let mut runtime = YarnRuntime::new("title: start\n---\n<<detour start>>\n===\n");
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap_err().error_name(), "Detour Depth Error");
+
+        let cycle = concat!(
+            "title: start\n",
+            "---\n",
+            "Ping.\n",
+            "<<detour other>>\n",
+            "===\n",
+            "title: other\n",
+            "---\n",
+            "Pong.\n",
+            "<<detour start>>\n",
+            "===\n",
+        );
+        let mut runtime = YarnRuntime::new(cycle);
+        runtime.start("start").unwrap();
+        let mut lines = 0;
+        let error = loop {
+            match runtime.next_event() {
+                Ok(YarnDialogueEvent::LINE(_)) => lines += 1,
+                Ok(_) => assert!(false),
+                Err(error) => break error,
+            }
+        };
+        assert_eq!(error.error_name(), "Detour Depth Error");
+        assert_eq!(lines, MAX_DETOUR_DEPTH + 1);
+        assert_eq!(runtime.snapshot().detours().len(), MAX_DETOUR_DEPTH);
+    }
+
+    #[test]
+    fn test_runtime_storage() {
+        let source = "title: start\n---\n<<set $gold to $gold + 1>>\n===\n";
+
+        let mut save = YarnMemoryStorage::new();
+        save.set("gold", YarnValue::NUMBER(4.0));
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+
+        let mut runtime = YarnRuntime::new(source)
+            .with_variable_listener(move |name, value| recorded.lock().unwrap().push((name.to_string(), value.clone())))
+            .with_variable("name", YarnValue::STRING("Mae".to_string()))
+            .with_variable("gold", YarnValue::NUMBER(100.0))
+            .with_storage(YarnLayeredStorage::new(save));
+        runtime.start("start").unwrap();
+        runtime.next_event().unwrap();
+
+        assert_eq!(runtime.variable("gold"), Some(YarnValue::NUMBER(5.0)));
+        assert_eq!(runtime.variable("name"), Some(YarnValue::STRING("Mae".to_string())));
+        assert_eq!(changes.lock().unwrap().last(), Some(&("gold".to_string(), YarnValue::NUMBER(5.0))));
+
+        runtime.storage_mut().set("gold", YarnValue::NUMBER(0.0));
+        assert_eq!(runtime.storage().to_map().get("gold"), Some(&YarnValue::NUMBER(0.0)));
+        assert_eq!(changes.lock().unwrap().last(), Some(&("gold".to_string(), YarnValue::NUMBER(0.0))));
+
+        runtime.start("start").unwrap();
+        runtime.next_event().unwrap();
+        assert_eq!(runtime.variable("gold"), Some(YarnValue::NUMBER(1.0)));
+    }
+
+    #[test]
+    fn test_runtime_seeded_random() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $roll to dice(20)>>\n",
+            "<<set $chance to random()>>\n",
+            "<<set $range to random_range(5, 10)>>\n",
+            "<<set $luck to luck()>>\n",
+            "===\n",
+        );
+
+        let setup = |runtime : YarnRuntime| {
+            runtime
+                .with_variable("roll", YarnValue::NUMBER(0.0))
+                .with_variable("chance", YarnValue::NUMBER(0.0))
+                .with_variable("range", YarnValue::NUMBER(0.0))
+                .with_variable("luck", YarnValue::NUMBER(0.0))
+                .with_function("luck", |_, context| Ok(Some(YarnValue::NUMBER(context.random(|random| random.range(1.0, 3.0))))))
+        };
+        let run = |runtime : YarnRuntime| {
+            let mut runtime = setup(runtime);
+            runtime.start("start").unwrap();
+            runtime.next_event().unwrap();
+            ["roll", "chance", "range", "luck"].iter().map(|name| runtime.variable(name).and_then(|value| value.as_f64()).unwrap()).collect::<Vec<f64>>()
+        };
+
+        let first = run(YarnRuntime::new(source).with_seed(7));
+        assert_eq!(first, run(YarnRuntime::new(source).with_seed(7)));
+        assert!((1.0..=20.0).contains(&first[0]) && first[0].fract() == 0.0);
+        assert!((0.0..1.0).contains(&first[1]));
+        assert!((5.0..=10.0).contains(&first[2]) && first[2].fract() == 0.0);
+        assert!((1.0..=3.0).contains(&first[3]));
+
+        let mut saved = setup(YarnRuntime::new(source).with_seed(7));
+        let state = saved.random_state();
+        saved.start("start").unwrap();
+        saved.next_event().unwrap();
+        assert_ne!(saved.random_state(), state);
+
+        let mut restored = YarnRuntime::new(source);
+        restored.set_random_state(state);
+        assert_eq!(run(restored), first);
+    }
+
+    #[test]
+    fn test_runtime_snapshot() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $gold to $gold + dice(6)>>\n",
+            "Vendor: Buy something?\n",
+            "-> Sword\n",
+            "    <<set $gold to $gold - 1>>\n",
+            "    <<jump shop>>\n",
+            "-> Leave\n",
+            "===\n",
+            "title: shop\n",
+            "---\n",
+            "<<set $gold to $gold + dice(100)>>\n",
+            "Vendor: Thanks!\n",
+            "===\n",
+        );
+
+        let play = |runtime : &mut YarnRuntime| {
+            runtime.select_option(0).unwrap();
+            let mut lines = Vec::new();
+            loop {
+                match runtime.next_event().unwrap() {
+                    YarnDialogueEvent::LINE(line) => lines.push(line.text().to_string()),
+                    YarnDialogueEvent::DIALOGUE_COMPLETE => break,
+                    _ => {},
+                }
+            }
+            (lines, runtime.variable("gold"), runtime.visit_count("start"))
+        };
+
+        let mut runtime = YarnRuntime::new(source).with_seed(3).with_variable("gold", YarnValue::NUMBER(10.0));
+        runtime.start("start").unwrap();
+        runtime.next_event().unwrap();
+        match runtime.next_event().unwrap() {
+            YarnDialogueEvent::OPTIONS(options) => assert_eq!(options.len(), 2),
+            _ => assert!(false),
+        }
+
+        let snapshot = runtime.snapshot();
+        let expected = play(&mut runtime);
+        assert_eq!(expected.0.len(), 1);
+
+        for loaded in saved_forms(&snapshot) {
+            let mut restored = YarnRuntime::new(source);
+            restored.restore(&loaded).unwrap();
+            match restored.next_event().unwrap() {
+                YarnDialogueEvent::OPTIONS(options) => assert_eq!(options[1].line().text(), "Leave"),
+                _ => assert!(false),
+            }
+            assert_eq!(play(&mut restored), expected);
+        }
+
+        #[cfg(feature = "serde")]
+        {
+            let missing = YarnSnapshot::from_json(snapshot.to_json().unwrap().replace("\"start\"", "\"nowhere\"").as_str()).unwrap();
+            assert!(YarnRuntime::new(source).restore(&missing).is_err());
+        }
+
+        let reworded = source.replace("Buy something?", "Buy something? Anything?");
+        assert!(YarnRuntime::new(reworded.as_str()).restore(&snapshot).is_ok());
+        let changed = source.replace("Vendor: Buy", "Vendor: Hello.\nVendor: Buy");
+        match YarnRuntime::new(changed.as_str()).restore(&snapshot) {
+            Err(error) => assert_eq!(error.error_name(), "Invalid Snapshot Error"),
+            _ => assert!(false),
+        }
+        let mut idle = YarnRuntime::new(source).with_variable("gold", YarnValue::NUMBER(4.0));
+        let idle_snapshot = idle.snapshot();
+        assert!(YarnRuntime::new(changed.as_str()).restore(&idle_snapshot).is_ok());
+        idle.compile().unwrap();
+        assert_eq!(idle.snapshot().program(), runtime.program().unwrap().identity());
+    }
+
+    #[test]
+    fn test_runtime_numbers() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $gold to 0.1 + 0.2>>\n",
+            "<<if $gold == 0.3>>\n",
+            "    Exact.\n",
+            "<<else>>\n",
+            "    Close.\n",
+            "<<endif>>\n",
+            "<<set $gold to $gold / $debt>>\n",
+            "===\n",
+        );
+
+        let first_line = |tolerance : f64| {
+            let mut runtime = YarnRuntime::new(source).with_tolerance(tolerance).with_variable("gold", YarnValue::NUMBER(0.0)).with_variable("debt", YarnValue::NUMBER(0.0));
+            runtime.start("start").unwrap();
+            let line = match runtime.next_event().unwrap() {
+                YarnDialogueEvent::LINE(line) => line.text().to_string(),
+                _ => String::new(),
+            };
+            (line, runtime.next_event().unwrap_err())
+        };
+
+        let (line, error) = first_line(1e-10);
+        assert_eq!(line, "Exact.");
+        assert_eq!(error.gen_error_message(), "Arithmetic Error at (8, 15) : Division by zero.");
+        assert_eq!(first_line(0.0).0, "Close.");
+    }
+
+    #[test]
+    fn test_runtime_exact_numbers() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<declare $price = 0.1 as decimal>>\n",
+            "<<declare $gold = 10 as int>>\n",
+            "<<declare $name = \"Mae\">>\n",
+            "<<set $price to $price + 0.2>>\n",
+            "<<if $price == 0.3>>\n",
+            "    <<pay {round_places($price + 2.375, 2)} {$price}>>\n",
+            "<<endif>>\n",
+            "<<set $gold to ($gold / 4) * 2>>\n",
+            "<<set $gold to $gold / 4>>\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source).with_tolerance(0.0);
+        runtime.start("start").unwrap();
+        match runtime.next_event().unwrap() {
+            YarnDialogueEvent::COMMAND(command) => assert_eq!(command.arguments(), &vec!["2.68".to_string(), "0.3".to_string()]),
+            _ => assert!(false),
+        }
+        assert!(matches!(runtime.variable("name"), Some(YarnValue::STRING(name)) if name == "Mae"));
+        assert_eq!(runtime.next_event().unwrap_err().gen_error_message(), "Arithmetic Error at (10, 0) : 1.25 is not a whole number.");
+        assert!(matches!(runtime.variable("gold"), Some(YarnValue::INT(5))));
+
+        let mut runtime = YarnRuntime::new(source).with_variable("gold", YarnValue::INT(20));
+        runtime.compile().unwrap();
+        assert!(matches!(runtime.variable("gold"), Some(YarnValue::INT(20))));
+
+        let compile_error = |declaration : &str| YarnRuntime::new(format!("title: start\n---\n{}\n===\n", declaration).as_str()).compile().unwrap_err();
+        assert_eq!(compile_error("<<declare $gold = 1.5 as int>>").error_name(), "Arithmetic Error");
+        assert_eq!(compile_error("<<declare $gold = \"a\" as int>>").error_name(), "Type Mismatch Error");
+        assert_eq!(compile_error("<<declare $gold = 1 as coins>>").error_name(), "Unexpected Token Error");
+        assert_eq!(compile_error("<<declare $gold = $silver>>").error_name(), "Variable Not Declared Error");
+        assert_eq!(compile_error("<<declare $gold = 1>>\n<<declare $gold = 2>>").error_name(), "Duplicate Variable Error");
+    }
+
+    #[test]
+    fn test_runtime_enums() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<enum QuestStage>>\n",
+            "    <<case NotStarted>>\n",
+            "    <<case Started>>\n",
+            "    <<case Complete>>\n",
+            "<<endenum>>\n",
+            "<<declare $stage = QuestStage.NotStarted>>\n",
+            "<<set $stage to .Started>>\n",
+            "<<if $stage == QuestStage.Started>>\n",
+            "    <<quest {$stage}>>\n",
+            "<<endif>>\n",
+            "<<set $stage to QuestStage.Complete>>\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source);
+        runtime.start("start").unwrap();
+        match runtime.next_event().unwrap() {
+            YarnDialogueEvent::COMMAND(command) => assert_eq!(command.arguments(), &vec!["QuestStage.Started".to_string()]),
+            _ => assert!(false),
+        }
+
+        let snapshot = runtime.snapshot();
+        for loaded in saved_forms(&snapshot) {
+            let mut restored = YarnRuntime::new(source);
+            restored.restore(&loaded).unwrap();
+            assert_eq!(restored.variable("stage"), Some(YarnValue::ENUM("QuestStage".to_string(), "Started".to_string())));
+        }
+
+        runtime.next_event().unwrap();
+        assert_eq!(runtime.variable("stage"), Some(YarnValue::ENUM("QuestStage".to_string(), "Complete".to_string())));
+
+        let enums = "<<enum Mood>>\n<<case Happy>>\n<<endenum>>\n<<enum Weather>>\n<<case Sunny>>\n<<case Happy>>\n<<endenum>>\n";
+        let compile_error = |body : &str| YarnRuntime::new(format!("title: start\n---\n{}{}\n===\n", enums, body).as_str()).compile().unwrap_err();
+        assert_eq!(compile_error("<<if Mood.Happy == Weather.Sunny>>\n<<endif>>").error_name(), "Type Mismatch Error");
+        assert_eq!(compile_error("<<declare $mood = Mood.Happy>>\n<<set $mood to .Sunny>>").error_name(), "Type Mismatch Error");
+        assert_eq!(compile_error("<<set $mood to .Happy>>").error_name(), "Variable Not Declared Error");
+        assert_eq!(compile_error("<<if .Happy == .Happy>>\n<<endif>>").error_name(), "Enum Error");
+        assert_eq!(compile_error("<<declare $mood = .Happy>>").error_name(), "Enum Error");
+        assert_eq!(compile_error("<<declare $mood = .Sunny as Mood>>").error_name(), "Enum Error");
+
+        let shorthand = concat!(
+            "<<declare $mood = Mood.Happy>>\n",
+            "<<declare $weather = .Happy as Weather>>\n",
+            "<<set $mood to .Happy>>\n",
+            "<<if .Happy == $mood>>\n",
+            "    <<if $weather != .Sunny>>\n",
+            "        <<set $weather to .Sunny>>\n",
+            "    <<endif>>\n",
+            "<<endif>>\n",
+        );
+        let mut runtime = YarnRuntime::new(format!("title: start\n---\n{}{}===\n", enums, shorthand).as_str());
+        runtime.start("start").unwrap();
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.variable("mood"), Some(YarnValue::ENUM("Mood".to_string(), "Happy".to_string())));
+        assert_eq!(runtime.variable("weather"), Some(YarnValue::ENUM("Weather".to_string(), "Sunny".to_string())));
+        assert_eq!(compile_error("<<declare $mood = Mood.Sad>>").error_name(), "Enum Error");
+        assert_eq!(compile_error("<<enum Mood>>\n<<case Sad>>\n<<endenum>>").error_name(), "Enum Error");
+    }
+
+    #[test]
+    fn test_runtime_closure_functions() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $coins to coins()>>\n",
+            "<<set $coins to pay(3)>>\n",
+            "===\n",
+        );
+
+        let inventory = Arc::new(Mutex::new(10.0));
+        let coins = inventory.clone();
+        let spent = inventory.clone();
+        let pay : Box<dyn Fn(Vec<YarnValue>, &YarnFunctionContext) -> YarnResult<Option<YarnValue>> + Send + Sync> = Box::new(move |arguments, _| {
+            let mut coins = spent.lock().unwrap();
+            *coins -= arguments[0].as_f64().unwrap();
+            Ok(Some(YarnValue::NUMBER(*coins)))
+        });
+
+        let mut runtime = YarnRuntime::new(source)
+            .with_variable("coins", YarnValue::NUMBER(0.0))
+            .with_function("coins", move |_, _| Ok(Some(YarnValue::NUMBER(*coins.lock().unwrap()))))
+            .with_function("pay", pay);
+        runtime.start("start").unwrap();
+
+        assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(runtime.variable("coins"), Some(YarnValue::NUMBER(7.0)));
+        assert_eq!(*inventory.lock().unwrap(), 7.0);
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use crate::{value::YarnValue, parcer::YarnVariableMap};
+
+//Called with the name and new value of a variable whenever one is set.
+pub type YarnVariableListener = Arc<dyn Fn(&str, &YarnValue) + Send + Sync>;
+
+//Where the dialogue keeps its variables. The game can implement this to keep them in its own save data instead of
+//copying them in and out of the runtime. Values are handed out by value so they can come from an ECS or a database
+//rather than memory the storage owns.
+pub trait YarnVariableStorage {
+    fn get(&self, name : &str) -> Option<YarnValue>;
+    fn set(&mut self, name : &str, value : YarnValue);
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, YarnValue)> + '_>;
+
+    fn contains(&self, name : &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    fn to_map(&self) -> YarnVariableMap {
+        self.iter().collect()
+    }
+}
+
+//==================================================================================================================
+//                   Memory Storage
+//==================================================================================================================
+
+#[derive(Clone, Default)]
+pub struct YarnMemoryStorage {
+    values : YarnVariableMap
+}
+
+impl YarnMemoryStorage {
+    pub fn new() -> YarnMemoryStorage {
+        YarnMemoryStorage::default()
+    }
+}
+
+impl From<YarnVariableMap> for YarnMemoryStorage {
+    fn from(values : YarnVariableMap) -> Self {
+        YarnMemoryStorage { values }
+    }
+}
+
+impl YarnVariableStorage for YarnMemoryStorage {
+    fn get(&self, name : &str) -> Option<YarnValue> {
+        self.values.get(name).cloned()
+    }
+
+    fn set(&mut self, name : &str, value : YarnValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, YarnValue)> + '_> {
+        Box::new(self.values.iter().map(|(name, value)| (name.clone(), value.clone())))
+    }
+}
+
+//==================================================================================================================
+//                   Layered Storage
+//==================================================================================================================
+
+//Stacks storages on top of each other. Reads look from the top layer down and writes always go to the top layer,
+//so the layers below are never changed. Useful for keeping defaults or another save file underneath the player's own.
+pub struct YarnLayeredStorage {
+    layers : Vec<Box<dyn YarnVariableStorage>>
+}
+
+impl YarnLayeredStorage {
+    pub fn new<S : YarnVariableStorage + 'static>(base : S) -> YarnLayeredStorage {
+        YarnLayeredStorage { layers: vec![Box::new(base)] }
+    }
+
+    pub fn with_layer<S : YarnVariableStorage + 'static>(mut self, layer : S) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    pub fn layers(&self) -> &Vec<Box<dyn YarnVariableStorage>> {
+        &self.layers
+    }
+}
+
+impl YarnVariableStorage for YarnLayeredStorage {
+    fn get(&self, name : &str) -> Option<YarnValue> {
+        self.layers.iter().rev().find_map(|layer| layer.get(name))
+    }
+
+    fn set(&mut self, name : &str, value : YarnValue) {
+        self.layers.last_mut().unwrap().set(name, value);
+    }
+
+    //Each variable once, with the value a read would give.
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, YarnValue)> + '_> {
+        Box::new(self.layers.iter().enumerate().flat_map(move |(index, layer)| {
+            layer.iter().filter(move |(name, _)| !self.layers[index + 1..].iter().any(|above| above.contains(name)))
+        }))
+    }
+}
+
+//==================================================================================================================
+//                   Watched Storage
+//==================================================================================================================
+
+//What the runtime keeps its storage in, so listeners are called on every set no matter which storage the game uses.
+pub(crate) struct YarnWatchedStorage {
+    storage : Box<dyn YarnVariableStorage>,
+    listeners : Vec<YarnVariableListener>
+}
+
+impl YarnWatchedStorage {
+    pub(crate) fn new<S : YarnVariableStorage + 'static>(storage : S) -> YarnWatchedStorage {
+        YarnWatchedStorage { storage: Box::new(storage), listeners: Vec::new() }
+    }
+
+    pub(crate) fn add_listener(&mut self, listener : YarnVariableListener) {
+        self.listeners.push(listener);
+    }
+
+    //Values the new storage does not have yet are copied over, so nothing set before the swap is lost.
+    pub(crate) fn replace<S : YarnVariableStorage + 'static>(&mut self, storage : S) {
+        let old = std::mem::replace(&mut self.storage, Box::new(storage));
+        for (name, value) in old.iter() {
+            if !self.storage.contains(&name) {
+                self.storage.set(&name, value);
+            }
+        }
+    }
+}
+
+impl YarnVariableStorage for YarnWatchedStorage {
+    fn get(&self, name : &str) -> Option<YarnValue> {
+        self.storage.get(name)
+    }
+
+    fn set(&mut self, name : &str, value : YarnValue) {
+        for listener in self.listeners.iter() {
+            listener(name, &value);
+        }
+        self.storage.set(name, value);
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, YarnValue)> + '_> {
+        self.storage.iter()
+    }
+
+    fn contains(&self, name : &str) -> bool {
+        self.storage.contains(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn test_memory_storage() {
+        let mut storage = YarnMemoryStorage::new();
+        storage.set("gold", YarnValue::NUMBER(1.0));
+        storage.set("name", YarnValue::STRING("Mae".to_string()));
+
+        assert!(storage.contains("gold"));
+        assert!(!storage.contains("silver"));
+        assert_eq!(storage.get("name"), Some(YarnValue::STRING("Mae".to_string())));
+        assert_eq!(storage.iter().count(), 2);
+    }
+
+    //Keeps every value as text, like a save file or database row would, so there is no YarnValue to lend out.
+    #[derive(Default)]
+    struct TextStorage {
+        rows : Vec<(String, String)>
+    }
+
+    impl YarnVariableStorage for TextStorage {
+        fn get(&self, name : &str) -> Option<YarnValue> {
+            self.rows.iter().find(|(row, _)| row == name).map(|(_, text)| match text.parse::<f64>() {
+                Ok(number) => YarnValue::NUMBER(number),
+                Err(_) => YarnValue::STRING(text.clone()),
+            })
+        }
+
+        fn set(&mut self, name : &str, value : YarnValue) {
+            self.rows.retain(|(row, _)| row != name);
+            self.rows.push((name.to_string(), value.to_string()));
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = (String, YarnValue)> + '_> {
+            Box::new(self.rows.iter().filter_map(|(name, _)| self.get(name).map(|value| (name.clone(), value))))
+        }
+    }
+
+    #[test]
+    fn test_custom_storage() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+
+        let mut storage = YarnWatchedStorage::new(TextStorage::default());
+        storage.add_listener(Arc::new(move |name, value| recorded.lock().unwrap().push((name.to_string(), value.clone()))));
+        storage.set("gold", YarnValue::NUMBER(2.5));
+        storage.set("gold", YarnValue::NUMBER(3.0));
+        storage.set("name", YarnValue::STRING("Mae".to_string()));
+
+        assert_eq!(storage.get("gold"), Some(YarnValue::NUMBER(3.0)));
+        assert!(storage.contains("name"));
+        assert_eq!(storage.to_map().len(), 2);
+        assert_eq!(changes.lock().unwrap().len(), 3);
+        assert_eq!(changes.lock().unwrap().last(), Some(&("name".to_string(), YarnValue::STRING("Mae".to_string()))));
+
+        let mut save = TextStorage::default();
+        save.set("gold", YarnValue::NUMBER(7.0));
+        storage.replace(save);
+        assert_eq!(storage.get("gold"), Some(YarnValue::NUMBER(7.0)));
+        assert_eq!(storage.get("name"), Some(YarnValue::STRING("Mae".to_string())));
+        assert_eq!(changes.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_layered_storage() {
+        let mut defaults = YarnMemoryStorage::new();
+        defaults.set("gold", YarnValue::NUMBER(0.0));
+        defaults.set("name", YarnValue::STRING("Mae".to_string()));
+
+        let mut save = YarnMemoryStorage::new();
+        save.set("gold", YarnValue::NUMBER(5.0));
+
+        let mut storage = YarnLayeredStorage::new(defaults).with_layer(save);
+        assert_eq!(storage.get("gold"), Some(YarnValue::NUMBER(5.0)));
+        assert_eq!(storage.get("name"), Some(YarnValue::STRING("Mae".to_string())));
+
+        storage.set("name", YarnValue::STRING("Bea".to_string()));
+        assert_eq!(storage.get("name"), Some(YarnValue::STRING("Bea".to_string())));
+        assert_eq!(storage.layers()[0].get("name"), Some(YarnValue::STRING("Mae".to_string())));
+
+        let map = storage.to_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("gold"), Some(&YarnValue::NUMBER(5.0)));
+        assert_eq!(storage.iter().count(), 2);
+    }
+}
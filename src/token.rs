@@ -60,6 +60,14 @@ impl <'a> YarnTokenQueue<'a> {
         }
     }
 
+    pub fn check_word(&self, index : usize, word : &str) -> bool {
+        if let Some(token) = self.peek_only_if_type(index, YarnTokenType::WORD) {
+            token.content() == word
+        } else {
+            false
+        }
+    }
+
     pub fn check_and_pop(&mut self, token_type : YarnTokenType) -> bool {
         if self.check(token_type) {
             self.tokens.pop_front();
@@ -121,6 +129,42 @@ impl <'a> YarnTokenQueue<'a> {
         self.tokens.front()
     }
 
+    pub fn skip_spaces(&self, offset : usize) -> usize {
+        let mut index = offset;
+        while self.check_index(index, YarnTokenType::SPACE) || self.check_index(index, YarnTokenType::TAB) {
+            index += 1;
+        }
+        index
+    }
+
+    pub fn content_between(&self, start : usize, end : usize) -> String {
+        let mut content = String::new();
+        for index in start..end {
+            if let Some(token) = self.tokens.get(index) {
+                content.push_str(token.content());
+            }
+        }
+        content
+    }
+
+    //Words containing if, else or end are split into several tokens. This joins them back up and gives the word with
+    //the index after it.
+    pub fn identifier(&self, index : usize) -> Option<(String, usize)> {
+        let mut end = index;
+        while let Some(token) = self.tokens.get(end) {
+            match token.token_type {
+                YarnTokenType::WORD | YarnTokenType::IF | YarnTokenType::ELSE | YarnTokenType::ELSEIF | YarnTokenType::END | YarnTokenType::ENDIF => end += 1,
+                _ => break
+            }
+        }
+
+        if end == index {
+            None
+        } else {
+            Some((self.content_between(index, end), end))
+        }
+    }
+
     pub fn next_non_space_after(&self, offset : usize) -> usize {
         let mut next_index = 1;
         while self.check_index(offset + next_index, YarnTokenType::SPACE) {
@@ -171,6 +215,8 @@ pub enum YarnTokenType {
     RIGHT_PAREN,
     LEFT_SQUARE_BRACKET,
     RIGHT_SQUARE_BRACKET,
+    LEFT_BRACE,
+    RIGHT_BRACE,
     EQUAL_TOO,
     NOT_EQUAL_TOO,
     LESS_THAN,
@@ -183,9 +229,10 @@ pub enum YarnTokenType {
     DOLLAR_SIGN
 }
 
-const TOKEN_MAP : [(YarnTokenType, &'static str); 23] = [
+const TOKEN_MAP : [(YarnTokenType, &'static str); 26] = [
     (YarnTokenType::COLON, ":"),
     (YarnTokenType::SPACE, " "),
+    (YarnTokenType::TAB, "\t"),
     (YarnTokenType::IF, "if"),
     (YarnTokenType::ELSE, "else"),
     (YarnTokenType::END, "end"),
@@ -198,6 +245,8 @@ const TOKEN_MAP : [(YarnTokenType, &'static str); 23] = [
     (YarnTokenType::HASHTAG, "#"),
     (YarnTokenType::LEFT_SQUARE_BRACKET, "["),
     (YarnTokenType::RIGHT_SQUARE_BRACKET, "]"),
+    (YarnTokenType::LEFT_BRACE, "{"),
+    (YarnTokenType::RIGHT_BRACE, "}"),
     (YarnTokenType::LEFT_PAREN, "("),
     (YarnTokenType::RIGHT_PAREN, ")"),
     (YarnTokenType::FORWARD_SLASH, "/"),
@@ -292,8 +341,8 @@ pub fn tokenize<'a>(source : &'a str) -> YarnTokenQueue<'a> {
     let mut queue = YarnTokenQueue { source, tokens: VecDeque::new() };
 
     unsafe {
-        let mut line_offset = 0;
         for (line_number, line) in source.lines().enumerate() {
+            let line_offset = line.as_ptr() as usize - source.as_ptr() as usize;
             queue.add(line_number, 0, line_offset, 0, YarnTokenType::START_LINE);
             let mut anchor = 0;
             let mut offset = 0;
@@ -328,11 +377,10 @@ pub fn tokenize<'a>(source : &'a str) -> YarnTokenQueue<'a> {
                 }
             }
 
-            queue.add(line_number, line.len(), line_offset + line.len() - 1, 0, YarnTokenType::END_LINE);
-            line_offset += line.len()
+            queue.add(line_number, line.len(), line_offset + line.len(), 0, YarnTokenType::END_LINE);
         }
 
-        queue.add(0, 0, line_offset, 0, YarnTokenType::EOF)
+        queue.add(0, 0, source.len(), 0, YarnTokenType::EOF)
     }
 
     match_tokens(&mut queue);
@@ -443,4 +491,13 @@ mod tests {
 
         assert_eq!(token_2.content(), "This is test")
     }
+
+    #[test]
+    fn test_multiline_offsets() {
+        let q = tokenize("title: a\r\n---\n\n===");
+        has_tokens!(q, 0, START_LINE, WORD, COLON, SPACE, WORD, END_LINE, START_LINE, START_NODE, END_LINE, START_LINE, END_LINE, START_LINE, END_NODE, END_LINE, EOF);
+        assert_eq!(q.peek(4).unwrap().content(), "a");
+        assert_eq!(q.peek(7).unwrap().content(), "---");
+        assert_eq!(q.peek(12).unwrap().content(), "===");
+    }
 }
\ No newline at end of file
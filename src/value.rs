@@ -1,17 +1,60 @@
-#[derive(Debug, Clone, PartialOrd)]
+use std::{cmp::Ordering, fmt::{self, Display}};
+
+use crate::{error::{YarnError, YarnResult}, decimal::YarnDecimal};
+
+//With serde values are tagged by their type, like {"NUMBER": 2.0}. Saves and tools depend on this, so the variant
+//names must not change.
+//INT and DECIMAL are exact numbers, used by variables declared `as int` or `as decimal`. They are all NUMBER to the
+//type checker and can be mixed freely with floats, see number_pair for how.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum YarnValue {
     STRING(String),
     NUMBER(f64),
-    BOOL(bool)
+    BOOL(bool),
+    INT(i64),
+    DECIMAL(YarnDecimal),
+    ENUM(String, String) // Enum name, Case name
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum YarnValueType {
+    STRING,
+    NUMBER,
+    BOOL,
+    ANY,
+    ENUM(String) // Enum name
+}
+
+//Numbers are equal when they are within the tolerance of each other, scaled by the larger of the two once they are
+//bigger than one so large totals compare as sensibly as small ones. The runtime can be given its own tolerance.
+pub const DEFAULT_TOLERANCE : f64 = 1e-10;
+
+pub fn numbers_equal(lhs : f64, rhs : f64, tolerance : f64) -> bool {
+    lhs == rhs || (lhs - rhs).abs() <= tolerance * lhs.abs().max(rhs.abs()).max(1.0)
 }
 
 impl PartialEq for YarnValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::STRING(l0), Self::STRING(r0)) => l0 == r0,
-            (Self::NUMBER(l0), Self::NUMBER(r0)) => (l0 - r0) < 1e-10,
             (Self::BOOL(l0), Self::BOOL(r0)) => l0 == r0,
-            _ => false
+            (Self::ENUM(l0, l1), Self::ENUM(r0, r1)) => l0 == r0 && l1 == r1,
+            _ => self.compare(other, DEFAULT_TOLERANCE) == Some(Ordering::Equal)
+        }
+    }
+}
+
+//Ordered the same way as they are compared for equality, so numbers of any kind order by value. Values of different
+//types have no order.
+impl PartialOrd for YarnValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::STRING(l0), Self::STRING(r0)) => l0.partial_cmp(r0),
+            (Self::BOOL(l0), Self::BOOL(r0)) => l0.partial_cmp(r0),
+            (Self::ENUM(l0, l1), Self::ENUM(r0, r1)) => (l0, l1).partial_cmp(&(r0, r1)),
+            _ => self.compare(other, DEFAULT_TOLERANCE)
         }
     }
 }
@@ -21,14 +64,33 @@ impl YarnValue {
     pub fn get_type_as_string(&self) -> &str {
         match self {
             YarnValue::STRING(_) => "STRING",
-            YarnValue::NUMBER(_) => "NUMBER",
+            YarnValue::NUMBER(_) | YarnValue::INT(_) | YarnValue::DECIMAL(_) => "NUMBER",
             YarnValue::BOOL(_) => "BOOL",
+            YarnValue::ENUM(name, _) => name.as_str(),
+        }
+    }
+
+    pub fn get_type(&self) -> YarnValueType {
+        match self {
+            YarnValue::STRING(_) => YarnValueType::STRING,
+            YarnValue::NUMBER(_) | YarnValue::INT(_) | YarnValue::DECIMAL(_) => YarnValueType::NUMBER,
+            YarnValue::BOOL(_) => YarnValueType::BOOL,
+            YarnValue::ENUM(name, _) => YarnValueType::ENUM(name.clone()),
+        }
+    }
+
+    //Reads back an enum value written with to_string, like "Mood.Happy", for storages that keep everything as text.
+    pub fn parse_enum(text : &str) -> Option<YarnValue> {
+        let is_identifier = |part : &str| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_');
+        match text.split_once('.') {
+            Some((name, case)) if is_identifier(name) && is_identifier(case) => Some(YarnValue::ENUM(name.to_string(), case.to_string())),
+            _ => None
         }
     }
 
     pub fn is_number(&self) -> bool {
         match self {
-            YarnValue::NUMBER(_) => true,
+            YarnValue::NUMBER(_) | YarnValue::INT(_) | YarnValue::DECIMAL(_) => true,
             _ => false,
         }
     }
@@ -36,215 +98,226 @@ impl YarnValue {
     pub fn as_f64(&self) -> Option<f64> {
         match self {
             YarnValue::NUMBER(num) => Some(*num),
+            YarnValue::INT(num) => Some(*num as f64),
+            YarnValue::DECIMAL(num) => Some(num.to_f64()),
             _ => None,
         }
     }
 
     pub fn is_equal(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::STRING(s1) => {
-                match other {
-                    YarnValue::STRING(s2) => Some(YarnValue::BOOL(s1 == s2)),
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::BOOL(n1 == n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::BOOL(b1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(b2) => Some(YarnValue::BOOL(b1 == b2)),
-                }
-            },
-        }
+        self.is_equal_within(other, DEFAULT_TOLERANCE)
     }
 
     pub fn is_not_equal(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::STRING(s1) => {
-                match other {
-                    YarnValue::STRING(s2) => Some(YarnValue::BOOL(s1 != s2)),
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::BOOL(n1 != n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::BOOL(b1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(b2) => Some(YarnValue::BOOL(b1 != b2)),
-                }
-            },
+        self.is_not_equal_within(other, DEFAULT_TOLERANCE)
+    }
+
+    pub fn is_equal_within(&self, other : &YarnValue, tolerance : f64) -> Option<YarnValue> {
+        match (self, other) {
+            (YarnValue::STRING(s1), YarnValue::STRING(s2)) => Some(YarnValue::BOOL(s1 == s2)),
+            (YarnValue::BOOL(b1), YarnValue::BOOL(b2)) => Some(YarnValue::BOOL(b1 == b2)),
+            (YarnValue::ENUM(e1, c1), YarnValue::ENUM(e2, c2)) if e1 == e2 => Some(YarnValue::BOOL(c1 == c2)),
+            _ if self.is_number() && other.is_number() => Some(YarnValue::BOOL(self.compare(other, tolerance) == Some(Ordering::Equal))),
+            _ => None
         }
     }
 
-    pub fn is_less_than(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::BOOL(n1 < n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
+    pub fn is_not_equal_within(&self, other : &YarnValue, tolerance : f64) -> Option<YarnValue> {
+        match self.is_equal_within(other, tolerance) {
+            Some(YarnValue::BOOL(equal)) => Some(YarnValue::BOOL(!equal)),
             _ => None
         }
     }
 
+    //Orders two numbers, treating them as equal when == would, so <= and >= always agree with ==. The tolerance only
+    //applies to floats, exact numbers are compared exactly.
+    pub fn compare(&self, other : &YarnValue, tolerance : f64) -> Option<Ordering> {
+        match number_pair(self, other)? {
+            YarnNumberPair::FLOAT(n1, n2) if numbers_equal(n1, n2, tolerance) => Some(Ordering::Equal),
+            YarnNumberPair::FLOAT(n1, n2) => n1.partial_cmp(&n2),
+            YarnNumberPair::INT(n1, n2) => Some(n1.cmp(&n2)),
+            YarnNumberPair::DECIMAL(n1, n2) => Some(n1.cmp(&n2)),
+        }
+    }
+
+    pub fn is_less_than(&self, other : &YarnValue) -> Option<YarnValue> {
+        self.compare(other, DEFAULT_TOLERANCE).map(|order| YarnValue::BOOL(order == Ordering::Less))
+    }
+
     pub fn is_less_than_eq(&self, other : &YarnValue) -> Option<YarnValue> {
+        self.compare(other, DEFAULT_TOLERANCE).map(|order| YarnValue::BOOL(order != Ordering::Greater))
+    }
+
+    pub fn is_greater_than(&self, other : &YarnValue) -> Option<YarnValue> {
+        self.compare(other, DEFAULT_TOLERANCE).map(|order| YarnValue::BOOL(order == Ordering::Greater))
+    }
+
+    pub fn is_greater_than_eq(&self, other : &YarnValue) -> Option<YarnValue> {
+        self.compare(other, DEFAULT_TOLERANCE).map(|order| YarnValue::BOOL(order != Ordering::Less))
+    }
+
+    pub fn add(&self, other : &YarnValue) -> Option<YarnValue> {
+        match (self, other) {
+            (YarnValue::STRING(_), _) | (_, YarnValue::STRING(_)) => Some(YarnValue::STRING(format!("{}{}", self, other))),
+            _ => match number_pair(self, other)? {
+                YarnNumberPair::FLOAT(n1, n2) => Some(YarnValue::NUMBER(n1 + n2)),
+                YarnNumberPair::INT(n1, n2) => n1.checked_add(n2).map(YarnValue::INT),
+                YarnNumberPair::DECIMAL(n1, n2) => n1.checked_add(n2).map(YarnValue::DECIMAL),
+            }
+        }
+    }
+
+    pub fn mult(&self, other : &YarnValue) -> Option<YarnValue> {
+        match number_pair(self, other)? {
+            YarnNumberPair::FLOAT(n1, n2) => Some(YarnValue::NUMBER(n1 * n2)),
+            YarnNumberPair::INT(n1, n2) => n1.checked_mul(n2).map(YarnValue::INT),
+            YarnNumberPair::DECIMAL(n1, n2) => n1.checked_mul(n2).map(YarnValue::DECIMAL),
+        }
+    }
+
+    pub fn sub(&self, other : &YarnValue) -> Option<YarnValue> {
+        match number_pair(self, other)? {
+            YarnNumberPair::FLOAT(n1, n2) => Some(YarnValue::NUMBER(n1 - n2)),
+            YarnNumberPair::INT(n1, n2) => n1.checked_sub(n2).map(YarnValue::INT),
+            YarnNumberPair::DECIMAL(n1, n2) => n1.checked_sub(n2).map(YarnValue::DECIMAL),
+        }
+    }
+
+    //Dividing two ints only gives an int when nothing is left over, otherwise the result is a decimal.
+    pub fn div(&self, other : &YarnValue) -> Option<YarnValue> {
+        match number_pair(self, other)? {
+            YarnNumberPair::FLOAT(n1, n2) => Some(YarnValue::NUMBER(n1 / n2)),
+            YarnNumberPair::INT(n1, n2) if n1.checked_rem(n2) == Some(0) => n1.checked_div(n2).map(YarnValue::INT),
+            YarnNumberPair::INT(n1, n2) => YarnDecimal::from_int(n1)?.checked_div(YarnDecimal::from_int(n2)?).map(YarnValue::DECIMAL),
+            YarnNumberPair::DECIMAL(n1, n2) => n1.checked_div(n2).map(YarnValue::DECIMAL),
+        }
+    }
+
+    //The remainder keeps the sign of the number being divided, like the official runtime's.
+    pub fn rem(&self, other : &YarnValue) -> Option<YarnValue> {
+        match number_pair(self, other)? {
+            YarnNumberPair::FLOAT(n1, n2) => Some(YarnValue::NUMBER(n1 % n2)),
+            YarnNumberPair::INT(n1, n2) => n1.checked_rem(n2).map(YarnValue::INT),
+            YarnNumberPair::DECIMAL(n1, n2) => n1.checked_rem(n2).map(YarnValue::DECIMAL),
+        }
+    }
+
+    pub fn negate(&self) -> Option<YarnValue> {
         match self {
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::BOOL(n1 <= n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
+            YarnValue::NUMBER(value) => Some(YarnValue::NUMBER(-value)),
+            YarnValue::INT(value) => value.checked_neg().map(YarnValue::INT),
+            YarnValue::DECIMAL(value) => value.units().checked_neg().map(|units| YarnValue::DECIMAL(YarnDecimal::from_units(units))),
             _ => None
         }
     }
 
-    pub fn is_greater_than(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::BOOL(n1 > n2)),
-                    YarnValue::BOOL(_) => None,
+    //Converts a number being stored into a variable to the kind of number the variable already holds, so an int
+    //stays an int however it is set. Anything else is stored as it is.
+    pub fn stored_as(&self, current : &YarnValue, line : usize, col : usize) -> YarnResult<YarnValue> {
+        if !self.is_number() {
+            return Ok(self.clone());
+        }
+
+        match current {
+            YarnValue::NUMBER(_) => Ok(YarnValue::NUMBER(self.as_f64().unwrap())),
+            YarnValue::INT(_) => match self {
+                YarnValue::INT(value) => Ok(YarnValue::INT(*value)),
+                YarnValue::DECIMAL(value) => value.to_int().map(YarnValue::INT)
+                    .ok_or_else(|| YarnError::new_arithmetic_error(line, col, format!("{} is not a whole number.", value).as_str())),
+                _ => {
+                    let value = self.as_f64().unwrap();
+                    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+                        Ok(YarnValue::INT(value as i64))
+                    } else {
+                        Err(YarnError::new_arithmetic_error(line, col, format!("{} is not a whole number.", self).as_str()))
+                    }
                 }
             },
-            _ => None
+            YarnValue::DECIMAL(_) => self.as_decimal().map(YarnValue::DECIMAL)
+                .ok_or_else(|| YarnError::new_arithmetic_error(line, col, "The result is too large to be a number.")),
+            _ => Ok(self.clone())
         }
     }
 
-    pub fn is_greater_than_eq(&self, other : &YarnValue) -> Option<YarnValue> {
+    fn as_decimal(&self) -> Option<YarnDecimal> {
         match self {
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(_) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::BOOL(n1 >= n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
+            YarnValue::NUMBER(value) => YarnDecimal::from_f64(*value),
+            YarnValue::INT(value) => YarnDecimal::from_int(*value),
+            YarnValue::DECIMAL(value) => Some(*value),
             _ => None
         }
     }
+}
 
-    pub fn add(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::STRING(s1) => {
-                match other {
-                    YarnValue::STRING(s2) => Some(YarnValue::STRING(format!("{}{}", s1, s2))),
-                    YarnValue::NUMBER(n2) => Some(YarnValue::STRING(format!("{}{}", s1, n2))),
-                    YarnValue::BOOL(b2) => Some(YarnValue::STRING(format!("{}{}", s1, b2))),
-                }
-            },
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(s2) => Some(YarnValue::STRING(format!("{}{}", n1, s2))),
-                    YarnValue::NUMBER(n2) => Some(YarnValue::NUMBER(n1 + n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::BOOL(b1) => {
-                match other {
-                    YarnValue::STRING(s2) => Some(YarnValue::STRING(format!("{}{}", b1, s2))),
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(_) => None,
-                }
-            },
+//How two numbers meet in arithmetic. Two floats stay floats and two ints stay ints. Any other mix is worked out
+//exactly as decimals, with a float first rounded to the decimal places, unless one side is too large to be a decimal
+//in which case both are treated as floats.
+enum YarnNumberPair {
+    FLOAT(f64, f64),
+    INT(i64, i64),
+    DECIMAL(YarnDecimal, YarnDecimal)
+}
+
+fn number_pair(lhs : &YarnValue, rhs : &YarnValue) -> Option<YarnNumberPair> {
+    match (lhs, rhs) {
+        (YarnValue::NUMBER(n1), YarnValue::NUMBER(n2)) => Some(YarnNumberPair::FLOAT(*n1, *n2)),
+        (YarnValue::INT(n1), YarnValue::INT(n2)) => Some(YarnNumberPair::INT(*n1, *n2)),
+        _ => match (lhs.as_decimal(), rhs.as_decimal()) {
+            (Some(n1), Some(n2)) => Some(YarnNumberPair::DECIMAL(n1, n2)),
+            _ => Some(YarnNumberPair::FLOAT(lhs.as_f64()?, rhs.as_f64()?))
         }
     }
+}
 
-    pub fn mult(&self, other : &YarnValue) -> Option<YarnValue> {
+//The type level versions of the operations above. These are used to check expressions before they are run,
+//and ANY is accepted wherever a concrete type would be.
+impl YarnValueType {
+
+    pub fn get_type_as_string(&self) -> &str {
         match self {
-            YarnValue::STRING(s1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(n2) => None,
-                    YarnValue::BOOL(b2) => None,
-                }
-            },
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::NUMBER(n1 * n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::BOOL(b1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(_) => None,
-                }
-            },
+            YarnValueType::STRING => "STRING",
+            YarnValueType::NUMBER => "NUMBER",
+            YarnValueType::BOOL => "BOOL",
+            YarnValueType::ANY => "ANY",
+            YarnValueType::ENUM(name) => name.as_str(),
         }
     }
 
-    pub fn sub(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::STRING(s1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(n2) => None,
-                    YarnValue::BOOL(b2) => None,
-                }
-            },
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::NUMBER(n1 - n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::BOOL(b1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(_) => None,
-                }
-            }
+    pub fn accepts(&self, other : &YarnValueType) -> bool {
+        self == other || *self == YarnValueType::ANY || *other == YarnValueType::ANY
+    }
+
+    pub fn is_equal(&self, other : &YarnValueType) -> Option<YarnValueType> {
+        if self.accepts(other) {
+            Some(YarnValueType::BOOL)
+        } else {
+            None
         }
     }
 
-    pub fn div(&self, other : &YarnValue) -> Option<YarnValue> {
-        match self {
-            YarnValue::STRING(s1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(n2) => None,
-                    YarnValue::BOOL(b2) => None,
-                }
-            },
-            YarnValue::NUMBER(n1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(n2) => Some(YarnValue::NUMBER(n1 / n2)),
-                    YarnValue::BOOL(_) => None,
-                }
-            },
-            YarnValue::BOOL(b1) => {
-                match other {
-                    YarnValue::STRING(s2) => None,
-                    YarnValue::NUMBER(_) => None,
-                    YarnValue::BOOL(_) => None,
-                }
-            },
+    pub fn compare(&self, other : &YarnValueType) -> Option<YarnValueType> {
+        if YarnValueType::NUMBER.accepts(self) && YarnValueType::NUMBER.accepts(other) {
+            Some(YarnValueType::BOOL)
+        } else {
+            None
+        }
+    }
+
+    pub fn add(&self, other : &YarnValueType) -> Option<YarnValueType> {
+        match (self, other) {
+            (YarnValueType::STRING, _) | (_, YarnValueType::STRING) => Some(YarnValueType::STRING),
+            (YarnValueType::NUMBER, YarnValueType::NUMBER) => Some(YarnValueType::NUMBER),
+            (YarnValueType::ANY, YarnValueType::BOOL) | (YarnValueType::BOOL, YarnValueType::ANY) => Some(YarnValueType::STRING),
+            (YarnValueType::ANY, _) | (_, YarnValueType::ANY) => Some(YarnValueType::ANY),
+            _ => None
+        }
+    }
+
+    pub fn arithmetic(&self, other : &YarnValueType) -> Option<YarnValueType> {
+        if YarnValueType::NUMBER.accepts(self) && YarnValueType::NUMBER.accepts(other) {
+            Some(YarnValueType::NUMBER)
+        } else {
+            None
         }
     }
 }
@@ -262,4 +335,534 @@ impl From<&str> for YarnValue {
             YarnValue::STRING(value.to_string())
         }
     }
-}
\ No newline at end of file
+}
+
+//Unlike &str, a String is always kept as a STRING.
+impl From<String> for YarnValue {
+    fn from(value : String) -> Self {
+        YarnValue::STRING(value)
+    }
+}
+
+impl From<f64> for YarnValue {
+    fn from(value : f64) -> Self {
+        YarnValue::NUMBER(value)
+    }
+}
+
+impl From<f32> for YarnValue {
+    fn from(value : f32) -> Self {
+        YarnValue::NUMBER(value as f64)
+    }
+}
+
+impl From<bool> for YarnValue {
+    fn from(value : bool) -> Self {
+        YarnValue::BOOL(value)
+    }
+}
+
+impl TryFrom<YarnValue> for String {
+    type Error = YarnError;
+
+    fn try_from(value : YarnValue) -> YarnResult<Self> {
+        match value {
+            YarnValue::STRING(value) => Ok(value),
+            _ => Err(YarnError::new_type_mismatch_error(0, 0, "STRING", value.get_type_as_string())),
+        }
+    }
+}
+
+impl TryFrom<YarnValue> for f64 {
+    type Error = YarnError;
+
+    fn try_from(value : YarnValue) -> YarnResult<Self> {
+        f64::from_argument(&value, 0, 0)
+    }
+}
+
+impl TryFrom<YarnValue> for f32 {
+    type Error = YarnError;
+
+    fn try_from(value : YarnValue) -> YarnResult<Self> {
+        f32::from_argument(&value, 0, 0)
+    }
+}
+
+impl TryFrom<YarnValue> for bool {
+    type Error = YarnError;
+
+    fn try_from(value : YarnValue) -> YarnResult<Self> {
+        bool::from_argument(&value, 0, 0)
+    }
+}
+
+//Formats values the way Yarn Spinner does whatever the locale: whole numbers have no decimal point, -0 is "0" and
+//the decimal separator is always '.'. The alternate form, {:#}, writes True and False capitalised like the C# runtime.
+impl Display for YarnValue {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YarnValue::STRING(value) => f.pad(value),
+            YarnValue::NUMBER(value) if *value == 0.0 => f.pad("0"),
+            YarnValue::NUMBER(value) => f.pad(value.to_string().as_str()),
+            YarnValue::INT(value) => f.pad(value.to_string().as_str()),
+            YarnValue::DECIMAL(value) => value.fmt(f),
+            YarnValue::ENUM(name, case) => f.pad(format!("{}.{}", name, case).as_str()),
+            YarnValue::BOOL(true) if f.alternate() => f.pad("True"),
+            YarnValue::BOOL(false) if f.alternate() => f.pad("False"),
+            YarnValue::BOOL(value) => f.pad(value.to_string().as_str()),
+        }
+    }
+}
+//Converts the arguments yarn functions are called with into the rust types the function declares.
+pub trait YarnArgument<'a> : Sized {
+    fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self>;
+
+    fn value_type() -> YarnValueType;
+}
+
+//Converts what a yarn function returns back into a value for the dialogue.
+pub trait YarnReturn {
+    fn into_return(self) -> YarnResult<Option<YarnValue>>;
+
+    fn return_type() -> YarnValueType;
+}
+
+impl <'a> YarnArgument<'a> for &'a YarnValue {
+    fn from_argument(value : &'a YarnValue, _line : usize, _col : usize) -> YarnResult<Self> {
+        Ok(value)
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::ANY
+    }
+}
+
+impl <'a> YarnArgument<'a> for YarnValue {
+    fn from_argument(value : &'a YarnValue, _line : usize, _col : usize) -> YarnResult<Self> {
+        Ok(value.clone())
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::ANY
+    }
+}
+
+impl <'a> YarnArgument<'a> for &'a str {
+    fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+        match value {
+            YarnValue::STRING(value) => Ok(value.as_str()),
+            _ => Err(YarnError::new_type_mismatch_error(line, col, "STRING", value.get_type_as_string())),
+        }
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::STRING
+    }
+}
+
+impl <'a> YarnArgument<'a> for String {
+    fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+        <&str>::from_argument(value, line, col).map(|value| value.to_string())
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::STRING
+    }
+}
+
+impl <'a> YarnArgument<'a> for bool {
+    fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+        match value {
+            YarnValue::BOOL(value) => Ok(*value),
+            _ => Err(YarnError::new_type_mismatch_error(line, col, "BOOL", value.get_type_as_string())),
+        }
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::BOOL
+    }
+}
+
+impl <'a> YarnArgument<'a> for f64 {
+    fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+        match value.as_f64() {
+            Some(number) => Ok(number),
+            None => Err(YarnError::new_type_mismatch_error(line, col, "NUMBER", value.get_type_as_string())),
+        }
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::NUMBER
+    }
+}
+
+impl <'a> YarnArgument<'a> for f32 {
+    fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+        f64::from_argument(value, line, col).map(|value| value as f32)
+    }
+
+    fn value_type() -> YarnValueType {
+        YarnValueType::NUMBER
+    }
+}
+
+//A rust enum the dialogue can use as the enum with the same name and cases. Usually made with #[derive(YarnEnum)],
+//which also converts it to and from YarnValue so it can be passed to and returned from yarn functions.
+pub trait YarnEnum : Sized {
+    fn enum_name() -> &'static str;
+
+    fn cases() -> &'static [&'static str];
+
+    fn from_case(case : &str) -> Option<Self>;
+
+    fn case(&self) -> &'static str;
+
+    //The <<enum>> block that declares the same enum in a dialogue.
+    fn declaration() -> String {
+        let cases = Self::cases().iter().map(|case| format!("    <<case {}>>\n", case)).collect::<String>();
+        format!("<<enum {}>>\n{}<<endenum>>\n", Self::enum_name(), cases)
+    }
+
+    fn to_value(&self) -> YarnValue {
+        YarnValue::ENUM(Self::enum_name().to_string(), self.case().to_string())
+    }
+
+    fn from_value(value : &YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+        match value {
+            YarnValue::ENUM(name, case) if name == Self::enum_name() => match Self::from_case(case) {
+                Some(value) => Ok(value),
+                None => Err(YarnError::new_enum_error(line, col, format!("'{}' is not a case of the enum '{}'.", case, name).as_str())),
+            },
+            _ => Err(YarnError::new_type_mismatch_error(line, col, Self::enum_name(), value.get_type_as_string())),
+        }
+    }
+}
+
+impl YarnReturn for () {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(None)
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::ANY
+    }
+}
+
+impl YarnReturn for YarnValue {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(Some(self))
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::ANY
+    }
+}
+
+impl YarnReturn for String {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(Some(YarnValue::STRING(self)))
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::STRING
+    }
+}
+
+impl YarnReturn for &str {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(Some(YarnValue::STRING(self.to_string())))
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::STRING
+    }
+}
+
+impl YarnReturn for bool {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(Some(YarnValue::BOOL(self)))
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::BOOL
+    }
+}
+
+impl YarnReturn for f64 {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(Some(YarnValue::NUMBER(self)))
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::NUMBER
+    }
+}
+
+impl YarnReturn for f32 {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        Ok(Some(YarnValue::NUMBER(self as f64)))
+    }
+
+    fn return_type() -> YarnValueType {
+        YarnValueType::NUMBER
+    }
+}
+
+impl <T : YarnReturn> YarnReturn for Option<T> {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        match self {
+            Some(value) => value.into_return(),
+            None => Ok(None),
+        }
+    }
+
+    fn return_type() -> YarnValueType {
+        T::return_type()
+    }
+}
+
+impl <T : YarnReturn> YarnReturn for YarnResult<T> {
+    fn into_return(self) -> YarnResult<Option<YarnValue>> {
+        self?.into_return()
+    }
+
+    fn return_type() -> YarnValueType {
+        T::return_type()
+    }
+}
+
+//Integers only accept whole numbers that fit in the type.
+macro_rules! integer_conversions {
+    ($convert:expr; $($integer:ty),*) => {
+        $(
+            impl <'a> YarnArgument<'a> for $integer {
+                fn from_argument(value : &'a YarnValue, line : usize, col : usize) -> YarnResult<Self> {
+                    if let YarnValue::INT(number) = value {
+                        return <$integer>::try_from(*number)
+                            .map_err(|_| YarnError::new_type_mismatch_error(line, col, stringify!($integer), number.to_string().as_str()));
+                    }
+
+                    let number = f64::from_argument(value, line, col)?;
+                    if number.fract() == 0.0 && number >= <$integer>::MIN as f64 && number < <$integer>::MAX as f64 + 1.0 {
+                        Ok(number as $integer)
+                    } else {
+                        Err(YarnError::new_type_mismatch_error(line, col, stringify!($integer), number.to_string().as_str()))
+                    }
+                }
+
+                fn value_type() -> YarnValueType {
+                    YarnValueType::NUMBER
+                }
+            }
+
+            impl From<$integer> for YarnValue {
+                fn from(value : $integer) -> Self {
+                    ($convert)(value)
+                }
+            }
+
+            impl TryFrom<YarnValue> for $integer {
+                type Error = YarnError;
+
+                fn try_from(value : YarnValue) -> YarnResult<Self> {
+                    <$integer>::from_argument(&value, 0, 0)
+                }
+            }
+
+            impl YarnReturn for $integer {
+                fn into_return(self) -> YarnResult<Option<YarnValue>> {
+                    Ok(Some(YarnValue::from(self)))
+                }
+
+                fn return_type() -> YarnValueType {
+                    YarnValueType::NUMBER
+                }
+            }
+        )*
+    };
+}
+
+//An f64 can not hold every 64 bit integer, so those stay exact as an INT whenever they fit in one.
+integer_conversions!(|value| YarnValue::NUMBER(value as f64); i8, i16, i32, u8, u16, u32);
+integer_conversions!(|value| i64::try_from(value as i128).map(YarnValue::INT).unwrap_or(YarnValue::NUMBER(value as f64)); i64, isize, u64, usize);
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::program::YarnOperator;
+
+    use super::*;
+
+    #[test]
+    fn test_value_display() {
+        assert_eq!(YarnValue::NUMBER(3.0).to_string(), "3");
+        assert_eq!(YarnValue::NUMBER(-0.0).to_string(), "0");
+        assert_eq!(YarnValue::NUMBER(0.25).to_string(), "0.25");
+        assert_eq!(YarnValue::NUMBER(-1500000.5).to_string(), "-1500000.5");
+        assert_eq!(YarnValue::STRING("Mae".to_string()).to_string(), "Mae");
+        assert_eq!(YarnValue::BOOL(true).to_string(), "true");
+        assert_eq!(format!("{:#}", YarnValue::BOOL(false)), "False");
+        assert_eq!(format!("{:>4}", YarnValue::NUMBER(7.0)), "   7");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_value_serde() {
+        let values = [
+            (YarnValue::STRING("Mae".to_string()), r#"{"STRING":"Mae"}"#),
+            (YarnValue::NUMBER(2.5), r#"{"NUMBER":2.5}"#),
+            (YarnValue::BOOL(true), r#"{"BOOL":true}"#),
+            (YarnValue::INT(-3), r#"{"INT":-3}"#),
+            (YarnValue::DECIMAL(YarnDecimal::from_units(2_500_000)), r#"{"DECIMAL":{"units":2500000}}"#),
+            (YarnValue::ENUM("Mood".to_string(), "Happy".to_string()), r#"{"ENUM":["Mood","Happy"]}"#),
+        ];
+        for (value, golden) in values {
+            assert_eq!(serde_json::to_string(&value).unwrap(), golden);
+            let restored = serde_json::from_str::<YarnValue>(golden).unwrap();
+            assert_eq!(format!("{:?}", restored), format!("{:?}", value));
+        }
+    }
+
+    #[test]
+    fn test_value_conversions() {
+        assert_eq!(YarnValue::from(2.5), YarnValue::NUMBER(2.5));
+        assert_eq!(YarnValue::from(-3), YarnValue::NUMBER(-3.0));
+        assert_eq!(YarnValue::from(true), YarnValue::BOOL(true));
+        assert_eq!(YarnValue::from("true".to_string()), YarnValue::STRING("true".to_string()));
+
+        assert_eq!(f64::try_from(YarnValue::NUMBER(2.5)).unwrap(), 2.5);
+        assert_eq!(i32::try_from(YarnValue::NUMBER(-3.0)).unwrap(), -3);
+        assert!(bool::try_from(YarnValue::BOOL(true)).unwrap());
+        assert_eq!(String::try_from(YarnValue::STRING("Mae".to_string())).unwrap(), "Mae");
+
+        assert_eq!(i32::try_from(YarnValue::NUMBER(2.5)).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(u8::try_from(YarnValue::NUMBER(-1.0)).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(String::try_from(YarnValue::NUMBER(1.0)).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(bool::try_from(YarnValue::STRING("true".to_string())).unwrap_err().error_name(), "Type Mismatch Error");
+
+        assert!(matches!(YarnValue::from(9007199254740993i64), YarnValue::INT(9007199254740993)));
+        assert!(matches!(YarnValue::from(u64::MAX), YarnValue::NUMBER(_)));
+        assert!(matches!(YarnValue::from(7usize), YarnValue::INT(7)));
+        assert!(matches!(42u64.into_return().unwrap(), Some(YarnValue::INT(42))));
+        assert_eq!(u8::try_from(YarnValue::NUMBER(255.0)).unwrap(), 255);
+        assert_eq!(u8::try_from(YarnValue::NUMBER(256.0)).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(i64::try_from(YarnValue::NUMBER(9223372036854775807.0)).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(u64::try_from(YarnValue::NUMBER(18446744073709551615.0)).unwrap_err().error_name(), "Type Mismatch Error");
+        assert_eq!(i64::try_from(YarnValue::NUMBER(-9223372036854775808.0)).unwrap(), i64::MIN);
+    }
+
+    #[test]
+    fn test_number_semantics() {
+        assert_ne!(YarnValue::NUMBER(1.0), YarnValue::NUMBER(5.0));
+        assert_eq!(YarnValue::NUMBER(0.1 + 0.2), YarnValue::NUMBER(0.3));
+        assert_eq!(YarnValue::NUMBER(0.1 + 0.2).is_less_than_eq(&YarnValue::NUMBER(0.3)), Some(YarnValue::BOOL(true)));
+        assert_eq!(YarnValue::NUMBER(1e12 + 1e-4).is_equal(&YarnValue::NUMBER(1e12)), Some(YarnValue::BOOL(true)));
+        assert_eq!(YarnValue::NUMBER(0.1 + 0.2).is_equal_within(&YarnValue::NUMBER(0.3), 0.0), Some(YarnValue::BOOL(false)));
+
+        let divide = |lhs : f64, rhs : f64| YarnOperator::DIV.apply(&[YarnValue::NUMBER(lhs), YarnValue::NUMBER(rhs)], DEFAULT_TOLERANCE, 2, 3);
+        let error = divide(1.0, 0.0).unwrap_err();
+        assert_eq!(error.error_name(), "Arithmetic Error");
+        assert_eq!(error.gen_error_message(), "Arithmetic Error at (2, 3) : Division by zero.");
+        assert_eq!(divide(1e300, 1e-300).unwrap_err().error_name(), "Arithmetic Error");
+        assert_eq!(divide(1.0, 4.0).unwrap(), YarnValue::NUMBER(0.25));
+        assert_eq!(YarnOperator::ADD.apply(&[YarnValue::NUMBER(f64::INFINITY), YarnValue::NUMBER(f64::NEG_INFINITY)], DEFAULT_TOLERANCE, 0, 0).unwrap_err().error_name(), "Arithmetic Error");
+        assert_eq!(YarnOperator::DIV.apply(&[YarnValue::STRING("a".to_string()), YarnValue::NUMBER(0.0)], DEFAULT_TOLERANCE, 0, 0).unwrap_err().error_name(), "Invalid Opperation Error");
+    }
+
+    #[test]
+    fn test_exact_numbers() {
+        let cents = |value : f64| YarnValue::DECIMAL(YarnDecimal::from_f64(value).unwrap());
+        let apply = |operator : YarnOperator, lhs : YarnValue, rhs : YarnValue| operator.apply(&[lhs, rhs], 0.0, 0, 0);
+
+        assert!(matches!(apply(YarnOperator::ADD, cents(0.1), YarnValue::NUMBER(0.2)).unwrap(), YarnValue::DECIMAL(value) if value == YarnDecimal::from_f64(0.3).unwrap()));
+        assert_eq!(apply(YarnOperator::EQUAL_TOO, cents(0.1).add(&cents(0.2)).unwrap(), cents(0.3)).unwrap(), YarnValue::BOOL(true));
+        assert!(matches!(apply(YarnOperator::MUL, YarnValue::INT(6), YarnValue::INT(7)).unwrap(), YarnValue::INT(42)));
+        assert!(matches!(apply(YarnOperator::DIV, YarnValue::INT(6), YarnValue::INT(3)).unwrap(), YarnValue::INT(2)));
+        assert!(matches!(apply(YarnOperator::DIV, YarnValue::INT(1), YarnValue::INT(4)).unwrap(), YarnValue::DECIMAL(value) if value.to_f64() == 0.25));
+        assert!(matches!(apply(YarnOperator::SUB, YarnValue::INT(1), cents(0.5)).unwrap(), YarnValue::DECIMAL(_)));
+        assert!(matches!(apply(YarnOperator::ADD, YarnValue::INT(1), YarnValue::NUMBER(1e300)).unwrap(), YarnValue::NUMBER(_)));
+        assert_eq!(apply(YarnOperator::LESS_THAN, YarnValue::INT(2), YarnValue::NUMBER(2.5)).unwrap(), YarnValue::BOOL(true));
+        assert_eq!(apply(YarnOperator::ADD, YarnValue::STRING("$".to_string()), cents(1.5)).unwrap(), YarnValue::STRING("$1.5".to_string()));
+
+        assert_eq!(apply(YarnOperator::DIV, YarnValue::INT(1), YarnValue::INT(0)).unwrap_err().error_message(), "Division by zero.");
+        assert_eq!(apply(YarnOperator::MOD, YarnValue::INT(-7), YarnValue::INT(3)).unwrap(), YarnValue::INT(-1));
+        assert_eq!(apply(YarnOperator::MOD, YarnValue::NUMBER(7.5), YarnValue::NUMBER(2.0)).unwrap(), YarnValue::NUMBER(1.5));
+        assert_eq!(apply(YarnOperator::MOD, YarnValue::INT(1), YarnValue::INT(0)).unwrap_err().error_message(), "Division by zero.");
+        assert_eq!(apply(YarnOperator::XOR, YarnValue::BOOL(true), YarnValue::BOOL(true)).unwrap(), YarnValue::BOOL(false));
+        assert_eq!(apply(YarnOperator::AND, YarnValue::BOOL(true), YarnValue::NUMBER(1.0)).unwrap_err().error_name(), "Invalid Opperation Error");
+        assert_eq!(apply(YarnOperator::ADD, YarnValue::INT(i64::MAX), YarnValue::INT(1)).unwrap_err().error_name(), "Arithmetic Error");
+        assert_eq!(YarnOperator::NEGATIVE.apply(&[YarnValue::INT(i64::MIN)], 0.0, 0, 0).unwrap_err().error_name(), "Arithmetic Error");
+
+        assert!(matches!(YarnValue::NUMBER(3.0).stored_as(&YarnValue::INT(0), 0, 0).unwrap(), YarnValue::INT(3)));
+        assert!(matches!(YarnValue::INT(3).stored_as(&YarnValue::NUMBER(0.0), 0, 0).unwrap(), YarnValue::NUMBER(_)));
+        assert_eq!(cents(2.5).stored_as(&YarnValue::INT(0), 4, 2).unwrap_err().gen_error_message(), "Arithmetic Error at (4, 2) : 2.5 is not a whole number.");
+        assert_eq!(i32::try_from(YarnValue::INT(-3)).unwrap(), -3);
+        assert_eq!(f64::try_from(cents(0.25)).unwrap(), 0.25);
+
+        assert_eq!(YarnValue::NUMBER(3.0).partial_cmp(&YarnValue::INT(3)), Some(Ordering::Equal));
+        assert_eq!(YarnValue::NUMBER(1.0).partial_cmp(&YarnValue::NUMBER(1.0 + 1e-12)), Some(Ordering::Equal));
+        assert!(YarnValue::INT(2) < cents(2.5));
+        assert_eq!(YarnValue::STRING("3".to_string()).partial_cmp(&YarnValue::INT(3)), None);
+    }
+
+    #[test]
+    fn test_enum_values() {
+        let happy = YarnValue::parse_enum("Mood.Happy").unwrap();
+        assert_eq!(happy.to_string(), "Mood.Happy");
+        assert_eq!(happy.get_type(), YarnValueType::ENUM("Mood".to_string()));
+        assert_eq!(YarnOperator::EQUAL_TOO.apply(&[happy.clone(), YarnValue::parse_enum("Mood.Sad").unwrap()], 0.0, 0, 0).unwrap(), YarnValue::BOOL(false));
+        assert!(YarnOperator::EQUAL_TOO.apply(&[happy.clone(), YarnValue::parse_enum("Weather.Happy").unwrap()], 0.0, 0, 0).is_err());
+        assert!(YarnOperator::ADD.apply(&[happy, YarnValue::NUMBER(1.0)], 0.0, 0, 0).is_err());
+        assert!(YarnValue::parse_enum("Happy").is_none());
+        assert!(YarnValue::parse_enum("Mood.Very Happy").is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn test_equality_properties(lhs in -1e15..1e15f64, rhs in -1e15..1e15f64) {
+            let (lhs_value, rhs_value) = (YarnValue::NUMBER(lhs), YarnValue::NUMBER(rhs));
+            prop_assert_eq!(&lhs_value, &lhs_value);
+            prop_assert_eq!(lhs_value == rhs_value, rhs_value == lhs_value);
+            prop_assert_eq!(lhs_value.is_equal(&rhs_value), Some(YarnValue::BOOL(lhs_value == rhs_value)));
+            prop_assert_eq!(lhs_value.is_not_equal(&rhs_value), Some(YarnValue::BOOL(lhs_value != rhs_value)));
+
+            let close = YarnValue::NUMBER(lhs + lhs * 1e-12);
+            let whole = YarnValue::INT(lhs as i64);
+            for (first, second) in [(&lhs_value, &rhs_value), (&lhs_value, &close), (&YarnValue::NUMBER(lhs.trunc()), &whole)] {
+                prop_assert_eq!(first == second, first.partial_cmp(second) == Some(Ordering::Equal));
+                prop_assert_eq!(first.partial_cmp(second), second.partial_cmp(first).map(Ordering::reverse));
+            }
+        }
+
+        #[test]
+        fn test_whole_numbers_compare_exactly(lhs in -1_000_000_000i64..1_000_000_000, rhs in -1_000_000_000i64..1_000_000_000) {
+            prop_assert_eq!(YarnValue::NUMBER(lhs as f64) == YarnValue::NUMBER(rhs as f64), lhs == rhs);
+            prop_assert_eq!(YarnValue::NUMBER(lhs as f64).is_less_than(&YarnValue::NUMBER(rhs as f64)), Some(YarnValue::BOOL(lhs < rhs)));
+        }
+
+        #[test]
+        fn test_comparisons_agree(lhs in -1e6..1e6f64, rhs in -1e6..1e6f64, tolerance in 0.0..1e-3f64) {
+            let operands = [YarnValue::NUMBER(lhs), YarnValue::NUMBER(rhs)];
+            let check = |operator : YarnOperator| operator.apply(&operands, tolerance, 0, 0).unwrap() == YarnValue::BOOL(true);
+
+            let (less, equal, greater) = (check(YarnOperator::LESS_THAN), check(YarnOperator::EQUAL_TOO), check(YarnOperator::GREATER_THAN));
+            prop_assert_eq!([less, equal, greater].iter().filter(|result| **result).count(), 1);
+            prop_assert_eq!(check(YarnOperator::LESS_THAN_EQ), less || equal);
+            prop_assert_eq!(check(YarnOperator::GREATER_THAN_EQ), greater || equal);
+            prop_assert_eq!(check(YarnOperator::NOT_EQUAL_TOO), !equal);
+        }
+
+        #[test]
+        fn test_arithmetic_stays_finite(lhs in proptest::num::f64::NORMAL, rhs in proptest::num::f64::NORMAL | proptest::num::f64::ZERO) {
+            for operator in [YarnOperator::ADD, YarnOperator::SUB, YarnOperator::MUL, YarnOperator::DIV] {
+                match operator.apply(&[YarnValue::NUMBER(lhs), YarnValue::NUMBER(rhs)], DEFAULT_TOLERANCE, 0, 0) {
+                    Ok(YarnValue::NUMBER(result)) => prop_assert!(result.is_finite()),
+                    Ok(_) => prop_assert!(false),
+                    Err(error) => prop_assert_eq!(error.error_name(), "Arithmetic Error"),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::{random::YarnRandom, value::{YarnValue, DEFAULT_TOLERANCE}, error::{YarnError, YarnResult}, program::{YarnProgram, YarnInstruction, YarnLine}, parcer::{YarnFunctionMap, YarnFunction, YarnFunctionContext}, storage::YarnVariableStorage, command::{YarnCommand, YarnCommandMap, YarnCommandStatus, builtin_command}};
+
+mod snapshot;
+pub use self::snapshot::YarnSnapshot;
+
+//How many detours deep the dialogue can go, so a node that detours back into itself ends with an error.
+pub const MAX_DETOUR_DEPTH : usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnOption {
+    line : YarnLine,
+    destination : usize,
+    available : bool
+}
+
+impl YarnOption {
+    pub fn line(&self) -> &YarnLine {
+        &self.line
+    }
+
+    pub fn available(&self) -> bool {
+        self.available
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum YarnDialogueEvent {
+    LINE(YarnLine),
+    OPTIONS(Vec<YarnOption>), // Wait for select_option before continuing
+    COMMAND(YarnCommand), // A command no handler was registered for
+    WAITING, // A command is still running, call resume or update before continuing
+    NODE_COMPLETE(String), // Title of the node that finished
+    DIALOGUE_COMPLETE
+}
+
+//Looks up every function the program calls once, ahead of running it.
+pub fn link_functions(program : &YarnProgram, functions : &YarnFunctionMap) -> Vec<Option<YarnFunction>> {
+    program.symbols().functions().names().iter().map(|name| functions.get(name).map(|function| function.function())).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum YarnExecutionState {
+    STOPPED,
+    RUNNING,
+    WAITING_FOR_OPTION,
+    WAITING_FOR_COMMAND
+}
+
+//How many times a node has been started and how many of those got to the end. Nodes with a `tracking: never`
+//header are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnNodeVisits {
+    entered : usize,
+    completed : usize
+}
+
+impl YarnNodeVisits {
+    pub fn new(entered : usize, completed : usize) -> YarnNodeVisits {
+        YarnNodeVisits { entered, completed }
+    }
+
+    pub fn entered(&self) -> usize {
+        self.entered
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct YarnVirtualMachine {
+    node : Option<String>,
+    program_counter : usize,
+    stack : Vec<YarnValue>,
+    options : Vec<YarnOption>,
+    state : YarnExecutionState,
+    wait_time : Option<f64>, // Seconds left when a command asked to wait for a time
+    random : RefCell<YarnRandom>, // Lent to the functions the dialogue calls
+    visits : HashMap<String, YarnNodeVisits>,
+    detours : Vec<(String, usize)>, // Nodes that detoured to the current one and where to carry on in them
+    tolerance : f64 // How far apart two numbers can be and still be equal
+}
+
+impl Default for YarnVirtualMachine {
+    fn default() -> Self {
+        YarnVirtualMachine::new()
+    }
+}
+
+impl YarnVirtualMachine {
+    pub fn new() -> YarnVirtualMachine {
+        YarnVirtualMachine {
+            node: None,
+            program_counter: 0,
+            stack: Vec::new(),
+            options: Vec::new(),
+            state: YarnExecutionState::STOPPED,
+            wait_time: None,
+            random: RefCell::new(YarnRandom::from_entropy()),
+            visits: HashMap::new(),
+            detours: Vec::new(),
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    pub fn state(&self) -> YarnExecutionState {
+        self.state
+    }
+
+    pub fn current_node(&self) -> Option<&str> {
+        self.node.as_deref()
+    }
+
+    pub fn random(&self) -> YarnRandom {
+        *self.random.borrow()
+    }
+
+    pub fn set_random(&mut self, random : YarnRandom) {
+        self.random = RefCell::new(random);
+    }
+
+    pub fn visits(&self) -> &HashMap<String, YarnNodeVisits> {
+        &self.visits
+    }
+
+    pub fn set_visits(&mut self, visits : HashMap<String, YarnNodeVisits>) {
+        self.visits = visits;
+    }
+
+    pub fn detours(&self) -> &[(String, usize)] {
+        &self.detours
+    }
+
+    pub fn tolerance(&self) -> f64 {
+        self.tolerance
+    }
+
+    pub fn set_tolerance(&mut self, tolerance : f64) {
+        self.tolerance = tolerance;
+    }
+
+    pub fn visit_count(&self, node : &str) -> usize {
+        self.visits.get(node).map(|visits| visits.completed).unwrap_or(0)
+    }
+
+    fn track_visit(&mut self, program : &YarnProgram, title : &str, completed : bool) {
+        let tracked = program.node(title).map(|node| node.headers().get("tracking").map(|tracking| tracking.as_str()) != Some("never")).unwrap_or(false);
+        if tracked {
+            let visits = self.visits.entry(title.to_string()).or_default();
+            if completed {
+                visits.completed += 1;
+            } else {
+                visits.entered += 1;
+            }
+        }
+    }
+
+    //Starts the dialogue over from the node, forgetting any detours it was in.
+    pub fn set_node(&mut self, program : &YarnProgram, title : &str) -> YarnResult<()> {
+        self.enter_node(program, title)?;
+        self.detours.clear();
+        Ok(())
+    }
+
+    fn enter_node(&mut self, program : &YarnProgram, title : &str) -> YarnResult<()> {
+        if program.node(title).is_none() {
+            return Err(YarnError::new_node_not_found_error(0, 0, title));
+        }
+
+        self.track_visit(program, title, false);
+        self.node = Some(title.to_string());
+        self.program_counter = 0;
+        self.stack.clear();
+        self.options.clear();
+        self.state = YarnExecutionState::RUNNING;
+        self.wait_time = None;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.node = None;
+        self.detours.clear();
+        self.stack.clear();
+        self.options.clear();
+        self.state = YarnExecutionState::STOPPED;
+        self.wait_time = None;
+    }
+
+    //Lets the dialogue carry on after a command that was still running.
+    pub fn resume(&mut self) -> YarnResult<()> {
+        if self.state != YarnExecutionState::WAITING_FOR_COMMAND {
+            return Err(YarnError::new_not_waiting_error());
+        }
+
+        self.state = YarnExecutionState::RUNNING;
+        self.wait_time = None;
+        Ok(())
+    }
+
+    //Counts down a timed wait and resumes once it has run out.
+    pub fn update(&mut self, delta : f64) {
+        if let Some(wait_time) = self.wait_time {
+            let wait_time = wait_time - delta;
+            if wait_time <= 0.0 {
+                self.state = YarnExecutionState::RUNNING;
+                self.wait_time = None;
+            } else {
+                self.wait_time = Some(wait_time);
+            }
+        }
+    }
+
+    pub fn select_option(&mut self, index : usize) -> YarnResult<()> {
+        if self.state != YarnExecutionState::WAITING_FOR_OPTION {
+            return Err(YarnError::new_invalid_option_error(index));
+        }
+
+        match self.options.get(index) {
+            Some(option) if option.available => {
+                self.program_counter = option.destination;
+                self.options.clear();
+                self.state = YarnExecutionState::RUNNING;
+                Ok(())
+            },
+            _ => Err(YarnError::new_invalid_option_error(index))
+        }
+    }
+
+    //Runs instructions until something happens that the game has to respond to. Commands with a handler are run
+    //here, the rest are given to the game as an event.
+    pub fn run(&mut self, program : &YarnProgram, variables : &mut dyn YarnVariableStorage, functions : &[Option<YarnFunction>], commands : &YarnCommandMap) -> YarnResult<YarnDialogueEvent> {
+        match self.state {
+            YarnExecutionState::STOPPED => return Ok(YarnDialogueEvent::DIALOGUE_COMPLETE),
+            YarnExecutionState::WAITING_FOR_OPTION => return Ok(YarnDialogueEvent::OPTIONS(self.options.clone())),
+            YarnExecutionState::WAITING_FOR_COMMAND => return Ok(YarnDialogueEvent::WAITING),
+            YarnExecutionState::RUNNING => {}
+        }
+
+        let mut title = match &self.node {
+            Some(title) => title.clone(),
+            None => return Ok(YarnDialogueEvent::DIALOGUE_COMPLETE),
+        };
+
+        let mut node = match program.node(title.as_str()) {
+            Some(node) => node,
+            None => return Err(YarnError::new_node_not_found_error(0, 0, title.as_str())),
+        };
+
+        loop {
+            let index = self.program_counter;
+            let (line, col) = node.position(index);
+            let instruction = match node.instruction(index) {
+                Some(instruction) => instruction,
+                None => &YarnInstruction::RETURN,
+            };
+            self.program_counter += 1;
+
+            match instruction {
+                YarnInstruction::PUSH(value) => self.stack.push(value.clone()),
+                YarnInstruction::POP => {
+                    self.pop(line, col)?;
+                },
+                YarnInstruction::LOAD(slot) => {
+                    match variables.get(program.symbols().variables().name(*slot)) {
+                        Some(value) => self.stack.push(value),
+                        None => return Err(YarnError::new_variable_not_declared_error(line, col)),
+                    }
+                },
+                YarnInstruction::STORE(slot) => {
+                    let name = program.symbols().variables().name(*slot);
+                    let value = self.pop(line, col)?;
+                    let value = match variables.get(name) {
+                        Some(current) => value.stored_as(&current, line, col)?,
+                        None => value,
+                    };
+                    variables.set(name, value);
+                },
+                YarnInstruction::CALL(slot, argument_count) => {
+                    let function = match functions.get(*slot) {
+                        Some(Some(function)) => function,
+                        _ => return Err(YarnError::new_undefined_function_error(line, col)),
+                    };
+
+                    let arguments = self.pop_many(*argument_count, line, col)?;
+                    let context = YarnFunctionContext::new(variables, Some(title.as_str()), line, col).with_random(&self.random).with_visits(&self.visits);
+                    if let Some(value) = function(arguments, &context)? {
+                        self.stack.push(value);
+                    }
+                },
+                YarnInstruction::OPERATOR(operator) => {
+                    let operands = self.pop_many(operator.operand_count(), line, col)?;
+                    self.stack.push(operator.apply(&operands, self.tolerance, line, col)?);
+                },
+                YarnInstruction::JUMP_TO(destination) => self.program_counter = *destination,
+                YarnInstruction::JUMP_IF_FALSE(destination) => {
+                    if !self.pop_bool(line, col)? {
+                        self.program_counter = *destination;
+                    }
+                },
+                YarnInstruction::RUN_LINE(id) => {
+                    return Ok(YarnDialogueEvent::LINE(self.line(program, id)?));
+                },
+                YarnInstruction::RUN_COMMAND(text, expression_count) => {
+                    let values = self.pop_many(*expression_count, line, col)?;
+                    let command = YarnCommand::interpolated(text, &values);
+
+                    let context = YarnFunctionContext::new(variables, Some(title.as_str()), line, col).with_random(&self.random).with_visits(&self.visits);
+                    let status = match (commands.get(command.name()), builtin_command(command.name())) {
+                        (Some(handler), _) => handler(command.arguments().clone(), &context)?,
+                        (None, Some(handler)) => handler(command.arguments().clone(), &context)?,
+                        (None, None) => return Ok(YarnDialogueEvent::COMMAND(command)),
+                    };
+
+                    match status {
+                        YarnCommandStatus::COMPLETE => {},
+                        YarnCommandStatus::WAIT(seconds) if !seconds.is_finite() => {
+                            return Err(YarnError::new_command_error(line, col, format!("A command can not wait for {} seconds.", seconds).as_str()));
+                        },
+                        YarnCommandStatus::WAIT(seconds) if seconds <= 0.0 => {},
+                        YarnCommandStatus::WAIT(seconds) => {
+                            self.state = YarnExecutionState::WAITING_FOR_COMMAND;
+                            self.wait_time = Some(seconds);
+                            return Ok(YarnDialogueEvent::WAITING);
+                        },
+                        YarnCommandStatus::PENDING => {
+                            self.state = YarnExecutionState::WAITING_FOR_COMMAND;
+                            return Ok(YarnDialogueEvent::WAITING);
+                        },
+                    }
+                },
+                YarnInstruction::ADD_OPTION(id, destination, has_condition) => {
+                    let available = if *has_condition { self.pop_bool(line, col)? } else { true };
+                    let line = self.line(program, id)?;
+                    self.options.push(YarnOption { line, destination: *destination, available });
+                },
+                YarnInstruction::SHOW_OPTIONS => {
+                    if !self.options.is_empty() {
+                        self.state = YarnExecutionState::WAITING_FOR_OPTION;
+                        return Ok(YarnDialogueEvent::OPTIONS(self.options.clone()));
+                    }
+                },
+                //A node that was jumped to from a detour returns to where the detour came from once it ends.
+                YarnInstruction::RUN_NODE(next) => {
+                    self.track_visit(program, title.as_str(), true);
+                    self.enter_node(program, next)?;
+                    return Ok(YarnDialogueEvent::NODE_COMPLETE(title));
+                },
+                YarnInstruction::DETOUR(next) => {
+                    if self.detours.len() >= MAX_DETOUR_DEPTH {
+                        return Err(YarnError::new_detour_depth_error(line, col, MAX_DETOUR_DEPTH));
+                    }
+                    let destination = match program.node(next) {
+                        Some(destination) => destination,
+                        None => return Err(YarnError::new_node_not_found_error(line, col, next)),
+                    };
+
+                    self.detours.push((title, self.program_counter));
+                    self.enter_node(program, next)?;
+                    title = next.clone();
+                    node = destination;
+                },
+                YarnInstruction::RETURN => {
+                    self.track_visit(program, title.as_str(), true);
+                    match self.detours.pop() {
+                        Some((caller, position)) => {
+                            self.node = Some(caller);
+                            self.program_counter = position;
+                        },
+                        None => self.stop(),
+                    }
+                    return Ok(YarnDialogueEvent::NODE_COMPLETE(title));
+                },
+                YarnInstruction::STOP => {
+                    self.track_visit(program, title.as_str(), true);
+                    self.stop();
+                    return Ok(YarnDialogueEvent::NODE_COMPLETE(title));
+                },
+            }
+        }
+    }
+
+    fn line(&self, program : &YarnProgram, id : &str) -> YarnResult<YarnLine> {
+        match program.line(id) {
+            Some(line) => Ok(line.clone()),
+            None => Err(YarnError::new_line_not_found_error(id)),
+        }
+    }
+
+    fn pop(&mut self, line : usize, col : usize) -> YarnResult<YarnValue> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(YarnError::new_null_function_arg_error(line, col)),
+        }
+    }
+
+    fn pop_many(&mut self, count : usize, line : usize, col : usize) -> YarnResult<Vec<YarnValue>> {
+        if self.stack.len() < count {
+            return Err(YarnError::new_null_function_arg_error(line, col));
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    fn pop_bool(&mut self, line : usize, col : usize) -> YarnResult<bool> {
+        match self.pop(line, col)? {
+            YarnValue::BOOL(value) => Ok(value),
+            value => Err(YarnError::new_type_mismatch_error(line, col, "BOOL", value.get_type_as_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{token::tokenize, parcer::{parse_nodes, default_function_map, YarnEnumMap}, program::compile_program, storage::YarnMemoryStorage};
+
+    use super::*;
+
+    #[test]
+    fn test_run_program() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "Mae: Hi!\n",
+            "<<set $gold to $gold + round(1.4)>>\n",
+            "<<shake {$gold} hard>>\n",
+            "<<if $gold >= 2>>\n",
+            "    Rich.\n",
+            "<<else>>\n",
+            "    Poor.\n",
+            "<<endif>>\n",
+            "-> Leave\n",
+            "    <<jump end>>\n",
+            "-> Pay <<if $gold > 5>>\n",
+            "-> Stay\n",
+            "    Fine.\n",
+            "===\n",
+            "title: end\n",
+            "---\n",
+            "Bye.\n",
+            "===\n",
+        );
+
+        let tokens = tokenize(source);
+        let program = compile_program(&parse_nodes(&tokens).unwrap(), &YarnEnumMap::new()).unwrap();
+        let functions = link_functions(&program, &default_function_map());
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("gold", YarnValue::NUMBER(1.0));
+        let commands = YarnCommandMap::new();
+
+        let mut vm = YarnVirtualMachine::new();
+        vm.set_node(&program, "start").unwrap();
+
+        match vm.run(&program, &mut variables, &functions, &commands).unwrap() {
+            YarnDialogueEvent::LINE(line) => assert_eq!((line.speaker(), line.text()), (Some("Mae"), "Hi!")),
+            _ => assert!(false),
+        }
+
+        match vm.run(&program, &mut variables, &functions, &commands).unwrap() {
+            YarnDialogueEvent::COMMAND(command) => assert_eq!((command.name(), command.arguments().clone()), ("shake", vec!["2".to_string(), "hard".to_string()])),
+            _ => assert!(false),
+        }
+
+        match vm.run(&program, &mut variables, &functions, &commands).unwrap() {
+            YarnDialogueEvent::LINE(line) => assert_eq!(line.text(), "Rich."),
+            _ => assert!(false),
+        }
+
+        match vm.run(&program, &mut variables, &functions, &commands).unwrap() {
+            YarnDialogueEvent::OPTIONS(options) => {
+                assert_eq!(options.len(), 3);
+                assert!(options[0].available());
+                assert!(!options[1].available());
+                assert_eq!(options[2].line().text(), "Stay");
+            },
+            _ => assert!(false),
+        }
+
+        assert!(vm.select_option(1).is_err());
+        vm.select_option(0).unwrap();
+
+        assert_eq!(vm.run(&program, &mut variables, &functions, &commands).unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(vm.current_node(), Some("end"));
+
+        match vm.run(&program, &mut variables, &functions, &commands).unwrap() {
+            YarnDialogueEvent::LINE(line) => assert_eq!(line.text(), "Bye."),
+            _ => assert!(false),
+        }
+
+        assert_eq!(vm.run(&program, &mut variables, &functions, &commands).unwrap(), YarnDialogueEvent::NODE_COMPLETE("end".to_string()));
+        assert_eq!(vm.run(&program, &mut variables, &functions, &commands).unwrap(), YarnDialogueEvent::DIALOGUE_COMPLETE);
+        assert_eq!(variables.get("gold"), Some(YarnValue::NUMBER(2.0)));
+        assert_eq!(variables.to_map().get("gold"), Some(&YarnValue::NUMBER(2.0)));
+    }
+
+    #[test]
+    fn test_run_reads_storage() {
+        let source = concat!(
+            "title: start\n",
+            "---\n",
+            "<<set $gold to $gold + $bonus>>\n",
+            "Paid.\n",
+            "<<set $gold to $gold * 2>>\n",
+            "===\n",
+        );
+
+        let program = compile_program(&parse_nodes(&tokenize(source)).unwrap(), &YarnEnumMap::new()).unwrap();
+        let functions = link_functions(&program, &default_function_map());
+        let mut variables = YarnMemoryStorage::new();
+        variables.set("gold", YarnValue::NUMBER(1.0));
+        variables.set("bonus", YarnValue::NUMBER(0.5));
+
+        let mut vm = YarnVirtualMachine::new();
+        vm.set_node(&program, "start").unwrap();
+        assert!(matches!(vm.run(&program, &mut variables, &functions, &YarnCommandMap::new()).unwrap(), YarnDialogueEvent::LINE(_)));
+        assert_eq!(variables.get("gold"), Some(YarnValue::NUMBER(1.5)));
+
+        variables.set("gold", YarnValue::NUMBER(10.0));
+        assert_eq!(vm.run(&program, &mut variables, &functions, &YarnCommandMap::new()).unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+        assert_eq!(variables.get("gold"), Some(YarnValue::NUMBER(20.0)));
+    }
+}
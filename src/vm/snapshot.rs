@@ -0,0 +1,427 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::{value::YarnValue, decimal::YarnDecimal, error::{YarnError, YarnResult}, program::YarnProgram, parcer::YarnVariableMap, random::YarnRandom, yarnc::{ProtoWriter, ProtoReader}};
+use super::{YarnVirtualMachine, YarnExecutionState, YarnNodeVisits, YarnOption, MAX_DETOUR_DEPTH};
+
+//Everything needed to carry on a dialogue later from exactly where it was left. Options are kept by their line id
+//and looked up again when restoring, so a save still loads after the string table has been translated.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnSnapshot {
+    program : u64, // Identity of the program it was taken from
+    node : Option<String>,
+    position : usize,
+    state : YarnExecutionState,
+    wait_time : Option<f64>,
+    stack : Vec<YarnValue>,
+    options : Vec<YarnSnapshotOption>,
+    detours : Vec<(String, usize)>, // Nodes to return to and where to carry on in them, the most recent last
+    variables : YarnVariableMap,
+    visits : HashMap<String, YarnNodeVisits>,
+    random : u64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct YarnSnapshotOption {
+    line : String,
+    destination : usize,
+    available : bool
+}
+
+impl YarnSnapshot {
+    pub fn program(&self) -> u64 {
+        self.program
+    }
+
+    pub fn node(&self) -> Option<&str> {
+        self.node.as_deref()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn state(&self) -> YarnExecutionState {
+        self.state
+    }
+
+    pub fn detours(&self) -> &[(String, usize)] {
+        &self.detours
+    }
+
+    pub fn variables(&self) -> &YarnVariableMap {
+        &self.variables
+    }
+
+    pub fn visits(&self) -> &HashMap<String, YarnNodeVisits> {
+        &self.visits
+    }
+
+    pub fn random_state(&self) -> u64 {
+        self.random
+    }
+}
+
+impl YarnVirtualMachine {
+    //The variables are passed in because the VM does not own them.
+    pub fn snapshot(&self, program : &YarnProgram, variables : YarnVariableMap) -> YarnSnapshot {
+        YarnSnapshot {
+            program: program.identity(),
+            node: self.node.clone(),
+            position: self.program_counter,
+            state: self.state,
+            wait_time: self.wait_time,
+            stack: self.stack.clone(),
+            options: self.options.iter().map(|option| YarnSnapshotOption {
+                line: option.line.id().to_string(),
+                destination: option.destination,
+                available: option.available,
+            }).collect(),
+            detours: self.detours.clone(),
+            variables,
+            visits: self.visits.clone(),
+            random: self.random.borrow().state(),
+        }
+    }
+
+    //Everything but the variables, which the caller puts back into its own storage.
+    //A save made while a node was running can only be carried on by the same program, since a different one would
+    //have different instructions at the saved positions.
+    pub fn restore(&mut self, program : &YarnProgram, snapshot : &YarnSnapshot) -> YarnResult<()> {
+        if snapshot.node.is_some() && snapshot.program != program.identity() {
+            return Err(YarnError::new_invalid_snapshot_error("It was saved while running a different version of the dialogue."));
+        }
+        if snapshot.detours.len() > MAX_DETOUR_DEPTH {
+            return Err(YarnError::new_invalid_snapshot_error("It has more detours than the dialogue can go into."));
+        }
+
+        for node in snapshot.node.iter().chain(snapshot.detours.iter().map(|(node, _)| node)) {
+            if program.node(node).is_none() {
+                return Err(YarnError::new_node_not_found_error(0, 0, node));
+            }
+        }
+
+        let mut options = Vec::new();
+        for option in snapshot.options.iter() {
+            options.push(YarnOption { line: self.line(program, &option.line)?, destination: option.destination, available: option.available });
+        }
+
+        self.node = snapshot.node.clone();
+        self.program_counter = snapshot.position;
+        self.stack = snapshot.stack.clone();
+        self.options = options;
+        self.detours = snapshot.detours.clone();
+        self.state = snapshot.state;
+        self.wait_time = snapshot.wait_time;
+        self.visits = snapshot.visits.clone();
+        self.random = RefCell::new(YarnRandom::new(snapshot.random));
+        Ok(())
+    }
+}
+
+//==================================================================================================================
+//                       JSON
+//==================================================================================================================
+
+//Written by serde, so tools can read saves with serde_json too. Values are tagged with their type, like {"NUMBER": 2.0}.
+//JSON can not hold NaN or infinity and serde_json would write them as null, which does not load again, so those are
+//refused here. The binary form keeps them as they are.
+#[cfg(feature = "serde")]
+impl YarnSnapshot {
+    pub fn to_json(&self) -> YarnResult<String> {
+        let is_finite = |value : &YarnValue| !matches!(value, YarnValue::NUMBER(number) if !number.is_finite());
+        if let Some((name, value)) = self.variables.iter().find(|(_, value)| !is_finite(value)) {
+            return Err(YarnError::new_snapshot_error(format!("The variable '${}' is {}, which can not be written as JSON.", name, value).as_str()));
+        }
+        if !self.stack.iter().all(is_finite) || self.wait_time.is_some_and(|wait_time| !wait_time.is_finite()) {
+            return Err(YarnError::new_snapshot_error("A number the dialogue is using is not finite, so it can not be written as JSON."));
+        }
+
+        serde_json::to_string(self).map_err(|error| YarnError::new_snapshot_error(error.to_string().as_str()))
+    }
+
+    pub fn from_json(json : &str) -> YarnResult<YarnSnapshot> {
+        serde_json::from_str(json).map_err(|error| YarnError::new_invalid_snapshot_error(error.to_string().as_str()))
+    }
+}
+
+//==================================================================================================================
+//                       Binary
+//==================================================================================================================
+
+//Uses the same protobuf wire format as .yarnc files.
+impl YarnSnapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut snapshot = ProtoWriter::new();
+
+        if let Some(node) = &self.node {
+            snapshot.string(1, node);
+        }
+        snapshot.uint(2, self.position as u64);
+        snapshot.uint(3, match self.state {
+            YarnExecutionState::STOPPED => 0,
+            YarnExecutionState::RUNNING => 1,
+            YarnExecutionState::WAITING_FOR_OPTION => 2,
+            YarnExecutionState::WAITING_FOR_COMMAND => 3,
+        });
+        if let Some(wait_time) = self.wait_time {
+            snapshot.double(4, wait_time);
+        }
+
+        for value in self.stack.iter() {
+            snapshot.message(5, encode_value(value));
+        }
+
+        for option in self.options.iter() {
+            let mut entry = ProtoWriter::new();
+            entry.string(1, &option.line);
+            entry.uint(2, option.destination as u64);
+            entry.bool(3, option.available);
+            snapshot.message(6, entry);
+        }
+
+        let mut names = self.variables.keys().collect::<Vec<&String>>();
+        names.sort();
+        for name in names {
+            let mut entry = ProtoWriter::new();
+            entry.string(1, name);
+            entry.message(2, encode_value(&self.variables[name]));
+            snapshot.message(7, entry);
+        }
+
+        let mut nodes = self.visits.keys().collect::<Vec<&String>>();
+        nodes.sort();
+        for node in nodes {
+            let mut entry = ProtoWriter::new();
+            entry.string(1, node);
+            entry.uint(2, self.visits[node].entered as u64);
+            entry.uint(3, self.visits[node].completed as u64);
+            snapshot.message(8, entry);
+        }
+
+        snapshot.uint(9, self.random);
+        snapshot.uint(11, self.program);
+
+        for (node, position) in self.detours.iter() {
+            let mut entry = ProtoWriter::new();
+            entry.string(1, node);
+            entry.uint(2, *position as u64);
+            snapshot.message(10, entry);
+        }
+        snapshot.bytes
+    }
+
+    pub fn from_bytes(bytes : &[u8]) -> YarnResult<YarnSnapshot> {
+        decode_snapshot(bytes).map_err(|error| YarnError::new_invalid_snapshot_error(error.error_message()))
+    }
+}
+
+fn encode_value(value : &YarnValue) -> ProtoWriter {
+    let mut writer = ProtoWriter::new();
+    match value {
+        YarnValue::STRING(value) => writer.string(1, value),
+        YarnValue::NUMBER(value) => writer.double(2, *value),
+        YarnValue::BOOL(value) => writer.bool(3, *value),
+        YarnValue::INT(value) => writer.uint(4, *value as u64),
+        YarnValue::DECIMAL(value) => writer.uint(5, value.units() as u64),
+        YarnValue::ENUM(..) => writer.string(6, value.to_string().as_str()),
+    }
+    writer
+}
+
+fn decode_value(bytes : &[u8]) -> YarnResult<YarnValue> {
+    let mut reader = ProtoReader::new(bytes);
+    match reader.next_field()? {
+        Some((1, value)) => Ok(YarnValue::STRING(value.as_string()?)),
+        Some((2, value)) => Ok(YarnValue::NUMBER(value.as_f64()?)),
+        Some((3, value)) => Ok(YarnValue::BOOL(value.as_u64()? != 0)),
+        Some((4, value)) => Ok(YarnValue::INT(value.as_u64()? as i64)),
+        Some((5, value)) => Ok(YarnValue::DECIMAL(YarnDecimal::from_units(value.as_u64()? as i64))),
+        Some((6, value)) => YarnValue::parse_enum(value.as_string()?.as_str()).ok_or_else(|| YarnError::new_invalid_snapshot_error("An enum value must be written as Name.Case.")),
+        _ => Err(YarnError::new_invalid_snapshot_error("A value has no type.")),
+    }
+}
+
+fn decode_snapshot(bytes : &[u8]) -> YarnResult<YarnSnapshot> {
+    let mut reader = ProtoReader::new(bytes);
+    let mut snapshot = YarnSnapshot {
+        program: 0,
+        node: None,
+        position: 0,
+        state: YarnExecutionState::STOPPED,
+        wait_time: None,
+        stack: Vec::new(),
+        options: Vec::new(),
+        detours: Vec::new(),
+        variables: YarnVariableMap::new(),
+        visits: HashMap::new(),
+        random: 0,
+    };
+
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => snapshot.node = Some(value.as_string()?),
+            2 => snapshot.position = value.as_u64()? as usize,
+            3 => snapshot.state = match value.as_u64()? {
+                0 => YarnExecutionState::STOPPED,
+                1 => YarnExecutionState::RUNNING,
+                2 => YarnExecutionState::WAITING_FOR_OPTION,
+                3 => YarnExecutionState::WAITING_FOR_COMMAND,
+                _ => return Err(YarnError::new_invalid_snapshot_error("Unknown execution state.")),
+            },
+            4 => snapshot.wait_time = Some(value.as_f64()?),
+            5 => snapshot.stack.push(decode_value(value.as_bytes()?)?),
+            6 => {
+                let mut entry = ProtoReader::new(value.as_bytes()?);
+                let mut option = YarnSnapshotOption { line: String::new(), destination: 0, available: false };
+                while let Some((field, value)) = entry.next_field()? {
+                    match field {
+                        1 => option.line = value.as_string()?,
+                        2 => option.destination = value.as_u64()? as usize,
+                        3 => option.available = value.as_u64()? != 0,
+                        _ => {},
+                    }
+                }
+                snapshot.options.push(option);
+            },
+            7 => {
+                let mut entry = ProtoReader::new(value.as_bytes()?);
+                let (mut name, mut variable) = (None, None);
+                while let Some((field, value)) = entry.next_field()? {
+                    match field {
+                        1 => name = Some(value.as_string()?),
+                        2 => variable = Some(decode_value(value.as_bytes()?)?),
+                        _ => {},
+                    }
+                }
+                match (name, variable) {
+                    (Some(name), Some(variable)) => {
+                        snapshot.variables.insert(name, variable);
+                    },
+                    _ => return Err(YarnError::new_invalid_snapshot_error("A variable is missing its name or value.")),
+                }
+            },
+            8 => {
+                let mut entry = ProtoReader::new(value.as_bytes()?);
+                let mut node = String::new();
+                let mut visits = YarnNodeVisits::default();
+                while let Some((field, value)) = entry.next_field()? {
+                    match field {
+                        1 => node = value.as_string()?,
+                        2 => visits.entered = value.as_u64()? as usize,
+                        3 => visits.completed = value.as_u64()? as usize,
+                        _ => {},
+                    }
+                }
+                snapshot.visits.insert(node, visits);
+            },
+            9 => snapshot.random = value.as_u64()?,
+            11 => snapshot.program = value.as_u64()?,
+            10 => {
+                let mut entry = ProtoReader::new(value.as_bytes()?);
+                let (mut node, mut position) = (None, 0);
+                while let Some((field, value)) = entry.next_field()? {
+                    match field {
+                        1 => node = Some(value.as_string()?),
+                        2 => position = value.as_u64()? as usize,
+                        _ => {},
+                    }
+                }
+                match node {
+                    Some(node) => snapshot.detours.push((node, position)),
+                    None => return Err(YarnError::new_invalid_snapshot_error("A detour is missing the node to return to.")),
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_snapshot() -> YarnSnapshot {
+        let mut variables = YarnVariableMap::new();
+        variables.insert("gold".to_string(), YarnValue::NUMBER(0.1 + 0.2));
+        variables.insert("name".to_string(), YarnValue::STRING("Mae \"the\" \\ \u{e9}\n".to_string()));
+        variables.insert("met".to_string(), YarnValue::BOOL(true));
+        variables.insert("lives".to_string(), YarnValue::INT(-2));
+        variables.insert("price".to_string(), YarnValue::DECIMAL(YarnDecimal::from_units(-1_500_000)));
+        variables.insert("mood".to_string(), YarnValue::ENUM("Mood".to_string(), "Happy".to_string()));
+
+        let mut visits = HashMap::new();
+        visits.insert("start".to_string(), YarnNodeVisits::new(2, 1));
+
+        YarnSnapshot {
+            program: u64::MAX - 1,
+            node: Some("start".to_string()),
+            position: 12,
+            state: YarnExecutionState::WAITING_FOR_OPTION,
+            wait_time: Some(1.5),
+            stack: vec![YarnValue::NUMBER(-3.0)],
+            options: vec![YarnSnapshotOption { line: "line:start-4".to_string(), destination: 17, available: false }],
+            detours: vec![("intro".to_string(), 3), ("start".to_string(), 9)],
+            variables,
+            visits,
+            random: u64::MAX - 7,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_bytes() {
+        let snapshot = test_snapshot();
+        let bytes = snapshot.to_bytes();
+
+        let restored = YarnSnapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(restored, snapshot);
+        assert_eq!(restored.random_state(), u64::MAX - 7);
+        assert_eq!(restored.variables().get("gold").and_then(|gold| gold.as_f64()), Some(0.1 + 0.2));
+        assert!(matches!(restored.variables().get("price"), Some(YarnValue::DECIMAL(price)) if price.units() == -1_500_000));
+        assert_eq!(YarnSnapshot::from_bytes(&bytes[..bytes.len() - 3]).unwrap_err().error_name(), "Invalid Snapshot Error");
+
+        let mut strange = snapshot.clone();
+        strange.variables.insert("gold".to_string(), YarnValue::NUMBER(f64::NAN));
+        strange.stack.push(YarnValue::NUMBER(f64::NEG_INFINITY));
+        let restored = YarnSnapshot::from_bytes(&strange.to_bytes()).unwrap();
+        assert!(restored.variables().get("gold").and_then(|gold| gold.as_f64()).unwrap().is_nan());
+        assert_eq!(restored.stack.last().and_then(|value| value.as_f64()), Some(f64::NEG_INFINITY));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_json() {
+        let snapshot = test_snapshot();
+        let json = snapshot.to_json().unwrap();
+        assert!(json.len() > snapshot.to_bytes().len());
+
+        let restored = YarnSnapshot::from_json(json.as_str()).unwrap();
+        assert_eq!(restored, snapshot);
+        assert_eq!(restored.random_state(), u64::MAX - 7);
+        assert_eq!(restored.variables().get("gold").and_then(|gold| gold.as_f64()), Some(0.1 + 0.2));
+        assert_eq!(restored.variables().get("name"), Some(&YarnValue::STRING("Mae \"the\" \\ \u{e9}\n".to_string())));
+
+        let value = serde_json::from_str::<serde_json::Value>(json.as_str()).unwrap();
+        assert_eq!(value["state"], "WAITING_FOR_OPTION");
+        assert_eq!(value["stack"], serde_json::json!([{"NUMBER": -3.0}]));
+        assert_eq!(value["variables"]["mood"], serde_json::json!({"ENUM": ["Mood", "Happy"]}));
+        assert_eq!(value["detours"], serde_json::json!([["intro", 3], ["start", 9]]));
+
+        let spaced = YarnSnapshot::from_json(" { \"program\": 0, \"node\" : null , \"position\" : 0, \"state\": \"STOPPED\", \"wait_time\": null, \"stack\": [], \"options\": [], \"detours\": [], \"variables\": { \"a\": {\"STRING\": \"\\u00e9\\ud83d\\ude00\"} }, \"visits\": {}, \"random\": 3 } ").unwrap();
+        assert_eq!(spaced.node(), None);
+        assert_eq!(spaced.variables().get("a"), Some(&YarnValue::STRING("\u{e9}\u{1F600}".to_string())));
+
+        assert_eq!(YarnSnapshot::from_json("{\"node\":null}").unwrap_err().error_name(), "Invalid Snapshot Error");
+        assert!(YarnSnapshot::from_json(&json[..json.len() - 1]).is_err());
+
+        let mut strange = snapshot.clone();
+        strange.variables.insert("gold".to_string(), YarnValue::NUMBER(f64::INFINITY));
+        assert_eq!(strange.to_json().unwrap_err().gen_error_message(), "Snapshot Error at (0, 0) : The variable '$gold' is inf, which can not be written as JSON.");
+        strange.variables.insert("gold".to_string(), YarnValue::NUMBER(1.0));
+        strange.stack.push(YarnValue::NUMBER(f64::NAN));
+        assert_eq!(strange.to_json().unwrap_err().error_name(), "Snapshot Error");
+    }
+}
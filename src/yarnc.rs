@@ -0,0 +1,1023 @@
+use std::collections::HashMap;
+
+use crate::{value::{YarnValue, YarnValueType}, error::{YarnError, YarnResult}, program::{YarnProgram, YarnProgramNode, YarnInstruction, YarnOperator, YarnLine, YarnSymbols}};
+
+//==================================================================================================================
+//                       Protobuf Wire Format
+//==================================================================================================================
+
+const WIRE_VARINT : u64 = 0;
+const WIRE_FIXED64 : u64 = 1;
+const WIRE_BYTES : u64 = 2;
+const WIRE_FIXED32 : u64 = 5;
+
+pub(crate) struct ProtoWriter {
+    pub(crate) bytes : Vec<u8>
+}
+
+impl ProtoWriter {
+    pub(crate) fn new() -> ProtoWriter {
+        ProtoWriter { bytes: Vec::new() }
+    }
+
+    fn varint(&mut self, mut value : u64) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8 & 0x7F) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn key(&mut self, field : u64, wire_type : u64) {
+        self.varint((field << 3) | wire_type);
+    }
+
+    pub(crate) fn uint(&mut self, field : u64, value : u64) {
+        self.key(field, WIRE_VARINT);
+        self.varint(value);
+    }
+
+    pub(crate) fn bool(&mut self, field : u64, value : bool) {
+        self.uint(field, value as u64);
+    }
+
+    fn float(&mut self, field : u64, value : f32) {
+        self.key(field, WIRE_FIXED32);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn double(&mut self, field : u64, value : f64) {
+        self.key(field, WIRE_FIXED64);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field : u64, value : &[u8]) {
+        self.key(field, WIRE_BYTES);
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    pub(crate) fn string(&mut self, field : u64, value : &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    pub(crate) fn message(&mut self, field : u64, message : ProtoWriter) {
+        self.bytes(field, &message.bytes);
+    }
+}
+
+pub(crate) enum ProtoValue<'a> {
+    VARINT(u64),
+    FIXED32(u32),
+    FIXED64(u64),
+    BYTES(&'a [u8])
+}
+
+impl <'a> ProtoValue<'a> {
+    pub(crate) fn as_u64(&self) -> YarnResult<u64> {
+        match self {
+            ProtoValue::VARINT(value) => Ok(*value),
+            _ => Err(YarnError::new_invalid_program_error("Expected a varint field.")),
+        }
+    }
+
+    fn as_f32(&self) -> YarnResult<f32> {
+        match self {
+            ProtoValue::FIXED32(value) => Ok(f32::from_bits(*value)),
+            _ => Err(YarnError::new_invalid_program_error("Expected a float field.")),
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> YarnResult<f64> {
+        match self {
+            ProtoValue::FIXED64(value) => Ok(f64::from_bits(*value)),
+            _ => Err(YarnError::new_invalid_program_error("Expected a double field.")),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> YarnResult<&'a [u8]> {
+        match self {
+            ProtoValue::BYTES(value) => Ok(value),
+            _ => Err(YarnError::new_invalid_program_error("Expected a length delimited field.")),
+        }
+    }
+
+    pub(crate) fn as_string(&self) -> YarnResult<String> {
+        match String::from_utf8(self.as_bytes()?.to_vec()) {
+            Ok(value) => Ok(value),
+            Err(_) => Err(YarnError::new_invalid_program_error("A string field is not valid UTF-8.")),
+        }
+    }
+}
+
+pub(crate) struct ProtoReader<'a> {
+    bytes : &'a [u8],
+    position : usize
+}
+
+impl <'a> ProtoReader<'a> {
+    pub(crate) fn new(bytes : &'a [u8]) -> ProtoReader<'a> {
+        ProtoReader { bytes, position: 0 }
+    }
+
+    fn varint(&mut self) -> YarnResult<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = match self.bytes.get(self.position) {
+                Some(byte) => *byte,
+                None => return Err(YarnError::new_invalid_program_error("The file ended inside a varint.")),
+            };
+            self.position += 1;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(YarnError::new_invalid_program_error("A varint is too long."))
+    }
+
+    fn take(&mut self, count : usize) -> YarnResult<&'a [u8]> {
+        if self.bytes.len() - self.position < count {
+            return Err(YarnError::new_invalid_program_error("The file ended inside a field."));
+        }
+        let slice = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        Ok(slice)
+    }
+
+    //Returns the next field number and its value, or None once the message has been read.
+    pub(crate) fn next_field(&mut self) -> YarnResult<Option<(u64, ProtoValue<'a>)>> {
+        if self.position >= self.bytes.len() {
+            return Ok(None);
+        }
+
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            WIRE_VARINT => ProtoValue::VARINT(self.varint()?),
+            WIRE_FIXED64 => ProtoValue::FIXED64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            WIRE_BYTES => {
+                let length = self.varint()? as usize;
+                ProtoValue::BYTES(self.take(length)?)
+            },
+            WIRE_FIXED32 => ProtoValue::FIXED32(u32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+            _ => return Err(YarnError::new_invalid_program_error("Unknown wire type.")),
+        };
+
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+//==================================================================================================================
+//                       Yarn Spinner Messages
+//==================================================================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum YarncOpCode {
+    JUMP_TO = 0,
+    JUMP = 1,
+    RUN_LINE = 2,
+    RUN_COMMAND = 3,
+    ADD_OPTION = 4,
+    SHOW_OPTIONS = 5,
+    PUSH_STRING = 6,
+    PUSH_FLOAT = 7,
+    PUSH_BOOL = 8,
+    PUSH_NULL = 9,
+    JUMP_IF_FALSE = 10,
+    POP = 11,
+    CALL_FUNC = 12,
+    PUSH_VARIABLE = 13,
+    STORE_VARIABLE = 14,
+    STOP = 15,
+    RUN_NODE = 16
+}
+
+const OPCODES : [YarncOpCode; 17] = [
+    YarncOpCode::JUMP_TO, YarncOpCode::JUMP, YarncOpCode::RUN_LINE, YarncOpCode::RUN_COMMAND, YarncOpCode::ADD_OPTION,
+    YarncOpCode::SHOW_OPTIONS, YarncOpCode::PUSH_STRING, YarncOpCode::PUSH_FLOAT, YarncOpCode::PUSH_BOOL, YarncOpCode::PUSH_NULL,
+    YarncOpCode::JUMP_IF_FALSE, YarncOpCode::POP, YarncOpCode::CALL_FUNC, YarncOpCode::PUSH_VARIABLE, YarncOpCode::STORE_VARIABLE,
+    YarncOpCode::STOP, YarncOpCode::RUN_NODE
+];
+
+//The official runtime calls operators as functions named after the type of their first operand, such as Number.Add.
+const OPERATOR_NAMES : [(YarnOperator, &str); 16] = [
+    (YarnOperator::ADD, "Add"),
+    (YarnOperator::SUB, "Minus"),
+    (YarnOperator::MUL, "Multiply"),
+    (YarnOperator::DIV, "Divide"),
+    (YarnOperator::MOD, "Modulo"),
+    (YarnOperator::EQUAL_TOO, "EqualTo"),
+    (YarnOperator::NOT_EQUAL_TOO, "NotEqualTo"),
+    (YarnOperator::LESS_THAN, "LessThan"),
+    (YarnOperator::LESS_THAN_EQ, "LessThanOrEqualTo"),
+    (YarnOperator::GREATER_THAN, "GreaterThan"),
+    (YarnOperator::GREATER_THAN_EQ, "GreaterThanOrEqualTo"),
+    (YarnOperator::NOT, "Not"),
+    (YarnOperator::NEGATIVE, "UnaryMinus"),
+    (YarnOperator::AND, "And"),
+    (YarnOperator::OR, "Or"),
+    (YarnOperator::XOR, "Xor"),
+];
+
+//The operators each of the official types has. A call to anything else under one of these types is an operator we
+//do not know, rather than a function the game registered.
+const OPERATOR_TYPES : [(&str, &[YarnOperator]); 4] = [
+    ("Number", &[YarnOperator::ADD, YarnOperator::SUB, YarnOperator::MUL, YarnOperator::DIV, YarnOperator::MOD, YarnOperator::NEGATIVE,
+        YarnOperator::EQUAL_TOO, YarnOperator::NOT_EQUAL_TOO, YarnOperator::LESS_THAN, YarnOperator::LESS_THAN_EQ, YarnOperator::GREATER_THAN, YarnOperator::GREATER_THAN_EQ]),
+    ("Bool", &[YarnOperator::EQUAL_TOO, YarnOperator::NOT_EQUAL_TOO, YarnOperator::AND, YarnOperator::OR, YarnOperator::XOR, YarnOperator::NOT]),
+    ("String", &[YarnOperator::ADD, YarnOperator::EQUAL_TOO, YarnOperator::NOT_EQUAL_TOO]),
+    ("Enum", &[YarnOperator::EQUAL_TOO, YarnOperator::NOT_EQUAL_TOO]),
+];
+
+//Gives None for a function that is not one of the official operators.
+fn operator_for(name : &str) -> YarnResult<Option<YarnOperator>> {
+    let (type_name, operator_name) = match name.split_once('.') {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    let operators = match OPERATOR_TYPES.iter().find(|(known, _)| *known == type_name) {
+        Some((_, operators)) => operators,
+        None => return Ok(None),
+    };
+
+    match OPERATOR_NAMES.iter().find(|(operator, known)| *known == operator_name && operators.contains(operator)) {
+        Some((operator, _)) => Ok(Some(*operator)),
+        None => Err(YarnError::new_invalid_program_error(format!("'{}' is not an operator this runtime supports.", name).as_str())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum YarncOperand {
+    STRING(String),
+    BOOL(bool),
+    FLOAT(f32)
+}
+
+impl YarncOperand {
+    fn encode(&self) -> ProtoWriter {
+        let mut writer = ProtoWriter::new();
+        match self {
+            YarncOperand::STRING(value) => writer.string(1, value),
+            YarncOperand::BOOL(value) => writer.bool(2, *value),
+            YarncOperand::FLOAT(value) => writer.float(3, *value),
+        }
+        writer
+    }
+
+    fn decode(bytes : &[u8]) -> YarnResult<YarncOperand> {
+        let mut reader = ProtoReader::new(bytes);
+        let mut operand = YarncOperand::STRING(String::new());
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => operand = YarncOperand::STRING(value.as_string()?),
+                2 => operand = YarncOperand::BOOL(value.as_u64()? != 0),
+                3 => operand = YarncOperand::FLOAT(value.as_f32()?),
+                _ => {}
+            }
+        }
+        Ok(operand)
+    }
+
+    fn from_value(value : &YarnValue) -> YarncOperand {
+        match value {
+            YarnValue::STRING(value) => YarncOperand::STRING(value.clone()),
+            YarnValue::BOOL(value) => YarncOperand::BOOL(*value),
+            YarnValue::ENUM(..) => YarncOperand::STRING(value.to_string()),
+            //The yarnc format only has floats, so exact numbers lose their kind here.
+            _ => YarncOperand::FLOAT(value.as_f64().unwrap() as f32),
+        }
+    }
+
+    fn to_value(&self) -> YarnValue {
+        match self {
+            YarncOperand::STRING(value) => YarnValue::STRING(value.clone()),
+            YarncOperand::BOOL(value) => YarnValue::BOOL(*value),
+            YarncOperand::FLOAT(value) => YarnValue::NUMBER(*value as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct YarncInstruction {
+    opcode : YarncOpCode,
+    operands : Vec<YarncOperand>
+}
+
+impl YarncInstruction {
+    fn new(opcode : YarncOpCode, operands : Vec<YarncOperand>) -> YarncInstruction {
+        YarncInstruction { opcode, operands }
+    }
+
+    fn encode(&self) -> ProtoWriter {
+        let mut writer = ProtoWriter::new();
+        if self.opcode != YarncOpCode::JUMP_TO {
+            writer.uint(1, self.opcode as u64);
+        }
+        for operand in self.operands.iter() {
+            writer.message(2, operand.encode());
+        }
+        writer
+    }
+
+    fn decode(bytes : &[u8]) -> YarnResult<YarncInstruction> {
+        let mut reader = ProtoReader::new(bytes);
+        let mut instruction = YarncInstruction::new(YarncOpCode::JUMP_TO, Vec::new());
+        while let Some((field, value)) = reader.next_field()? {
+            match field {
+                1 => {
+                    instruction.opcode = match OPCODES.get(value.as_u64()? as usize) {
+                        Some(opcode) => *opcode,
+                        None => return Err(YarnError::new_invalid_program_error("Unknown opcode.")),
+                    }
+                },
+                2 => instruction.operands.push(YarncOperand::decode(value.as_bytes()?)?),
+                _ => {}
+            }
+        }
+        Ok(instruction)
+    }
+
+    fn string(&self, index : usize) -> YarnResult<String> {
+        match self.operands.get(index) {
+            Some(YarncOperand::STRING(value)) => Ok(value.clone()),
+            _ => Err(YarnError::new_invalid_program_error(format!("{:?} expects a string operand.", self.opcode).as_str())),
+        }
+    }
+
+    fn float(&self, index : usize) -> YarnResult<f32> {
+        match self.operands.get(index) {
+            Some(YarncOperand::FLOAT(value)) => Ok(*value),
+            None => Ok(0.0),
+            _ => Err(YarnError::new_invalid_program_error(format!("{:?} expects a number operand.", self.opcode).as_str())),
+        }
+    }
+
+    fn bool(&self, index : usize) -> YarnResult<bool> {
+        match self.operands.get(index) {
+            Some(YarncOperand::BOOL(value)) => Ok(*value),
+            None => Ok(false),
+            _ => Err(YarnError::new_invalid_program_error(format!("{:?} expects a bool operand.", self.opcode).as_str())),
+        }
+    }
+}
+
+fn type_name(value_type : &YarnValueType) -> &'static str {
+    match value_type {
+        YarnValueType::STRING | YarnValueType::ENUM(_) => "String",
+        YarnValueType::BOOL => "Bool",
+        YarnValueType::NUMBER | YarnValueType::ANY => "Number",
+    }
+}
+
+//==================================================================================================================
+//                       Export
+//==================================================================================================================
+
+//The official instructions leave conditions and stored values on the stack and pop them afterwards, so
+//our instructions expand into a few of theirs. Jumps that have to pop first go through a small block after the node.
+fn export_node(node : &YarnProgramNode, program : &YarnProgram) -> (Vec<YarncInstruction>, HashMap<String, usize>) {
+    let mut starts = Vec::new();
+    let mut length = 0;
+    for instruction in node.instructions().iter() {
+        starts.push(length);
+        length += match instruction {
+            YarnInstruction::STORE(_) | YarnInstruction::CALL(_, _) | YarnInstruction::OPERATOR(_) => 2,
+            YarnInstruction::JUMP_IF_FALSE(_) | YarnInstruction::SHOW_OPTIONS | YarnInstruction::RUN_NODE(_) => 2,
+            _ => 1
+        };
+    }
+    starts.push(length);
+
+    let mut instructions = Vec::new();
+    let mut trampolines = Vec::new();
+    let mut labels = HashMap::new();
+    let mut types : Vec<YarnValueType> = Vec::new();
+
+    let label_for = |labels : &mut HashMap<String, usize>, destination : usize| {
+        let label = format!("L{}", destination);
+        labels.insert(label.clone(), starts[destination]);
+        YarncOperand::STRING(label)
+    };
+
+    for (index, instruction) in node.instructions().iter().enumerate() {
+        match instruction {
+            YarnInstruction::PUSH(value) => {
+                types.push(value.get_type());
+                let opcode = match value {
+                    YarnValue::STRING(_) | YarnValue::ENUM(..) => YarncOpCode::PUSH_STRING,
+                    YarnValue::NUMBER(_) | YarnValue::INT(_) | YarnValue::DECIMAL(_) => YarncOpCode::PUSH_FLOAT,
+                    YarnValue::BOOL(_) => YarncOpCode::PUSH_BOOL,
+                };
+                instructions.push(YarncInstruction::new(opcode, vec![YarncOperand::from_value(value)]));
+            },
+            YarnInstruction::POP => {
+                types.pop();
+                instructions.push(YarncInstruction::new(YarncOpCode::POP, Vec::new()));
+            },
+            YarnInstruction::LOAD(slot) => {
+                let name = program.symbols().variables().name(*slot);
+                types.push(program.initial_values().get(name).map(|value| value.get_type()).unwrap_or(YarnValueType::ANY));
+                instructions.push(YarncInstruction::new(YarncOpCode::PUSH_VARIABLE, vec![YarncOperand::STRING(format!("${}", name))]));
+            },
+            YarnInstruction::STORE(slot) => {
+                let name = program.symbols().variables().name(*slot);
+                types.pop();
+                instructions.push(YarncInstruction::new(YarncOpCode::STORE_VARIABLE, vec![YarncOperand::STRING(format!("${}", name))]));
+                instructions.push(YarncInstruction::new(YarncOpCode::POP, Vec::new()));
+            },
+            YarnInstruction::CALL(slot, argument_count) => {
+                let name = program.symbols().functions().name(*slot).to_string();
+                types.truncate(types.len().saturating_sub(*argument_count));
+                types.push(YarnValueType::ANY);
+                instructions.push(YarncInstruction::new(YarncOpCode::PUSH_FLOAT, vec![YarncOperand::FLOAT(*argument_count as f32)]));
+                instructions.push(YarncInstruction::new(YarncOpCode::CALL_FUNC, vec![YarncOperand::STRING(name)]));
+            },
+            YarnInstruction::OPERATOR(operator) => {
+                let operands = types.split_off(types.len().saturating_sub(operator.operand_count()));
+                let operand_type = operands.first().cloned().unwrap_or(YarnValueType::ANY);
+                types.push(match operator {
+                    YarnOperator::ADD | YarnOperator::SUB | YarnOperator::MUL | YarnOperator::DIV | YarnOperator::MOD | YarnOperator::NEGATIVE => operand_type.clone(),
+                    _ => YarnValueType::BOOL
+                });
+
+                //Logic only works on bools, so it is named after them even when the operand's type is not known.
+                let operand_type = match operator {
+                    YarnOperator::NOT | YarnOperator::AND | YarnOperator::OR | YarnOperator::XOR => YarnValueType::BOOL,
+                    _ => operand_type
+                };
+                let name = OPERATOR_NAMES.iter().find(|(op, _)| op == operator).unwrap().1;
+                instructions.push(YarncInstruction::new(YarncOpCode::PUSH_FLOAT, vec![YarncOperand::FLOAT(operator.operand_count() as f32)]));
+                instructions.push(YarncInstruction::new(YarncOpCode::CALL_FUNC, vec![YarncOperand::STRING(format!("{}.{}", type_name(&operand_type), name))]));
+            },
+            YarnInstruction::JUMP_TO(destination) => {
+                let label = label_for(&mut labels, *destination);
+                instructions.push(YarncInstruction::new(YarncOpCode::JUMP_TO, vec![label]));
+            },
+            YarnInstruction::JUMP_IF_FALSE(destination) => {
+                types.pop();
+                let label = format!("L{}_false", index);
+                instructions.push(YarncInstruction::new(YarncOpCode::JUMP_IF_FALSE, vec![YarncOperand::STRING(label.clone())]));
+                instructions.push(YarncInstruction::new(YarncOpCode::POP, Vec::new()));
+                trampolines.push((label, *destination));
+            },
+            YarnInstruction::RUN_LINE(id) => {
+                instructions.push(YarncInstruction::new(YarncOpCode::RUN_LINE, vec![YarncOperand::STRING(id.clone()), YarncOperand::FLOAT(0.0)]));
+            },
+            YarnInstruction::RUN_COMMAND(text, expression_count) => {
+                types.truncate(types.len().saturating_sub(*expression_count));
+                instructions.push(YarncInstruction::new(YarncOpCode::RUN_COMMAND, vec![YarncOperand::STRING(text.clone()), YarncOperand::FLOAT(*expression_count as f32)]));
+            },
+            YarnInstruction::ADD_OPTION(id, destination, has_condition) => {
+                if *has_condition {
+                    types.pop();
+                }
+                let label = format!("L{}_option", index);
+                instructions.push(YarncInstruction::new(YarncOpCode::ADD_OPTION, vec![
+                    YarncOperand::STRING(id.clone()),
+                    YarncOperand::STRING(label.clone()),
+                    YarncOperand::FLOAT(0.0),
+                    YarncOperand::BOOL(*has_condition)
+                ]));
+                trampolines.push((label, *destination));
+            },
+            YarnInstruction::SHOW_OPTIONS => {
+                instructions.push(YarncInstruction::new(YarncOpCode::SHOW_OPTIONS, Vec::new()));
+                instructions.push(YarncInstruction::new(YarncOpCode::JUMP, Vec::new()));
+            },
+            YarnInstruction::RUN_NODE(title) => {
+                instructions.push(YarncInstruction::new(YarncOpCode::PUSH_STRING, vec![YarncOperand::STRING(title.clone())]));
+                instructions.push(YarncInstruction::new(YarncOpCode::RUN_NODE, Vec::new()));
+            },
+            //The format has no detours, so they are written as the command they were made from and read back from it.
+            //Returning is the same as stopping when there is nowhere to return to.
+            YarnInstruction::DETOUR(title) => {
+                instructions.push(YarncInstruction::new(YarncOpCode::RUN_COMMAND, vec![YarncOperand::STRING(format!("detour {}", title)), YarncOperand::FLOAT(0.0)]));
+            },
+            YarnInstruction::RETURN | YarnInstruction::STOP => {
+                instructions.push(YarncInstruction::new(YarncOpCode::STOP, Vec::new()));
+            },
+        }
+    }
+
+    for (label, destination) in trampolines {
+        labels.insert(label, instructions.len());
+        let target = label_for(&mut labels, destination);
+        instructions.push(YarncInstruction::new(YarncOpCode::POP, Vec::new()));
+        instructions.push(YarncInstruction::new(YarncOpCode::JUMP_TO, vec![target]));
+    }
+
+    (instructions, labels)
+}
+
+impl YarnProgram {
+    pub fn to_yarnc(&self) -> Vec<u8> {
+        let mut program = ProtoWriter::new();
+
+        let mut titles = self.nodes().keys().collect::<Vec<&String>>();
+        titles.sort();
+
+        for title in titles {
+            let node = self.node(title).unwrap();
+            let (instructions, labels) = export_node(node, self);
+
+            let mut message = ProtoWriter::new();
+            message.string(1, title);
+            for instruction in instructions.iter() {
+                message.message(2, instruction.encode());
+            }
+
+            let mut labels = labels.into_iter().collect::<Vec<(String, usize)>>();
+            labels.sort();
+            for (label, index) in labels {
+                let mut entry = ProtoWriter::new();
+                entry.string(1, label.as_str());
+                entry.uint(2, index as u64);
+                message.message(3, entry);
+            }
+
+            if let Some(tags) = node.headers().get("tags") {
+                for tag in tags.split_whitespace() {
+                    message.string(4, tag);
+                }
+            }
+
+            let mut headers = node.headers().iter().collect::<Vec<(&String, &String)>>();
+            headers.sort();
+            for (key, value) in headers {
+                let mut header = ProtoWriter::new();
+                header.string(1, key);
+                header.string(2, value);
+                message.message(6, header);
+            }
+
+            let mut entry = ProtoWriter::new();
+            entry.string(1, title);
+            entry.message(2, message);
+            program.message(2, entry);
+        }
+
+        let mut variables = self.initial_values().iter().collect::<Vec<(&String, &YarnValue)>>();
+        variables.sort_by(|lhs, rhs| lhs.0.cmp(rhs.0));
+        for (name, value) in variables {
+            let mut entry = ProtoWriter::new();
+            entry.string(1, format!("${}", name).as_str());
+            entry.message(2, YarncOperand::from_value(value).encode());
+            program.message(3, entry);
+        }
+
+        program.bytes
+    }
+
+    //The string table the official tooling writes next to the .yarnc file. Speakers are part of the text there.
+    pub fn string_table_csv(&self) -> String {
+        let nodes = self.line_nodes();
+        let mut csv = String::from("id,text,file,node,lineNumber,lock,comment\n");
+        for line in self.sorted_lines() {
+            let text = match line.speaker() {
+                Some(speaker) => format!("{}: {}", speaker, line.text()),
+                None => line.text().to_string(),
+            };
+            let node = nodes.get(line.id()).map(|node| node.as_str()).unwrap_or("");
+            push_csv_row(&mut csv, &[line.id(), text.as_str(), "", node, "", "", ""]);
+        }
+        csv
+    }
+
+    pub fn metadata_csv(&self) -> String {
+        let nodes = self.line_nodes();
+        let mut csv = String::from("id,node,lineNumber,tags\n");
+        for line in self.sorted_lines().into_iter().filter(|line| !line.tags().is_empty()) {
+            let node = nodes.get(line.id()).map(|node| node.as_str()).unwrap_or("");
+            let mut row = vec![line.id(), node, ""];
+            row.extend(line.tags().iter().map(|tag| tag.as_str()));
+            push_csv_row(&mut csv, &row);
+        }
+        csv
+    }
+
+    fn sorted_lines(&self) -> Vec<&YarnLine> {
+        let mut lines = self.lines().values().collect::<Vec<&YarnLine>>();
+        lines.sort_by(|lhs, rhs| lhs.id().cmp(rhs.id()));
+        lines
+    }
+
+    fn line_nodes(&self) -> HashMap<String, String> {
+        let mut nodes = HashMap::new();
+        for node in self.nodes().values() {
+            for instruction in node.instructions().iter() {
+                match instruction {
+                    YarnInstruction::RUN_LINE(id) | YarnInstruction::ADD_OPTION(id, _, _) => {
+                        nodes.insert(id.clone(), node.title().to_string());
+                    },
+                    _ => {}
+                }
+            }
+        }
+        nodes
+    }
+}
+
+//==================================================================================================================
+//                       Import
+//==================================================================================================================
+
+fn import_node(title : &str, headers : HashMap<String, String>, instructions : &[YarncInstruction], labels : &HashMap<String, usize>, symbols : &mut YarnSymbols) -> YarnResult<YarnProgramNode> {
+    let mut node = YarnProgramNode::new(title, headers);
+    let mut mapping = vec![0; instructions.len() + 1];
+    let mut jumps = Vec::new();
+
+    let label = |name : String| match labels.get(&name) {
+        Some(index) if *index <= instructions.len() => Ok(*index),
+        Some(index) => Err(YarnError::new_invalid_program_error(format!("The label '{}' points past the end of node '{}' to {}.", name, title, index).as_str())),
+        None => Err(YarnError::new_invalid_program_error(format!("The label '{}' does not exist in node '{}'.", name, title).as_str())),
+    };
+
+    //Our jumps pop their condition and our options jump straight to their block, so the pop the official
+    //compiler puts at the start of those blocks is skipped.
+    let skip_pop = |index : usize| match instructions.get(index) {
+        Some(instruction) if instruction.opcode == YarncOpCode::POP => index + 1,
+        _ => index,
+    };
+
+    let mut index = 0;
+    while index < instructions.len() {
+        let instruction = &instructions[index];
+        let next = instructions.get(index + 1).map(|next| next.opcode);
+        let unsupported = || Err(YarnError::new_invalid_program_error(format!("{:?} is not supported here in node '{}'.", instruction.opcode, title).as_str()));
+
+        let (converted, consumed) = match instruction.opcode {
+            YarncOpCode::PUSH_STRING if next == Some(YarncOpCode::RUN_NODE) => (YarnInstruction::RUN_NODE(instruction.string(0)?), 2),
+            YarncOpCode::PUSH_FLOAT if next == Some(YarncOpCode::CALL_FUNC) => {
+                let argument_count = instruction.float(0)? as usize;
+                let name = instructions[index + 1].string(0)?;
+                match operator_for(name.as_str())? {
+                    Some(operator) => (YarnInstruction::OPERATOR(operator), 2),
+                    None => (YarnInstruction::CALL(symbols.function_slot(name.as_str()), argument_count), 2),
+                }
+            },
+            YarncOpCode::PUSH_STRING | YarncOpCode::PUSH_FLOAT | YarncOpCode::PUSH_BOOL => {
+                match instruction.operands.first() {
+                    Some(operand) => (YarnInstruction::PUSH(operand.to_value()), 1),
+                    None => return unsupported(),
+                }
+            },
+            YarncOpCode::PUSH_VARIABLE => (YarnInstruction::LOAD(symbols.variable_slot(instruction.string(0)?.trim_start_matches('$'))), 1),
+            YarncOpCode::STORE_VARIABLE if next == Some(YarncOpCode::POP) => (YarnInstruction::STORE(symbols.variable_slot(instruction.string(0)?.trim_start_matches('$'))), 2),
+            YarncOpCode::POP => (YarnInstruction::POP, 1),
+            YarncOpCode::JUMP_TO => {
+                jumps.push((node.len(), label(instruction.string(0)?)?));
+                (YarnInstruction::JUMP_TO(0), 1)
+            },
+            YarncOpCode::JUMP_IF_FALSE if next == Some(YarncOpCode::POP) => {
+                jumps.push((node.len(), skip_pop(label(instruction.string(0)?)?)));
+                (YarnInstruction::JUMP_IF_FALSE(0), 2)
+            },
+            YarncOpCode::RUN_LINE if instruction.float(1)? == 0.0 => (YarnInstruction::RUN_LINE(instruction.string(0)?), 1),
+            YarncOpCode::RUN_COMMAND if instruction.float(1)? == 0.0 && instruction.string(0)?.starts_with("detour ") => {
+                (YarnInstruction::DETOUR(instruction.string(0)?["detour ".len()..].trim().to_string()), 1)
+            },
+            YarncOpCode::RUN_COMMAND => (YarnInstruction::RUN_COMMAND(instruction.string(0)?, instruction.float(1)? as usize), 1),
+            YarncOpCode::ADD_OPTION if instruction.float(2)? == 0.0 => {
+                jumps.push((node.len(), skip_pop(label(instruction.string(1)?)?)));
+                (YarnInstruction::ADD_OPTION(instruction.string(0)?, 0, instruction.bool(3)?), 1)
+            },
+            YarncOpCode::SHOW_OPTIONS if next == Some(YarncOpCode::JUMP) => (YarnInstruction::SHOW_OPTIONS, 2),
+            YarncOpCode::STOP => (YarnInstruction::RETURN, 1),
+            _ => return unsupported(),
+        };
+
+        for slot in mapping.iter_mut().skip(index).take(consumed) {
+            *slot = node.len();
+        }
+        node.emit(converted, 0, 0);
+        index += consumed;
+    }
+    mapping[instructions.len()] = node.len();
+
+    for (index, destination) in jumps {
+        node.patch(index, mapping[destination]);
+    }
+
+    Ok(node)
+}
+
+fn decode_node(bytes : &[u8], symbols : &mut YarnSymbols) -> YarnResult<YarnProgramNode> {
+    let mut reader = ProtoReader::new(bytes);
+    let mut title = String::new();
+    let mut instructions = Vec::new();
+    let mut labels = HashMap::new();
+    let mut tags = Vec::new();
+    let mut headers = HashMap::new();
+
+    while let Some((field, value)) = reader.next_field()? {
+        match field {
+            1 => title = value.as_string()?,
+            2 => instructions.push(YarncInstruction::decode(value.as_bytes()?)?),
+            3 | 6 => {
+                let mut entry = ProtoReader::new(value.as_bytes()?);
+                let mut key = String::new();
+                let mut entry_value = None;
+                while let Some((entry_field, entry_field_value)) = entry.next_field()? {
+                    match entry_field {
+                        1 => key = entry_field_value.as_string()?,
+                        2 => entry_value = Some(entry_field_value),
+                        _ => {}
+                    }
+                }
+
+                if field == 3 {
+                    let index = match entry_value { Some(value) => value.as_u64()? as usize, None => 0 };
+                    labels.insert(key, index);
+                } else {
+                    let value = match entry_value { Some(value) => value.as_string()?, None => String::new() };
+                    headers.insert(key, value);
+                }
+            },
+            4 => tags.push(value.as_string()?),
+            _ => {}
+        }
+    }
+
+    if !tags.is_empty() {
+        headers.entry("tags".to_string()).or_insert(tags.join(" "));
+    }
+    headers.entry("title".to_string()).or_insert(title.clone());
+
+    import_node(title.as_str(), headers, &instructions, &labels, symbols)
+}
+
+impl YarnProgram {
+    pub fn from_yarnc(bytes : &[u8], string_table : &str, metadata : Option<&str>) -> YarnResult<YarnProgram> {
+        let mut program = YarnProgram::new();
+        let mut reader = ProtoReader::new(bytes);
+
+        while let Some((field, value)) = reader.next_field()? {
+            if field != 2 && field != 3 {
+                continue;
+            }
+
+            let mut entry = ProtoReader::new(value.as_bytes()?);
+            let mut key = String::new();
+            let mut entry_value = None;
+            while let Some((entry_field, entry_field_value)) = entry.next_field()? {
+                match entry_field {
+                    1 => key = entry_field_value.as_string()?,
+                    2 => entry_value = Some(entry_field_value.as_bytes()?),
+                    _ => {}
+                }
+            }
+            let entry_value = entry_value.unwrap_or(&[]);
+
+            if field == 2 {
+                let node = decode_node(entry_value, program.symbols_mut())?;
+                program.add_node(node);
+            } else {
+                let value = YarncOperand::decode(entry_value)?.to_value();
+                program.set_initial_value(key.trim_start_matches('$'), value);
+            }
+        }
+
+        let mut tags : HashMap<String, Vec<String>> = HashMap::new();
+        if let Some(metadata) = metadata {
+            let rows = parse_csv(metadata);
+            let columns = csv_columns(&rows, &["id", "tags"])?;
+            for row in rows.iter().skip(1) {
+                let id = row.get(columns[0]).cloned().unwrap_or_default();
+                let line_tags = row.iter().skip(columns[1])
+                    .flat_map(|tag| tag.split_whitespace())
+                    .map(|tag| tag.trim_start_matches('#').to_string())
+                    .collect();
+                tags.insert(id, line_tags);
+            }
+        }
+
+        let rows = parse_csv(string_table);
+        let columns = csv_columns(&rows, &["id", "text"])?;
+        for row in rows.iter().skip(1) {
+            let id = row.get(columns[0]).cloned().unwrap_or_default();
+            let text = row.get(columns[1]).cloned().unwrap_or_default();
+            let (speaker, text) = split_speaker(text.as_str());
+            let line_tags = tags.remove(&id).unwrap_or_default();
+            program.add_line(YarnLine::new(id, speaker, text, line_tags));
+        }
+
+        Ok(program)
+    }
+}
+
+//Matches how lines are parsed from source, where a speaker is the words before the first colon.
+fn split_speaker(text : &str) -> (Option<String>, String) {
+    if let Some((speaker, rest)) = text.split_once(':') {
+        let speaker = speaker.trim();
+        if !speaker.is_empty() && speaker.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ' ') {
+            return (Some(speaker.to_string()), rest.trim().to_string());
+        }
+    }
+    (None, text.to_string())
+}
+
+//==================================================================================================================
+//                       CSV
+//==================================================================================================================
+
+fn push_csv_row(csv : &mut String, fields : &[&str]) {
+    let fields = fields.iter().map(|field| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }).collect::<Vec<String>>();
+
+    csv.push_str(fields.join(",").as_str());
+    csv.push('\n');
+}
+
+fn parse_csv(source : &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {},
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            _ => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+fn csv_columns(rows : &[Vec<String>], names : &[&str]) -> YarnResult<Vec<usize>> {
+    let header = match rows.first() {
+        Some(header) => header,
+        None => return Err(YarnError::new_invalid_program_error("A CSV table is empty.")),
+    };
+
+    names.iter().map(|name| match header.iter().position(|column| column.trim() == *name) {
+        Some(index) => Ok(index),
+        None => Err(YarnError::new_invalid_program_error(format!("A CSV table is missing the '{}' column.", name).as_str())),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{runtime::YarnRuntime, vm::{YarnDialogueEvent, YarnOption}};
+
+    use super::*;
+
+    #[test]
+    fn test_yarnc_round_trip() {
+        let source = concat!(
+            "title: start\n",
+            "tags: intro\n",
+            "---\n",
+            "Mae: Hi, \"friend\"! #happy\n",
+            "<<detour end>>\n",
+            "<<set $gold to $gold + 2>>\n",
+            "<<shake {$gold * 2} \"very hard\">>\n",
+            "<<if $gold > 2>>\n",
+            "    Rich.\n",
+            "<<else>>\n",
+            "    Poor.\n",
+            "<<endif>>\n",
+            "-> Leave\n",
+            "    <<jump end>>\n",
+            "-> Pay <<if $gold > 5>>\n",
+            "===\n",
+            "title: end\n",
+            "---\n",
+            "Bye.\n",
+            "===\n",
+        );
+
+        let mut runtime = YarnRuntime::new(source).with_variable("gold", YarnValue::NUMBER(1.0));
+        let program = runtime.compile().unwrap().clone();
+
+        let bytes = program.to_yarnc();
+        let imported = YarnProgram::from_yarnc(&bytes, &program.string_table_csv(), Some(&program.metadata_csv())).unwrap();
+
+        assert_eq!(imported.lines(), program.lines());
+        assert_eq!(imported.initial_values(), program.initial_values());
+        assert_eq!(imported.node("start").unwrap().headers().get("tags"), Some(&"intro".to_string()));
+
+        let mut original = YarnRuntime::new("").with_program(program);
+        let mut loaded = YarnRuntime::new("").with_program(imported);
+        original.start("start").unwrap();
+        loaded.start("start").unwrap();
+
+        loop {
+            let event = original.next_event().unwrap();
+            match (&event, loaded.next_event().unwrap()) {
+                (YarnDialogueEvent::OPTIONS(expected), YarnDialogueEvent::OPTIONS(options)) => {
+                    let lines = |options : &Vec<YarnOption>| options.iter().map(|option| (option.line().clone(), option.available())).collect::<Vec<_>>();
+                    assert_eq!(lines(&options), lines(expected));
+                },
+                (expected, loaded_event) => assert_eq!(&loaded_event, expected),
+            }
+
+            match event {
+                YarnDialogueEvent::OPTIONS(_) => {
+                    original.select_option(0).unwrap();
+                    loaded.select_option(0).unwrap();
+                },
+                YarnDialogueEvent::DIALOGUE_COMPLETE => break,
+                _ => {}
+            }
+        }
+        assert_eq!(loaded.variable("gold"), Some(YarnValue::NUMBER(3.0)));
+    }
+
+    //Laid out the way the official compiler writes tests/fixtures/operators.yarn, with every operator as a function call.
+    #[test]
+    fn test_yarnc_official_operators() {
+        let bytes = include_bytes!("../tests/fixtures/operators.yarnc");
+        let lines = include_str!("../tests/fixtures/operators-Lines.csv");
+
+        let program = YarnProgram::from_yarnc(bytes, lines, None).unwrap();
+        let mut runtime = YarnRuntime::new("").with_program(program);
+        runtime.start("Start").unwrap();
+
+        let mut text = Vec::new();
+        loop {
+            match runtime.next_event().unwrap() {
+                YarnDialogueEvent::LINE(line) => text.push(line.text().to_string()),
+                YarnDialogueEvent::DIALOGUE_COMPLETE => break,
+                _ => {}
+            }
+        }
+        assert_eq!(text, vec!["Rich!", "Poor."]);
+        assert_eq!(runtime.variable("rich"), Some(YarnValue::BOOL(true)));
+
+        let replace = |from : &str, to : &str| {
+            let mut changed = bytes.to_vec();
+            let start = changed.windows(from.len()).position(|window| window == from.as_bytes()).unwrap();
+            changed[start..start + to.len()].copy_from_slice(to.as_bytes());
+            YarnProgram::from_yarnc(&changed, lines, None).map(|_| ()).map_err(|error| error.error_message().to_string())
+        };
+        assert_eq!(replace("Number.Modulo", "Number.Remain"), Err("The compiled program could not be read. 'Number.Remain' is not an operator this runtime supports.".to_string()));
+        assert_eq!(replace("Bool.And", "Bool.Add"), Err("The compiled program could not be read. 'Bool.Add' is not an operator this runtime supports.".to_string()));
+        assert_eq!(replace("Number.Modulo", "Weather.Sunny"), Ok(()));
+    }
+
+    #[test]
+    fn test_yarnc_malformed() {
+        let mut node = ProtoWriter::new();
+        node.string(1, "Start");
+        node.message(2, YarncInstruction::new(YarncOpCode::JUMP_TO, vec![YarncOperand::STRING("L0".to_string())]).encode());
+        node.message(2, YarncInstruction::new(YarncOpCode::STOP, Vec::new()).encode());
+        let mut label = ProtoWriter::new();
+        label.string(1, "L0");
+        label.uint(2, 99);
+        node.message(3, label);
+        let mut entry = ProtoWriter::new();
+        entry.string(1, "Start");
+        entry.message(2, node);
+        let mut program = ProtoWriter::new();
+        program.message(2, entry);
+
+        let error = YarnProgram::from_yarnc(&program.bytes, "id,text\n", None).unwrap_err();
+        assert_eq!(error.error_message(), "The compiled program could not be read. The label 'L0' points past the end of node 'Start' to 99.");
+
+        let bytes = include_bytes!("../tests/fixtures/operators.yarnc");
+        let lines = include_str!("../tests/fixtures/operators-Lines.csv");
+        for end in 0..bytes.len() {
+            let _ = YarnProgram::from_yarnc(&bytes[..end], lines, None);
+        }
+        for index in 0..bytes.len() {
+            let mut garbage = bytes.to_vec();
+            garbage[index] = garbage[index].wrapping_add(0x5b);
+            let _ = YarnProgram::from_yarnc(&garbage, lines, None);
+        }
+        assert!(YarnProgram::from_yarnc(&bytes[..bytes.len() / 2], lines, None).is_err());
+        assert!(YarnProgram::from_yarnc(&[0xff; 16], lines, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv("id,text\nline:a,\"Mae: Hi, \"\"you\"\"\"\r\nline:b,\"Two\nlines\"\n");
+        assert_eq!(rows, vec![
+            vec!["id".to_string(), "text".to_string()],
+            vec!["line:a".to_string(), "Mae: Hi, \"you\"".to_string()],
+            vec!["line:b".to_string(), "Two\nlines".to_string()],
+        ]);
+        assert_eq!(split_speaker("Mae: Hi, \"you\""), (Some("Mae".to_string()), "Hi, \"you\"".to_string()));
+    }
+}
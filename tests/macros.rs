@@ -0,0 +1,117 @@
+use std::sync::{Mutex, atomic::{AtomicUsize, Ordering}};
+
+use yarn_spinner_compiler::{yarn_function, yarn_library, yarn_command, YarnEnum, value::{YarnValue, YarnEnum as _}, error::YarnResult, parcer::{YarnFunctionContext, YarnFunctionMap}, runtime::YarnRuntime, vm::YarnDialogueEvent};
+
+#[yarn_function(pure)]
+fn double(value : f64) -> f64 {
+    value * 2.0
+}
+
+#[yarn_function]
+fn greeting(name : Option<&str>, context : &YarnFunctionContext) -> YarnResult<String> {
+    match name {
+        Some(name) => Ok(format!("Hi {}", name)),
+        None => Err(context.error("Nobody to greet.")),
+    }
+}
+
+#[test]
+fn test_yarn_function_outside_the_crate() {
+    let source = concat!(
+        "title: start\n",
+        "---\n",
+        "<<set $gold to double($gold)>>\n",
+        "<<set $text to greeting(\"Mae\")>>\n",
+        "===\n",
+    );
+
+    let mut runtime = YarnRuntime::new(source)
+        .with_variable("gold", YarnValue::NUMBER(1.5))
+        .with_variable("text", YarnValue::STRING(String::new()))
+        .with_typed_function("double", double, double_signature())
+        .with_typed_function("greeting", greeting, greeting_signature());
+    runtime.start("start").unwrap();
+    assert_eq!(runtime.next_event().unwrap(), YarnDialogueEvent::NODE_COMPLETE("start".to_string()));
+    assert_eq!(runtime.variable("gold"), Some(YarnValue::NUMBER(3.0)));
+    assert_eq!(runtime.variable("text"), Some(YarnValue::STRING("Hi Mae".to_string())));
+    assert!(double_signature().is_pure());
+}
+
+struct Counter {
+    count : AtomicUsize
+}
+
+#[yarn_library(namespace = "counter")]
+impl Counter {
+    pub fn next(&self) -> f64 {
+        (self.count.fetch_add(1, Ordering::SeqCst) + 1) as f64
+    }
+
+    #[yarn_function(pure)]
+    pub fn start() -> f64 {
+        10.0
+    }
+}
+
+#[test]
+fn test_yarn_library_outside_the_crate() {
+    let mut functions = YarnFunctionMap::new();
+    Counter { count : AtomicUsize::new(0) }.register(&mut functions);
+
+    let source = "title: start\n---\n<<set $count to counter_start() + counter_next()>>\n<<set $count to $count + counter_next()>>\n===\n";
+    let mut runtime = functions.into_iter()
+        .fold(YarnRuntime::new(source), |runtime, (name, function)| runtime.with_registered_function(&name, function))
+        .with_variable("count", YarnValue::NUMBER(0.0));
+    runtime.start("start").unwrap();
+    runtime.next_event().unwrap();
+    assert_eq!(runtime.variable("count"), Some(YarnValue::NUMBER(13.0)));
+}
+
+static GIVEN : Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[yarn_command]
+fn give(who : &str, amount : u32, item : Option<String>) -> YarnResult<()> {
+    GIVEN.lock().unwrap().push(format!("{} {} {}", who, amount, item.unwrap_or("gold".to_string())));
+    Ok(())
+}
+
+#[test]
+fn test_yarn_command_outside_the_crate() {
+    let source = "title: start\n---\n<<give Mae 3>>\n<<give Bea {$count} apples>>\n<<give Mae lots>>\n===\n";
+    let mut runtime = YarnRuntime::new(source)
+        .with_variable("count", YarnValue::NUMBER(2.0))
+        .with_command("give", give);
+    runtime.start("start").unwrap();
+
+    let error = runtime.next_event().unwrap_err();
+    assert_eq!(error.error_name(), "Command Usage Error");
+    assert_eq!(error.error_message(), format!("The command was given the wrong arguments. Usage: {}", give_usage()));
+    assert_eq!(*GIVEN.lock().unwrap(), vec!["Mae 3 gold", "Bea 2 apples"]);
+}
+
+#[derive(YarnEnum, Debug, PartialEq)]
+enum Weather {
+    Sunny,
+    Rainy,
+}
+
+#[yarn_function]
+fn forecast(today : Weather) -> Weather {
+    match today {
+        Weather::Sunny => Weather::Rainy,
+        Weather::Rainy => Weather::Sunny,
+    }
+}
+
+#[test]
+fn test_yarn_enum_outside_the_crate() {
+    let source = "title: start\n---\n<<if $weather == .Sunny>>\n    <<set $weather to forecast($weather)>>\n<<endif>>\n===\n";
+    let mut runtime = YarnRuntime::new(source)
+        .with_enum::<Weather>()
+        .with_variable("weather", Weather::Sunny.into())
+        .with_typed_function("forecast", forecast, forecast_signature());
+    runtime.start("start").unwrap();
+    runtime.next_event().unwrap();
+    assert_eq!(Weather::try_from(runtime.variable("weather").unwrap()).unwrap(), Weather::Rainy);
+    assert_eq!(Weather::declaration(), "<<enum Weather>>\n    <<case Sunny>>\n    <<case Rainy>>\n<<endenum>>\n");
+}